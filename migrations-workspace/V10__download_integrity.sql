@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS integrity_report (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS integrity_check (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  report INTEGER NOT NULL REFERENCES integrity_report(id) ON DELETE CASCADE,
+  download INTEGER NOT NULL REFERENCES download(id),
+  status TEXT NOT NULL CHECK (status IN ('intact', 'missing', 'altered', 'unverified')),
+  md5 TEXT,
+  sha256 TEXT
+);
+CREATE INDEX IF NOT EXISTS integrity_check_report_idx ON integrity_check (report, status);
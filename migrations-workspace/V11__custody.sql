@@ -0,0 +1,231 @@
+ALTER TABLE annotation ADD COLUMN author TEXT;
+
+CREATE TABLE IF NOT EXISTS process_run (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  process TEXT NOT NULL,
+  task TEXT NOT NULL,
+  started_at TIMESTAMP NOT NULL,
+  finished_at TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS custody_event (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  unit INTEGER NOT NULL REFERENCES unit(id),
+  kind TEXT NOT NULL CHECK (kind IN ('fetched', 'downloaded', 'verification', 'annotation')),
+  data JSON NOT NULL,
+  created_at TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS custody_event_unit_idx ON custody_event (unit);
+
+-- The log starts with the current state of all units. The history of units
+-- before this migration is lost, e.g. only the latest verification state is
+-- known.
+INSERT INTO custody_event (unit, kind, data, created_at)
+SELECT u.id,
+       'fetched',
+       json_object('source', u.source, 'href', u.href, 'fetched_at', u.fetched_at, 'run', NULL),
+       COALESCE(strftime('%Y-%m-%dT%H:%M:%fZ', u.fetched_at), strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+  FROM unit u
+ ORDER BY u.id;
+
+INSERT INTO custody_event (unit, kind, data, created_at)
+SELECT d.unit,
+       'downloaded',
+       json_object(
+         'download', d.id,
+         'id_hash', d.id_hash,
+         'type', d.type,
+         'term', d.term,
+         'location', d.location,
+         'md5', d.md5,
+         'sha256', d.sha256
+       ),
+       COALESCE(strftime('%Y-%m-%dT%H:%M:%fZ', u.fetched_at), strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+  FROM download d
+  JOIN unit u ON u.id = d.unit
+ ORDER BY d.id;
+
+INSERT INTO custody_event (unit, kind, data, created_at)
+SELECT v.unit,
+       'verification',
+       json_object(
+         'investigation', i.slug,
+         'segment', s.slug,
+         'state', json_extract(v.state, '$.value')
+       ),
+       COALESCE(strftime('%Y-%m-%dT%H:%M:%fZ', v.updated_at), strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+  FROM verification v
+  JOIN investigation i ON i.id = v.investigation
+  JOIN segment s ON s.id = v.segment
+ ORDER BY v.id;
+
+INSERT INTO custody_event (unit, kind, data, created_at)
+SELECT v.unit,
+       'annotation',
+       json_object(
+         'investigation', i.slug,
+         'key', a.key,
+         'value', CASE WHEN json_valid(a.value) THEN json(a.value) ELSE a.value END,
+         'name', a.name,
+         'note', a.note,
+         'author', a.author
+       ),
+       COALESCE(strftime('%Y-%m-%dT%H:%M:%fZ', a.updated_at), strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+  FROM annotation a
+  JOIN verification v ON v.id = a.verification
+  JOIN investigation i ON i.id = v.investigation
+ ORDER BY a.id;
+
+-- Custody events can't be changed or removed once they are recorded.
+CREATE TRIGGER IF NOT EXISTS custody_event_bu BEFORE UPDATE ON custody_event BEGIN
+  SELECT RAISE(ABORT, 'custody events are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS custody_event_bd BEFORE DELETE ON custody_event BEGIN
+  SELECT RAISE(ABORT, 'custody events are immutable');
+END;
+
+-- Units that are fetched while a data process runs are attributed to this
+-- process run. Processes run one after another.
+CREATE TRIGGER IF NOT EXISTS custody_unit_ai AFTER INSERT ON unit BEGIN
+  INSERT INTO custody_event (unit, kind, data, created_at)
+  VALUES (
+    new.id,
+    'fetched',
+    json_object(
+      'source', new.source,
+      'href', new.href,
+      'fetched_at', new.fetched_at,
+      'run', json((SELECT json_object('id', r.id, 'process', r.process, 'task', r.task)
+                     FROM process_run r
+                    WHERE r.finished_at IS NULL
+                    ORDER BY r.id DESC
+                    LIMIT 1))
+    ),
+    strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+  );
+END;
+
+CREATE TRIGGER IF NOT EXISTS custody_unit_au AFTER UPDATE OF fetched_at ON unit
+WHEN new.fetched_at IS NOT old.fetched_at BEGIN
+  INSERT INTO custody_event (unit, kind, data, created_at)
+  VALUES (
+    new.id,
+    'fetched',
+    json_object(
+      'source', new.source,
+      'href', new.href,
+      'fetched_at', new.fetched_at,
+      'run', json((SELECT json_object('id', r.id, 'process', r.process, 'task', r.task)
+                     FROM process_run r
+                    WHERE r.finished_at IS NULL
+                    ORDER BY r.id DESC
+                    LIMIT 1))
+    ),
+    strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+  );
+END;
+
+CREATE TRIGGER IF NOT EXISTS custody_download_ai AFTER INSERT ON download BEGIN
+  INSERT INTO custody_event (unit, kind, data, created_at)
+  VALUES (
+    new.unit,
+    'downloaded',
+    json_object(
+      'download', new.id,
+      'id_hash', new.id_hash,
+      'type', new.type,
+      'term', new.term,
+      'location', new.location,
+      'md5', new.md5,
+      'sha256', new.sha256
+    ),
+    strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+  );
+END;
+
+CREATE TRIGGER IF NOT EXISTS custody_download_au AFTER UPDATE ON download
+WHEN new.location IS NOT old.location
+  OR new.md5 IS NOT old.md5
+  OR new.sha256 IS NOT old.sha256 BEGIN
+  INSERT INTO custody_event (unit, kind, data, created_at)
+  VALUES (
+    new.unit,
+    'downloaded',
+    json_object(
+      'download', new.id,
+      'id_hash', new.id_hash,
+      'type', new.type,
+      'term', new.term,
+      'location', new.location,
+      'md5', new.md5,
+      'sha256', new.sha256
+    ),
+    strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+  );
+END;
+
+CREATE TRIGGER IF NOT EXISTS custody_verification_ai AFTER INSERT ON verification BEGIN
+  INSERT INTO custody_event (unit, kind, data, created_at)
+  VALUES (
+    new.unit,
+    'verification',
+    json_object(
+      'investigation', (SELECT slug FROM investigation WHERE id = new.investigation),
+      'segment', (SELECT slug FROM segment WHERE id = new.segment),
+      'state', json_extract(new.state, '$.value')
+    ),
+    strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+  );
+END;
+
+CREATE TRIGGER IF NOT EXISTS custody_verification_au AFTER UPDATE OF state ON verification
+WHEN json_extract(new.state, '$.value') IS NOT json_extract(old.state, '$.value') BEGIN
+  INSERT INTO custody_event (unit, kind, data, created_at)
+  VALUES (
+    new.unit,
+    'verification',
+    json_object(
+      'investigation', (SELECT slug FROM investigation WHERE id = new.investigation),
+      'segment', (SELECT slug FROM segment WHERE id = new.segment),
+      'state', json_extract(new.state, '$.value')
+    ),
+    strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+  );
+END;
+
+CREATE TRIGGER IF NOT EXISTS custody_annotation_ai AFTER INSERT ON annotation BEGIN
+  INSERT INTO custody_event (unit, kind, data, created_at)
+  SELECT v.unit,
+         'annotation',
+         json_object(
+           'investigation', i.slug,
+           'key', new.key,
+           'value', CASE WHEN json_valid(new.value) THEN json(new.value) ELSE new.value END,
+           'name', new.name,
+           'note', new.note,
+           'author', new.author
+         ),
+         strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+    FROM verification v
+    JOIN investigation i ON i.id = v.investigation
+   WHERE v.id = new.verification;
+END;
+
+CREATE TRIGGER IF NOT EXISTS custody_annotation_au AFTER UPDATE ON annotation BEGIN
+  INSERT INTO custody_event (unit, kind, data, created_at)
+  SELECT v.unit,
+         'annotation',
+         json_object(
+           'investigation', i.slug,
+           'key', new.key,
+           'value', CASE WHEN json_valid(new.value) THEN json(new.value) ELSE new.value END,
+           'name', new.name,
+           'note', new.note,
+           'author', new.author
+         ),
+         strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+    FROM verification v
+    JOIN investigation i ON i.id = v.investigation
+   WHERE v.id = new.verification;
+END;
@@ -0,0 +1,16 @@
+-- Every custody event is sealed by a link that chains it to the previous
+-- event. SQLite can't compute SHA256 digests, the links are appended by
+-- Ncube after events are recorded.
+CREATE TABLE IF NOT EXISTS custody_link (
+  event INTEGER PRIMARY KEY,
+  hash TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL
+);
+
+CREATE TRIGGER IF NOT EXISTS custody_link_bu BEFORE UPDATE ON custody_link BEGIN
+  SELECT RAISE(ABORT, 'custody links are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS custody_link_bd BEFORE DELETE ON custody_link BEGIN
+  SELECT RAISE(ABORT, 'custody links are immutable');
+END;
@@ -0,0 +1,7 @@
+-- The metadata that is embedded in downloaded images and videos. It is
+-- extracted once, when the metadata of a download is requested first.
+CREATE TABLE IF NOT EXISTS download_metadata (
+  download INTEGER PRIMARY KEY REFERENCES download(id),
+  metadata JSON NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
@@ -0,0 +1,25 @@
+-- Every change to the process of a methodology creates a new version of it.
+-- Versions are immutable, investigations pin the version they started with so
+-- that the verifications of running investigations keep a process that knows
+-- their states.
+CREATE TABLE IF NOT EXISTS methodology_version (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  methodology INTEGER NOT NULL REFERENCES methodology(id),
+  version INTEGER NOT NULL,
+  process JSON NOT NULL,
+  initial_state JSON NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  UNIQUE (methodology, version)
+);
+CREATE INDEX IF NOT EXISTS methodology_version_methodology_idx ON methodology_version (methodology);
+
+CREATE TRIGGER IF NOT EXISTS methodology_version_bu BEFORE UPDATE ON methodology_version BEGIN
+  SELECT RAISE(ABORT, 'methodology versions are immutable');
+END;
+
+ALTER TABLE methodology ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE investigation ADD COLUMN methodology_version INTEGER NOT NULL DEFAULT 1;
+
+INSERT INTO methodology_version (methodology, version, process, initial_state, created_at)
+SELECT id, 1, process, initial_state, updated_at
+  FROM methodology;
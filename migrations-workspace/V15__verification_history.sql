@@ -0,0 +1,39 @@
+-- A verification only keeps the current state of a unit. Every transition is
+-- recorded in the history of the verification, together with the account that
+-- made it. The history is append only, it is removed only with its
+-- verification.
+CREATE TABLE IF NOT EXISTS verification_history (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  verification INTEGER NOT NULL REFERENCES verification(id),
+  from_state TEXT,
+  to_state TEXT NOT NULL,
+  event TEXT NOT NULL,
+  author TEXT,
+  note TEXT,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS verification_history_verification_idx ON verification_history (verification);
+
+CREATE TRIGGER IF NOT EXISTS verification_history_bu BEFORE UPDATE ON verification_history BEGIN
+  SELECT RAISE(ABORT, 'the history of verifications is append only');
+END;
+
+-- New verifications start the history with their initial state.
+CREATE TRIGGER IF NOT EXISTS verification_history_ai AFTER INSERT ON verification BEGIN
+  INSERT INTO verification_history (verification, to_state, event, created_at)
+  VALUES (
+    new.id,
+    json_extract(new.state, '$.value'),
+    COALESCE(json_extract(new.state, '$.event.type'), 'xstate.init'),
+    strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+  );
+END;
+
+-- Earlier transitions are lost, the history of existing verifications starts
+-- with their current state.
+INSERT INTO verification_history (verification, to_state, event, created_at)
+SELECT id,
+       json_extract(state, '$.value'),
+       COALESCE(json_extract(state, '$.event.type'), 'xstate.init'),
+       updated_at
+  FROM verification;
@@ -0,0 +1,44 @@
+-- The history of a verification outlives the verification. Removing a
+-- segment removes the verifications of its units, but their transitions stay
+-- recorded. Verification ids are never reused, the history can't be mixed up
+-- with a later verification.
+DROP TRIGGER IF EXISTS verification_history_ai;
+
+CREATE TABLE IF NOT EXISTS verification_history_kept (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  verification INTEGER NOT NULL,
+  from_state TEXT,
+  to_state TEXT NOT NULL,
+  event TEXT NOT NULL,
+  author TEXT,
+  note TEXT,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+INSERT INTO verification_history_kept (id, verification, from_state, to_state, event, author, note, created_at)
+SELECT id, verification, from_state, to_state, event, author, note, created_at
+  FROM verification_history;
+
+DROP TABLE verification_history;
+ALTER TABLE verification_history_kept RENAME TO verification_history;
+
+CREATE INDEX IF NOT EXISTS verification_history_verification_idx ON verification_history (verification);
+
+CREATE TRIGGER IF NOT EXISTS verification_history_bu BEFORE UPDATE ON verification_history BEGIN
+  SELECT RAISE(ABORT, 'the history of verifications is append only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS verification_history_bd BEFORE DELETE ON verification_history BEGIN
+  SELECT RAISE(ABORT, 'the history of verifications is append only');
+END;
+
+-- New verifications start the history with their initial state.
+CREATE TRIGGER IF NOT EXISTS verification_history_ai AFTER INSERT ON verification BEGIN
+  INSERT INTO verification_history (verification, to_state, event, created_at)
+  VALUES (
+    new.id,
+    json_extract(new.state, '$.value'),
+    COALESCE(json_extract(new.state, '$.event.type'), 'xstate.init'),
+    strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+  );
+END;
@@ -0,0 +1,7 @@
+-- Keyset pagination seeks to the unit after the cursor and reads the units in
+-- the order of the sort key. The keys mirror the expressions of the sort
+-- orders, the unit id breaks ties.
+CREATE INDEX IF NOT EXISTS unit_created_at_idx ON unit (coalesce(created_at, ''), id);
+CREATE INDEX IF NOT EXISTS unit_fetched_at_idx ON unit (fetched_at, id);
+CREATE INDEX IF NOT EXISTS unit_author_idx ON unit (coalesce(author, ''), id);
+CREATE INDEX IF NOT EXISTS unit_source_idx ON unit (source, id);
@@ -0,0 +1 @@
+ALTER TABLE segment ADD COLUMN sort TEXT;
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS duplicate (
+  unit INTEGER PRIMARY KEY REFERENCES unit(id),
+  cluster INTEGER NOT NULL REFERENCES unit(id),
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS duplicate_cluster_idx ON duplicate (cluster);
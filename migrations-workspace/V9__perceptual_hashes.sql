@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS perceptual_hash (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  download INTEGER NOT NULL REFERENCES download(id),
+  frame INTEGER NOT NULL,
+  hash INTEGER NOT NULL,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+  UNIQUE (download, frame)
+);
+CREATE INDEX IF NOT EXISTS perceptual_hash_download_idx ON perceptual_hash (download);
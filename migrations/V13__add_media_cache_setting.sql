@@ -0,0 +1,9 @@
+INSERT INTO setting (
+  name,
+  description,
+  data_type,
+  required,
+  restricted
+) VALUES
+('media_cache_size', 'The size limit in megabytes of the media cache of remote workspaces.', 'alphanumeric', 0, 0)
+ON CONFLICT DO NOTHING;
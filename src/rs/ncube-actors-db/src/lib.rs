@@ -3,7 +3,7 @@ use ncube_actors_common::{message, Actor, ActorError, Context, Handler, Registry
 use ncube_actors_host::{HostActor, RequirePool};
 use ncube_data::{Account, WorkspaceDatabase};
 use ncube_db::{errors::DatabaseError, http, migrations, sqlite, Database, DatabaseCache};
-use ncube_stores::{account_store, process_store, workspace_store, WorkspaceStore};
+use ncube_stores::{account_store, custody_store, process_store, workspace_store, WorkspaceStore};
 use std::result::Result;
 use tracing::{debug, info};
 use url::Url;
@@ -190,6 +190,12 @@ impl Handler<MigrateWorkspace> for DatabaseActor {
                     .await
                     .map_err(|e| DatabaseError::SqlitePool(e))?;
                 migrations::migrate_workspace(&mut **conn)?;
+                // The pool holds a single connection.
+                drop(conn);
+
+                // Migrations record custody events for existing units.
+                let custody_store = custody_store(Database::Sqlite(Box::new(workspace_db)));
+                custody_store.seal().await?;
 
                 let process_store = process_store(db);
                 process_store.bootstrap(&msg.workspace).await?;
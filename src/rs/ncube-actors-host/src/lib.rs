@@ -1,5 +1,6 @@
 use async_trait::async_trait;
 use ncube_actors_common::{message, Actor, ActorError, Context, Handler, Registry};
+use ncube_cache::MediaCache;
 use ncube_data::{ConfigSetting, NcubeConfig};
 use ncube_db::{errors::DatabaseError, sqlite, Database};
 use ncube_fs::expand_tilde;
@@ -7,8 +8,13 @@ use ncube_stores::{config_store, workspace_store, ConfigStore, WorkspaceStore};
 use std::path::PathBuf;
 use std::result::Result;
 
+/// The size limit of the media cache in megabytes if the `media_cache_size`
+/// setting is not set.
+const DEFAULT_MEDIA_CACHE_SIZE: u64 = 2048;
+
 pub struct HostActor {
     db: Database,
+    media_cache: PathBuf,
 }
 
 #[async_trait]
@@ -24,12 +30,13 @@ impl Actor for HostActor {
 impl Registry for HostActor {}
 
 impl HostActor {
-    pub fn new(connection_str: &str) -> Result<Self, ActorError> {
+    pub fn new(connection_str: &str, media_cache: PathBuf) -> Result<Self, ActorError> {
         let db = sqlite::Database::from_str(&connection_str, 1)
             .map_err(|e| ActorError::Database(DatabaseError::SqliteConfig(e)))?;
 
         Ok(Self {
             db: Database::Sqlite(Box::new(db)),
+            media_cache,
         })
     }
 
@@ -101,6 +108,34 @@ impl Handler<WorkspaceRootSetting> for HostActor {
     }
 }
 
+#[message(result = "Result<MediaCache, ActorError>")]
+#[derive(Debug)]
+pub struct MediaCacheSetting;
+
+#[async_trait]
+impl Handler<MediaCacheSetting> for HostActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        _msg: MediaCacheSetting,
+    ) -> Result<MediaCache, ActorError> {
+        let max_size = match self.get_setting("media_cache_size").await? {
+            Some(ConfigSetting {
+                value: Some(value), ..
+            }) => value
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| ActorError::Invalid(format!("invalid media cache size: {}", value)))?,
+            _ => DEFAULT_MEDIA_CACHE_SIZE,
+        };
+
+        Ok(MediaCache::new(
+            self.media_cache.clone(),
+            max_size * 1024 * 1024,
+        ))
+    }
+}
+
 #[message(result = "Result<ConfigSetting, ActorError>")]
 #[derive(Debug)]
 pub struct SecretKeySetting;
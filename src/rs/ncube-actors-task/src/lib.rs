@@ -73,6 +73,10 @@ impl TaskLifecycle {
             TaskKind::SetupWorkspace { .. } => "setup_workspace".to_string(),
             TaskKind::RemoveLocation { .. } => "remove_project".to_string(),
             TaskKind::RunProcess { process_name, .. } => format!("run_{}", process_name),
+            TaskKind::HashMedia { .. } => "hash_media".to_string(),
+            TaskKind::VerifyDownloads { .. } => "verify_downloads".to_string(),
+            TaskKind::GenerateThumbnails { .. } => "generate_thumbnails".to_string(),
+            TaskKind::DetectDuplicates { .. } => "detect_duplicates".to_string(),
         }
     }
 
@@ -1,9 +1,13 @@
 use async_trait::async_trait;
 use ncube_actors_common::{message, Actor, ActorError, Context, Handler, Registry};
-use ncube_actors_db::{DatabaseActor, MigrateWorkspace};
+use ncube_actors_db::{DatabaseActor, LookupDatabase, MigrateWorkspace};
 use ncube_actors_host::{EnableWorkspace, HostActor};
 use ncube_data::{Task, TaskKind};
-use ncube_tasks::{create_workspace, remove_location, run_data_process};
+use ncube_stores::custody_store;
+use ncube_tasks::{
+    create_workspace, detect_duplicates, generate_thumbnails, hash_media, remove_location,
+    run_data_process, verify_downloads,
+};
 use std::fmt::Debug;
 use tokio::sync::mpsc::{self, Receiver, Sender};
 use tracing::info;
@@ -93,13 +97,217 @@ impl TaskRunner {
                             workspace.slug, process_name
                         );
 
+                        let database_actor = DatabaseActor::from_registry().await.unwrap();
+
                         lifecycle.init().await;
 
+                        // Units that are fetched by this process are
+                        // attributed to the process run in their custody log.
+                        let custody_store = match database_actor
+                            .call(LookupDatabase {
+                                workspace: workspace.slug.clone(),
+                            })
+                            .await
+                            .unwrap()
+                        {
+                            Ok(database) => custody_store(database),
+                            Err(e) => {
+                                lifecycle
+                                    .error(&format!("Failed to run process: {}", e))
+                                    .await;
+                                return;
+                            }
+                        };
+                        let run = match custody_store
+                            .start_run(&process_name, &lifecycle.task_id())
+                            .await
+                        {
+                            Ok(run) => run,
+                            Err(e) => {
+                                lifecycle
+                                    .error(&format!("Failed to run process: {}", e))
+                                    .await;
+                                return;
+                            }
+                        };
+
+                        let result =
+                            run_data_process(workspace, &process_name, &mut lifecycle.tx).await;
+
+                        // Units fetched by a failed run are sealed as well.
+                        let finished = custody_store.finish_run(run).await;
+                        let sealed = custody_store.seal().await;
+
+                        if let Err(e) = result {
+                            lifecycle
+                                .error(&format!("Failed to run process: {}", e))
+                                .await;
+                            return;
+                        };
+
+                        if let Err(e) = finished {
+                            lifecycle
+                                .error(&format!("Failed to finish the process run: {}", e))
+                                .await;
+                            return;
+                        };
+
+                        if let Err(e) = sealed {
+                            lifecycle
+                                .error(&format!(
+                                    "The process ran, but sealing the custody chain failed: {}",
+                                    e
+                                ))
+                                .await;
+                            return;
+                        };
+
+                        lifecycle.finish().await;
+                    }
+
+                    TaskKind::HashMedia { workspace } => {
+                        info!(
+                            "Received a request to hash the media of {}.",
+                            workspace.slug
+                        );
+
+                        let database_actor = DatabaseActor::from_registry().await.unwrap();
+
+                        lifecycle.init().await;
+
+                        let database = match database_actor
+                            .call(LookupDatabase {
+                                workspace: workspace.slug.clone(),
+                            })
+                            .await
+                            .unwrap()
+                        {
+                            Ok(database) => database,
+                            Err(e) => {
+                                lifecycle
+                                    .error(&format!("Failed to hash media: {}", e))
+                                    .await;
+                                return;
+                            }
+                        };
+
+                        if let Err(e) = hash_media(workspace, database, &mut lifecycle.tx).await {
+                            lifecycle
+                                .error(&format!("Failed to hash media: {}", e))
+                                .await;
+                            return;
+                        };
+
+                        lifecycle.finish().await;
+                    }
+
+                    TaskKind::VerifyDownloads { workspace } => {
+                        info!(
+                            "Received a request to verify the downloads of {}.",
+                            workspace.slug
+                        );
+
+                        let database_actor = DatabaseActor::from_registry().await.unwrap();
+
+                        lifecycle.init().await;
+
+                        let database = match database_actor
+                            .call(LookupDatabase {
+                                workspace: workspace.slug.clone(),
+                            })
+                            .await
+                            .unwrap()
+                        {
+                            Ok(database) => database,
+                            Err(e) => {
+                                lifecycle
+                                    .error(&format!("Failed to verify downloads: {}", e))
+                                    .await;
+                                return;
+                            }
+                        };
+
+                        if let Err(e) =
+                            verify_downloads(workspace, database, &mut lifecycle.tx).await
+                        {
+                            lifecycle
+                                .error(&format!("Failed to verify downloads: {}", e))
+                                .await;
+                            return;
+                        };
+
+                        lifecycle.finish().await;
+                    }
+
+                    TaskKind::GenerateThumbnails { workspace } => {
+                        info!(
+                            "Received a request to generate the thumbnails of {}.",
+                            workspace.slug
+                        );
+
+                        let database_actor = DatabaseActor::from_registry().await.unwrap();
+
+                        lifecycle.init().await;
+
+                        let database = match database_actor
+                            .call(LookupDatabase {
+                                workspace: workspace.slug.clone(),
+                            })
+                            .await
+                            .unwrap()
+                        {
+                            Ok(database) => database,
+                            Err(e) => {
+                                lifecycle
+                                    .error(&format!("Failed to generate thumbnails: {}", e))
+                                    .await;
+                                return;
+                            }
+                        };
+
+                        if let Err(e) =
+                            generate_thumbnails(workspace, database, &mut lifecycle.tx).await
+                        {
+                            lifecycle
+                                .error(&format!("Failed to generate thumbnails: {}", e))
+                                .await;
+                            return;
+                        };
+
+                        lifecycle.finish().await;
+                    }
+
+                    TaskKind::DetectDuplicates { workspace } => {
+                        info!(
+                            "Received a request to detect the duplicates of {}.",
+                            workspace.slug
+                        );
+
+                        let database_actor = DatabaseActor::from_registry().await.unwrap();
+
+                        lifecycle.init().await;
+
+                        let database = match database_actor
+                            .call(LookupDatabase {
+                                workspace: workspace.slug.clone(),
+                            })
+                            .await
+                            .unwrap()
+                        {
+                            Ok(database) => database,
+                            Err(e) => {
+                                lifecycle
+                                    .error(&format!("Failed to detect duplicates: {}", e))
+                                    .await;
+                                return;
+                            }
+                        };
+
                         if let Err(e) =
-                            run_data_process(workspace, &process_name, &mut lifecycle.tx).await
+                            detect_duplicates(workspace, database, &mut lifecycle.tx).await
                         {
                             lifecycle
-                                .error(&format!("Failed to run process: {}", e.to_string()))
+                                .error(&format!("Failed to detect duplicates: {}", e))
                                 .await;
                             return;
                         };
@@ -153,3 +153,95 @@ impl Handler<RunProcess> for TaskActor {
         self.queue_task(task).await
     }
 }
+
+#[message(result = "Result<(), ActorError>")]
+#[derive(Debug)]
+pub struct HashMedia {
+    pub workspace: Workspace,
+}
+
+#[async_trait]
+impl Handler<HashMedia> for TaskActor {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: HashMedia) -> Result<(), ActorError> {
+        let slug = msg.workspace.slug.clone();
+        let task = Task::new(
+            TaskKind::HashMedia {
+                workspace: msg.workspace,
+            },
+            &slug,
+        );
+        self.queue_task(task).await
+    }
+}
+
+#[message(result = "Result<(), ActorError>")]
+#[derive(Debug)]
+pub struct VerifyDownloads {
+    pub workspace: Workspace,
+}
+
+#[async_trait]
+impl Handler<VerifyDownloads> for TaskActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: VerifyDownloads,
+    ) -> Result<(), ActorError> {
+        let slug = msg.workspace.slug.clone();
+        let task = Task::new(
+            TaskKind::VerifyDownloads {
+                workspace: msg.workspace,
+            },
+            &slug,
+        );
+        self.queue_task(task).await
+    }
+}
+
+#[message(result = "Result<(), ActorError>")]
+#[derive(Debug)]
+pub struct GenerateThumbnails {
+    pub workspace: Workspace,
+}
+
+#[async_trait]
+impl Handler<GenerateThumbnails> for TaskActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: GenerateThumbnails,
+    ) -> Result<(), ActorError> {
+        let slug = msg.workspace.slug.clone();
+        let task = Task::new(
+            TaskKind::GenerateThumbnails {
+                workspace: msg.workspace,
+            },
+            &slug,
+        );
+        self.queue_task(task).await
+    }
+}
+
+#[message(result = "Result<(), ActorError>")]
+#[derive(Debug)]
+pub struct DetectDuplicates {
+    pub workspace: Workspace,
+}
+
+#[async_trait]
+impl Handler<DetectDuplicates> for TaskActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: DetectDuplicates,
+    ) -> Result<(), ActorError> {
+        let slug = msg.workspace.slug.clone();
+        let task = Task::new(
+            TaskKind::DetectDuplicates {
+                workspace: msg.workspace,
+            },
+            &slug,
+        );
+        self.queue_task(task).await
+    }
+}
@@ -3,6 +3,10 @@ use std::fmt::Debug;
 use std::sync::{Mutex, RwLock};
 use tracing::trace;
 
+mod media;
+
+pub use media::{MediaCache, MediaCacheStats};
+
 #[derive(Debug)]
 pub struct GuardedCache<T>(RwLock<HashMap<String, Mutex<T>>>)
 where
@@ -0,0 +1,381 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, trace};
+
+/// Files of downloads that are still in progress carry this extension.
+const PARTIAL_EXTENSION: &str = "part";
+
+/// A partial file that wasn't written to for this long belongs to a download
+/// that was aborted, e.g. because Ncube was stopped while it was running.
+const STALE_PARTIAL: Duration = Duration::from_secs(15 * 60);
+
+fn is_stale(modified: SystemTime) -> bool {
+    modified
+        .elapsed()
+        .is_ok_and(|elapsed| elapsed > STALE_PARTIAL)
+}
+
+fn is_digest(sha256: &str) -> bool {
+    sha256.len() == 64 && sha256.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn touch(path: &Path, time: SystemTime) -> io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .open(path)?
+        .set_modified(time)
+}
+
+fn invalid_digest(sha256: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("invalid SHA256 digest: {}", sha256),
+    )
+}
+
+/// The size of a media cache.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaCacheStats {
+    pub entries: usize,
+    pub size_in_bytes: u64,
+}
+
+/// An on-disk cache of the downloads of remote workspaces. Files are stored
+/// by their SHA256 digest, a file that is downloaded by several units is
+/// cached only once. The modification time of a cached file is the time it was
+/// last used. Once the cache grows beyond its size limit the least recently
+/// used files are evicted.
+#[derive(Debug, Clone)]
+pub struct MediaCache {
+    root: PathBuf,
+    max_size: u64,
+}
+
+impl MediaCache {
+    pub fn new<P: Into<PathBuf>>(root: P, max_size: u64) -> Self {
+        Self {
+            root: root.into(),
+            max_size,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The size limit of the cache in bytes.
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    fn entry_path(&self, sha256: &str) -> Option<PathBuf> {
+        if !is_digest(sha256) {
+            return None;
+        }
+        let sha256 = sha256.to_lowercase();
+
+        Some(self.root.join(&sha256[..2]).join(sha256))
+    }
+
+    /// Look up a cached file by its digest and mark it as used.
+    pub fn get(&self, sha256: &str) -> Option<PathBuf> {
+        let path = self.entry_path(sha256)?;
+
+        match touch(&path, SystemTime::now()) {
+            Ok(_) => {
+                trace!("file {} served from media cache", sha256);
+                Some(path)
+            }
+            Err(_) => {
+                trace!("file {} not in media cache", sha256);
+                None
+            }
+        }
+    }
+
+    /// Create the partial file that a download is written to before it is
+    /// added to the cache. Only a single download of a file can be in
+    /// progress, this is `None` if another download was started already. The
+    /// stale partial file of an aborted download is replaced.
+    pub fn reserve(&self, sha256: &str) -> io::Result<Option<(PathBuf, File)>> {
+        let path = self
+            .entry_path(sha256)
+            .ok_or_else(|| invalid_digest(sha256))?
+            .with_extension(PARTIAL_EXTENSION);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        match fs::metadata(&path) {
+            Ok(metadata) if is_stale(metadata.modified()?) => {
+                debug!("removing stale partial download {:?}", path);
+                fs::remove_file(&path)?;
+            }
+            _ => (),
+        }
+
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => Ok(Some((path, file))),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Move a completed download into the cache and evict the least recently
+    /// used files if the cache grew too large.
+    pub fn insert(&self, sha256: &str, partial: &Path) -> io::Result<PathBuf> {
+        let path = self
+            .entry_path(sha256)
+            .ok_or_else(|| invalid_digest(sha256))?;
+
+        fs::rename(partial, &path)?;
+        touch(&path, SystemTime::now())?;
+        debug!("file {} added to media cache", sha256);
+
+        self.evict()?;
+
+        Ok(path)
+    }
+
+    fn entries(&self) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let mut entries = vec![];
+
+        let dirs = match fs::read_dir(&self.root) {
+            Ok(dirs) => dirs,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(entries),
+            Err(err) => return Err(err),
+        };
+
+        for dir in dirs {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+
+            for entry in fs::read_dir(dir.path())? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                if metadata.is_file() {
+                    entries.push((entry.path(), metadata.len(), metadata.modified()?));
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn is_partial(path: &Path) -> bool {
+        path.extension().is_some_and(|ext| ext == PARTIAL_EXTENSION)
+    }
+
+    /// Remove the least recently used files until the cache fits into its
+    /// size limit. Partial files of downloads in progress are kept, stale
+    /// partial files are removed. Returns the size of the evicted files.
+    pub fn evict(&self) -> io::Result<MediaCacheStats> {
+        let mut entries = vec![];
+
+        for (path, len, modified) in self.entries()? {
+            if !Self::is_partial(&path) {
+                entries.push((path, len, modified));
+            } else if is_stale(modified) {
+                debug!("removing stale partial download {:?}", path);
+                fs::remove_file(&path)?;
+            }
+        }
+
+        entries.sort_by_key(|(_, _, modified)| *modified);
+
+        let mut size: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        let mut evicted = MediaCacheStats::default();
+
+        for (path, len, _) in entries {
+            if size <= self.max_size {
+                break;
+            }
+            fs::remove_file(&path)?;
+            debug!("evicted {:?} from media cache", path);
+
+            size -= len;
+            evicted.entries += 1;
+            evicted.size_in_bytes += len;
+        }
+
+        Ok(evicted)
+    }
+
+    /// The number and the size of all cached files.
+    pub fn stats(&self) -> io::Result<MediaCacheStats> {
+        let entries = self.entries()?;
+        let cached = entries
+            .iter()
+            .filter(|(path, _, _)| !Self::is_partial(path));
+
+        Ok(MediaCacheStats {
+            entries: cached.clone().count(),
+            size_in_bytes: cached.map(|(_, len, _)| len).sum(),
+        })
+    }
+
+    /// Remove all cached files. Partial files of downloads in progress are
+    /// kept. Returns the size of the removed files.
+    pub fn purge(&self) -> io::Result<MediaCacheStats> {
+        let stats = self.stats()?;
+
+        for (path, _, _) in self.entries()? {
+            if !Self::is_partial(&path) {
+                fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::Duration;
+
+    fn digest(n: u8) -> String {
+        format!("{:02x}", n).repeat(32)
+    }
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("ncube-media-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn add(cache: &MediaCache, sha256: &str, len: usize) -> PathBuf {
+        let (partial, mut file) = cache.reserve(sha256).unwrap().unwrap();
+        file.write_all(&vec![0u8; len]).unwrap();
+        cache.insert(sha256, &partial).unwrap()
+    }
+
+    #[test]
+    fn files_are_stored_by_digest() {
+        let dir = cache_dir("digest");
+        let cache = MediaCache::new(&dir, 1024);
+
+        assert_eq!(cache.get(&digest(1)), None);
+        let path = add(&cache, &digest(1), 10);
+        assert_eq!(cache.get(&digest(1)), Some(path));
+        assert_eq!(cache.get("../../etc/passwd"), None);
+        assert!(cache.reserve("../../etc/passwd").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn downloads_are_reserved_once() {
+        let dir = cache_dir("reserve");
+        let cache = MediaCache::new(&dir, 1024);
+
+        let reserved = cache.reserve(&digest(1)).unwrap();
+        assert!(reserved.is_some());
+        assert!(cache.reserve(&digest(1)).unwrap().is_none());
+        assert_eq!(
+            cache.stats().unwrap(),
+            MediaCacheStats {
+                entries: 0,
+                size_in_bytes: 0
+            }
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn least_recently_used_files_are_evicted() {
+        let dir = cache_dir("evict");
+        let cache = MediaCache::new(&dir, 25);
+
+        add(&cache, &digest(1), 10);
+        add(&cache, &digest(2), 10);
+        // Using the first file makes the second one the least recently used.
+        let past = SystemTime::now() - Duration::from_secs(60);
+        touch(&cache.entry_path(&digest(2)).unwrap(), past).unwrap();
+        cache.get(&digest(1)).unwrap();
+        add(&cache, &digest(3), 10);
+
+        assert!(cache.get(&digest(1)).is_some());
+        assert!(cache.get(&digest(2)).is_none());
+        assert!(cache.get(&digest(3)).is_some());
+        assert_eq!(
+            cache.stats().unwrap(),
+            MediaCacheStats {
+                entries: 2,
+                size_in_bytes: 20
+            }
+        );
+
+        assert_eq!(cache.purge().unwrap().entries, 2);
+        assert_eq!(cache.stats().unwrap(), MediaCacheStats::default());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stale_partial_files_are_replaced() {
+        let dir = cache_dir("stale");
+        let cache = MediaCache::new(&dir, 1024);
+
+        let (partial, mut file) = cache.reserve(&digest(1)).unwrap().unwrap();
+        file.write_all(&[0u8; 10]).unwrap();
+        assert!(cache.reserve(&digest(1)).unwrap().is_none());
+
+        // The download was aborted a while ago.
+        let past = SystemTime::now() - STALE_PARTIAL - Duration::from_secs(60);
+        touch(&partial, past).unwrap();
+        let (replaced, _) = cache.reserve(&digest(1)).unwrap().unwrap();
+        assert_eq!(replaced, partial);
+        assert_eq!(fs::metadata(&partial).unwrap().len(), 0);
+
+        // Stale partial files of digests that are never downloaded again are
+        // removed once the cache evicts files.
+        let (stale, _) = cache.reserve(&digest(2)).unwrap().unwrap();
+        touch(&stale, past).unwrap();
+        add(&cache, &digest(3), 10);
+        assert!(!stale.exists());
+        assert!(partial.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn purge_keeps_partial_files() {
+        let dir = cache_dir("purge");
+        let cache = MediaCache::new(&dir, 1024);
+
+        add(&cache, &digest(1), 10);
+        let (partial, mut file) = cache.reserve(&digest(2)).unwrap().unwrap();
+        file.write_all(&[0u8; 10]).unwrap();
+
+        assert_eq!(
+            cache.stats().unwrap(),
+            MediaCacheStats {
+                entries: 1,
+                size_in_bytes: 10
+            }
+        );
+        assert_eq!(
+            cache.purge().unwrap(),
+            MediaCacheStats {
+                entries: 1,
+                size_in_bytes: 10
+            }
+        );
+        assert!(cache.get(&digest(1)).is_none());
+        assert!(partial.exists());
+
+        cache.insert(&digest(2), &partial).unwrap();
+        assert!(cache.get(&digest(2)).is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
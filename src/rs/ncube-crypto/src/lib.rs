@@ -7,10 +7,12 @@ use block_modes::Cbc;
 use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use hmac::{Hmac, Mac};
 use jwt::{RegisteredClaims, SignWithKey, VerifyWithKey};
+use md5::Md5;
 use rand::Rng;
 use secstr::SecVec;
 use sha2::{Digest, Sha256, Sha512};
 use std::fmt::{Display, Formatter};
+use std::io::Read;
 use thiserror::Error;
 
 type Aes256Cbc = Cbc<Aes256, Iso7816>;
@@ -43,6 +45,42 @@ pub fn sha256(key: SecVec<u8>) -> String {
     format!("{:x}", hash)
 }
 
+/// Compute the MD5 and SHA256 digests of a file in a single pass. The
+/// digests are hex encoded in lower case, like the digests of downloads.
+pub fn file_digests<R: Read>(mut reader: R) -> std::io::Result<(String, String)> {
+    let mut md5 = Md5::new();
+    let mut sha256 = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let len = reader.read(&mut buffer)?;
+        if len == 0 {
+            break;
+        }
+        md5.input(&buffer[..len]);
+        sha256.input(&buffer[..len]);
+    }
+
+    Ok((
+        format!("{:x}", md5.result()),
+        format!("{:x}", sha256.result()),
+    ))
+}
+
+/// The link that precedes the first record of a hash chain.
+pub const CHAIN_ROOT: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Append a record to a hash chain. The link of a record is the SHA256 of
+/// the previous link followed by the record, hex encoded in lower case.
+/// Changing, removing or inserting a record changes the links of all records
+/// that follow it.
+pub fn chain_hash(previous: &str, record: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input(previous.as_bytes());
+    hasher.input(record);
+    format!("{:x}", hasher.result())
+}
+
 pub fn gen_secret_key<R: Rng>(rng: R) -> String {
     let key = gen_symmetric_key(rng);
     sha256(key)
@@ -161,6 +199,26 @@ mod tests {
         assert!(claims.not_before.is_some());
     }
 
+    #[test]
+    fn digests_of_files() {
+        let (md5, sha256) = file_digests(&b"abc"[..]).unwrap();
+        assert_eq!(md5, "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn chaining_records() {
+        let first = chain_hash(CHAIN_ROOT, b"abc");
+        assert_eq!(
+            first,
+            "b64374d04ef9c4f39fddb1e0d6be38a0130f6c057fc0f4ee467ea0e18bc758f1"
+        );
+        assert_ne!(chain_hash(&first, b"def"), chain_hash(CHAIN_ROOT, b"def"));
+    }
+
     #[test]
     fn generate_secret_key() {
         let rng = StepRng::new(0, 1);
@@ -5,9 +5,12 @@ use chrono::prelude::{DateTime, Utc};
 use http::StatusCode;
 use serde::{Deserialize, Serialize};
 use slugify::slugify;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::default::Default;
 use std::fmt::Debug;
 use std::fmt::{self, Display};
+use std::str::FromStr;
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
@@ -289,6 +292,25 @@ pub struct Stat {
     pub value: i32,
 }
 
+/// The number of units of a search that share the same value.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i32,
+}
+
+/// Break down the results of a search query by tag, source type, language,
+/// year and month of publication and media type.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Facets {
+    pub tags: Vec<FacetCount>,
+    pub sources: Vec<FacetCount>,
+    pub languages: Vec<FacetCount>,
+    pub years: Vec<FacetCount>,
+    pub months: Vec<FacetCount>,
+    pub media: Vec<FacetCount>,
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "lowercase", tag = "type")]
 pub enum MediaType {
@@ -342,6 +364,449 @@ pub struct Unit {
     pub sources: Vec<Source>,
     #[serde(default)]
     pub tags: Vec<QueryTag>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlights: Option<Highlights>,
+    /// The `bm25` rank of a search result ordered by relevance. Better
+    /// matches have a lower rank.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<f64>,
+}
+
+/// Units that are duplicates of each other, either because they share the
+/// same content or because their texts are nearly identical. A cluster is
+/// identified by its first unit.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct DuplicateCluster {
+    pub id: i32,
+    pub units: Vec<i32>,
+}
+
+/// A downloaded image or video, e.g. one that has no perceptual hashes yet.
+/// `location` is relative to the workspace directory.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct MediaFile {
+    pub download: i32,
+    #[serde(flatten)]
+    pub kind: MediaType,
+    pub location: String,
+}
+
+/// A download of another unit that looks like one of the downloads of a unit.
+/// `similar_to` is the id hash of the download it resembles, and `distance`
+/// the number of bits in which their closest perceptual hashes differ.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct SimilarMedia {
+    pub unit: i32,
+    pub title: Option<String>,
+    pub similar_to: String,
+    pub distance: u32,
+    pub download: Download,
+}
+
+/// A downloaded file together with the digests that were recorded when it was
+/// downloaded.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct DownloadFile {
+    pub download: i32,
+    pub unit: i32,
+    pub id_hash: String,
+    pub location: String,
+    pub md5: Option<String>,
+    pub sha256: Option<String>,
+}
+
+/// The outcome of verifying a single download. Downloads without recorded
+/// digests can't be verified.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum IntegrityStatus {
+    Intact,
+    Missing,
+    Altered,
+    Unverified,
+}
+
+impl Display for IntegrityStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            IntegrityStatus::Intact => "intact",
+            IntegrityStatus::Missing => "missing",
+            IntegrityStatus::Altered => "altered",
+            IntegrityStatus::Unverified => "unverified",
+        };
+
+        write!(f, "{}", status)
+    }
+}
+
+/// The verification of a single download. `md5` and `sha256` are the digests
+/// of the file on disk, they are missing if the file is missing.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct IntegrityCheck {
+    pub download: i32,
+    pub unit: i32,
+    pub id_hash: String,
+    pub location: String,
+    pub status: IntegrityStatus,
+    pub md5: Option<String>,
+    pub sha256: Option<String>,
+}
+
+impl IntegrityCheck {
+    /// Compare the recorded digests of a download to the digests of the file
+    /// on disk. `digests` is `None` if the file is missing.
+    pub fn new(file: DownloadFile, digests: Option<(String, String)>) -> Self {
+        let status = match (&digests, &file.md5, &file.sha256) {
+            (None, _, _) => IntegrityStatus::Missing,
+            (Some(_), None, None) => IntegrityStatus::Unverified,
+            (Some((md5, sha256)), expected_md5, expected_sha256) => {
+                let matches = |expected: &Option<String>, actual: &str| match expected {
+                    Some(expected) => expected.eq_ignore_ascii_case(actual),
+                    None => true,
+                };
+
+                if matches(expected_md5, md5) && matches(expected_sha256, sha256) {
+                    IntegrityStatus::Intact
+                } else {
+                    IntegrityStatus::Altered
+                }
+            }
+        };
+        let (md5, sha256) = match digests {
+            Some((md5, sha256)) => (Some(md5), Some(sha256)),
+            None => (None, None),
+        };
+
+        Self {
+            download: file.download,
+            unit: file.unit,
+            id_hash: file.id_hash,
+            location: file.location,
+            status,
+            md5,
+            sha256,
+        }
+    }
+}
+
+/// The result of verifying all downloads of a workspace. `checks` only
+/// contains the downloads that aren't intact, and is empty when reports are
+/// listed.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct IntegrityReport {
+    pub id: i32,
+    pub created_at: DateTime<Utc>,
+    pub total: i32,
+    pub intact: i32,
+    pub missing: i32,
+    pub altered: i32,
+    pub unverified: i32,
+    #[serde(default)]
+    pub checks: Vec<IntegrityCheck>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CustodyEventKind {
+    Fetched,
+    Downloaded,
+    Verification,
+    Annotation,
+}
+
+impl Display for CustodyEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            CustodyEventKind::Fetched => "fetched",
+            CustodyEventKind::Downloaded => "downloaded",
+            CustodyEventKind::Verification => "verification",
+            CustodyEventKind::Annotation => "annotation",
+        };
+
+        write!(f, "{}", kind)
+    }
+}
+
+/// An entry of the chain-of-custody log of a unit. Events are recorded by the
+/// database whenever a unit is fetched, a file is downloaded, the
+/// verification state of a unit changes or a unit is annotated. They can't be
+/// changed once they are recorded. The contents of `data` depend on the kind
+/// of event.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CustodyEvent {
+    pub id: i32,
+    pub unit: i32,
+    pub kind: CustodyEventKind,
+    pub data: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The ways a custody chain can break. An event is `altered` if it doesn't
+/// match its link, `deleted` if its link remains but the event is gone and
+/// `inserted` if it lacks a link but later events are linked.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ChainBreak {
+    Altered,
+    Deleted,
+    Inserted,
+}
+
+impl Display for ChainBreak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            ChainBreak::Altered => "altered",
+            ChainBreak::Deleted => "deleted",
+            ChainBreak::Inserted => "inserted",
+        };
+
+        write!(f, "{}", reason)
+    }
+}
+
+/// The first custody event that breaks the chain. `expected` is the link
+/// computed from the event and its predecessor, `found` is the link that was
+/// recorded when the event was sealed.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct BrokenLink {
+    pub event: i32,
+    pub reason: ChainBreak,
+    pub expected: Option<String>,
+    pub found: Option<String>,
+}
+
+/// The result of replaying the custody chain of a workspace. `length` is the
+/// number of sealed events up to the first broken link, `unsealed` the
+/// number of events at the end of the log that haven't been sealed yet.
+/// `head` is the last intact link, it changes whenever an earlier event is
+/// altered.
+///
+/// The chain is only tamper-evident relative to a head that is kept outside
+/// of the workspace, whoever can write the database can recompute every link.
+/// `anchored` is whether such a previously recorded head is an intact link of
+/// the chain, or `None` if no head was given.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CustodyChain {
+    pub length: i32,
+    pub unsealed: i32,
+    pub head: Option<String>,
+    pub broken: Option<BrokenLink>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchored: Option<bool>,
+}
+
+/// A unit of an export together with its chain-of-custody log. The
+/// `location` of its downloads is the path of the file in the archive.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ExportUnit {
+    #[serde(flatten)]
+    pub unit: Unit,
+    pub custody: Vec<CustodyEvent>,
+}
+
+/// The manifest of an export of the units of a segment, or of the units of a
+/// segment in an investigation. `missing` lists the downloads that couldn't
+/// be found in the workspace and are not part of the archive.
+///
+/// The custody chain is replayed without an anchor, Ncube doesn't keep one.
+/// Its `head` proves that the chain was unchanged when the export was created
+/// only once it is compared to a head that was recorded outside of the
+/// workspace, e.g. with `ncubectl audit --anchor`.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ExportManifest {
+    pub workspace: String,
+    pub segment: String,
+    pub investigation: Option<String>,
+    pub state: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub custody_chain: CustodyChain,
+    pub units: Vec<ExportUnit>,
+    pub missing: Vec<String>,
+}
+
+/// The formats that units can be exported as. CSV is meant for spreadsheets,
+/// NDJSON has one JSON object per unit and line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Ndjson,
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// The columns of a unit export. In CSV exports lists of media, downloads,
+/// sources, tags, annotations and custody events are flattened into a single
+/// cell, NDJSON exports keep them as lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportColumn {
+    Id,
+    IdHash,
+    ContentHash,
+    Source,
+    UnitId,
+    Title,
+    Description,
+    Body,
+    Author,
+    Href,
+    Language,
+    CreatedAt,
+    FetchedAt,
+    Media,
+    Downloads,
+    Sources,
+    Tags,
+    Annotations,
+    Custody,
+}
+
+impl ExportColumn {
+    pub const ALL: [ExportColumn; 19] = [
+        ExportColumn::Id,
+        ExportColumn::IdHash,
+        ExportColumn::ContentHash,
+        ExportColumn::Source,
+        ExportColumn::UnitId,
+        ExportColumn::Title,
+        ExportColumn::Description,
+        ExportColumn::Body,
+        ExportColumn::Author,
+        ExportColumn::Href,
+        ExportColumn::Language,
+        ExportColumn::CreatedAt,
+        ExportColumn::FetchedAt,
+        ExportColumn::Media,
+        ExportColumn::Downloads,
+        ExportColumn::Sources,
+        ExportColumn::Tags,
+        ExportColumn::Annotations,
+        ExportColumn::Custody,
+    ];
+
+    /// Parse a comma separated list of columns. An empty list selects all
+    /// columns.
+    pub fn parse_list(s: &str) -> Result<Vec<ExportColumn>, InvalidExportColumn> {
+        let columns = s
+            .split(',')
+            .map(str::trim)
+            .filter(|column| !column.is_empty())
+            .map(ExportColumn::from_str)
+            .collect::<Result<Vec<ExportColumn>, InvalidExportColumn>>()?;
+
+        if columns.is_empty() {
+            Ok(ExportColumn::ALL.to_vec())
+        } else {
+            Ok(columns)
+        }
+    }
+}
+
+impl Display for ExportColumn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ExportColumn::Id => "id",
+            ExportColumn::IdHash => "id_hash",
+            ExportColumn::ContentHash => "content_hash",
+            ExportColumn::Source => "source",
+            ExportColumn::UnitId => "unit_id",
+            ExportColumn::Title => "title",
+            ExportColumn::Description => "description",
+            ExportColumn::Body => "body",
+            ExportColumn::Author => "author",
+            ExportColumn::Href => "href",
+            ExportColumn::Language => "language",
+            ExportColumn::CreatedAt => "created_at",
+            ExportColumn::FetchedAt => "fetched_at",
+            ExportColumn::Media => "media",
+            ExportColumn::Downloads => "downloads",
+            ExportColumn::Sources => "sources",
+            ExportColumn::Tags => "tags",
+            ExportColumn::Annotations => "annotations",
+            ExportColumn::Custody => "custody",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidExportColumn(pub String);
+
+impl Display for InvalidExportColumn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Export column `{}` is invalid.", self.0)
+    }
+}
+
+impl std::error::Error for InvalidExportColumn {}
+
+impl FromStr for ExportColumn {
+    type Err = InvalidExportColumn;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ExportColumn::ALL
+            .iter()
+            .find(|column| column.to_string() == s)
+            .copied()
+            .ok_or_else(|| InvalidExportColumn(s.into()))
+    }
+}
+
+/// The format and the columns of a unit export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub columns: Vec<ExportColumn>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::default(),
+            columns: ExportColumn::ALL.to_vec(),
+        }
+    }
+}
+
+impl ExportOptions {
+    /// The columns as a comma separated list.
+    pub fn column_list(&self) -> String {
+        self.columns
+            .iter()
+            .map(|column| column.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+/// A unit labeled with the slug of the workspace it was found in.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct WorkspaceUnit {
+    pub workspace: String,
+    #[serde(flatten)]
+    pub unit: Unit,
+}
+
+/// The passages of a unit that matched a full text search. Matching terms
+/// are wrapped in `<mark>` tags, the surrounding text is HTML escaped. Fields
+/// without a match are left empty.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Highlights {
+    pub body: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -433,6 +898,286 @@ pub struct LoginResponse {
 pub struct SearchResponse<T> {
     pub data: Vec<T>,
     pub total: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<Cursor>,
+}
+
+/// The results of a search across workspaces. Workspaces that failed to
+/// search are listed in `failed_workspaces`, their units are missing from
+/// `data` and `total`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WorkspacesSearchResponse {
+    pub data: Vec<WorkspaceUnit>,
+    pub total: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<WorkspacesCursor>,
+    pub failed_workspaces: Vec<FailedWorkspace>,
+}
+
+/// A workspace that failed to search, and the reason why.
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct FailedWorkspace {
+    pub workspace: String,
+    pub error: String,
+}
+
+/// A position in a search across workspaces. Every workspace is paged by its
+/// own cursor, a workspace without a cursor starts at its first unit.
+/// Workspaces that aren't part of the position have no more units.
+///
+/// Cursors are opaque to clients, they are serialized as a comma separated
+/// list of `<workspace>:<cursor>`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct WorkspacesCursor {
+    pub positions: BTreeMap<String, Option<Cursor>>,
+}
+
+impl Display for WorkspacesCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let positions = self
+            .positions
+            .iter()
+            .map(|(workspace, cursor)| match cursor {
+                Some(cursor) => format!("{}:{}", workspace, cursor),
+                None => format!("{}:", workspace),
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+
+        write!(f, "{}", positions)
+    }
+}
+
+impl FromStr for WorkspacesCursor {
+    type Err = InvalidCursor;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidCursor(s.into());
+        let mut positions = BTreeMap::new();
+
+        for position in s.split(',') {
+            let (workspace, cursor) = position.split_once(':').ok_or_else(invalid)?;
+            if workspace.is_empty() {
+                return Err(invalid());
+            }
+            let cursor = match cursor {
+                "" => None,
+                cursor => Some(cursor.parse::<Cursor>().map_err(|_| invalid())?),
+            };
+            positions.insert(workspace.to_string(), cursor);
+        }
+
+        Ok(Self { positions })
+    }
+}
+
+impl From<WorkspacesCursor> for String {
+    fn from(cursor: WorkspacesCursor) -> Self {
+        cursor.to_string()
+    }
+}
+
+impl TryFrom<String> for WorkspacesCursor {
+    type Error = InvalidCursor;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// The order of a list of units. Lists default to relevance. A list of units
+/// that is not the result of a search has no rank, it is ordered by the time
+/// the units were stored instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Relevance,
+    CreatedAtAsc,
+    CreatedAtDesc,
+    FetchedAtAsc,
+    FetchedAtDesc,
+    Author,
+    Source,
+}
+
+impl Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SortOrder::Relevance => "relevance",
+            SortOrder::CreatedAtAsc => "created_at_asc",
+            SortOrder::CreatedAtDesc => "created_at_desc",
+            SortOrder::FetchedAtAsc => "fetched_at_asc",
+            SortOrder::FetchedAtDesc => "fetched_at_desc",
+            SortOrder::Author => "author",
+            SortOrder::Source => "source",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// The sort key of the item a cursor points to. Lists that are ordered by
+/// relevance use the rank as key, all other orders a text column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CursorKey {
+    Rank(f64),
+    Text(String),
+}
+
+/// A position in a list of results. Lists are ordered by a sort key and id,
+/// and a cursor points to the last item of a page. The next page starts right
+/// after it, no matter how many items were added to the list in the meantime.
+/// Lists that have no rank, e.g. a list of all units, use a rank of `0`.
+///
+/// Cursors are opaque to clients, they are serialized as hex strings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Cursor {
+    pub key: CursorKey,
+    pub id: i32,
+}
+
+impl Cursor {
+    pub fn rank(rank: f64, id: i32) -> Self {
+        Self {
+            key: CursorKey::Rank(rank),
+            id,
+        }
+    }
+
+    pub fn text(text: &str, id: i32) -> Self {
+        Self {
+            key: CursorKey::Text(text.to_string()),
+            id,
+        }
+    }
+
+    /// A cursor can only continue a list in the same sort order that it was
+    /// created for.
+    pub fn matches(&self, sort: &SortOrder) -> bool {
+        match self.key {
+            CursorKey::Rank(_) => *sort == SortOrder::Relevance,
+            CursorKey::Text(_) => *sort != SortOrder::Relevance,
+        }
+    }
+}
+
+impl Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.key {
+            CursorKey::Rank(rank) => write!(f, "{:016x}{:08x}", rank.to_bits(), self.id as u32),
+            CursorKey::Text(text) => {
+                write!(f, "t{:08x}", self.id as u32)?;
+                for byte in text.bytes() {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidCursor(pub String);
+
+impl Display for InvalidCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cursor `{}` is invalid.", self.0)
+    }
+}
+
+impl std::error::Error for InvalidCursor {}
+
+impl FromStr for Cursor {
+    type Err = InvalidCursor;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidCursor(s.into());
+
+        if !s.is_ascii() {
+            return Err(invalid());
+        }
+
+        if let Some(s) = s.strip_prefix('t') {
+            if s.len() < 8 || s.len() % 2 != 0 {
+                return Err(invalid());
+            }
+
+            let id = u32::from_str_radix(&s[..8], 16).map_err(|_| invalid())?;
+            let bytes = (8..s.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| invalid())?;
+            let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+
+            return Ok(Self::text(&text, id as i32));
+        }
+
+        if s.len() != 24 {
+            return Err(invalid());
+        }
+
+        let rank = u64::from_str_radix(&s[..16], 16).map_err(|_| invalid())?;
+        let id = u32::from_str_radix(&s[16..], 16).map_err(|_| invalid())?;
+
+        Ok(Self::rank(f64::from_bits(rank), id as i32))
+    }
+}
+
+impl From<Cursor> for String {
+    fn from(cursor: Cursor) -> Self {
+        cursor.to_string()
+    }
+}
+
+impl TryFrom<String> for Cursor {
+    type Error = InvalidCursor;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Request a page of a list. Pages start after the `after` cursor, or at the
+/// beginning of the list if there is none. The offset based `page` is only
+/// kept for compatibility with older clients, and is ignored if a cursor is
+/// given. Offsets get slower the further into a list they point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageRequest {
+    pub after: Option<Cursor>,
+    pub page: i32,
+    pub size: i32,
+}
+
+impl PageRequest {
+    /// The number of items to skip. It is always `0` when paging by cursor.
+    pub fn offset(&self) -> i32 {
+        match &self.after {
+            Some(_) => 0,
+            None => self.page * self.size,
+        }
+    }
+
+    /// The cursor for the page that follows a page of `len` items, `last` is
+    /// the cursor to the last item of the page. A page that is not full is the
+    /// last page.
+    pub fn next(&self, len: usize, last: Option<Cursor>) -> Option<Cursor> {
+        if len as i32 >= self.size {
+            last
+        } else {
+            None
+        }
+    }
+}
+
+/// A single page of a list, and the cursor to the page after it.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub next: Option<Cursor>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -467,6 +1212,8 @@ pub struct ErrorResponse {
     pub status: Status,
     pub code: u16,
     pub errors: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<QueryError>,
 }
 
 impl ErrorResponse {
@@ -475,8 +1222,23 @@ impl ErrorResponse {
             status: Status::Error,
             code: code.as_u16(),
             errors: errors.into(),
+            query: None,
         }
     }
+
+    pub fn with_query(mut self, query: QueryError) -> Self {
+        self.query = Some(query);
+        self
+    }
+}
+
+/// The details of a search query that failed to parse. `position` counts
+/// the characters of the query up to the error, `expected` names what was
+/// expected at that position.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryError {
+    pub position: usize,
+    pub expected: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -495,6 +1257,7 @@ pub struct Segment {
     pub slug: String,
     pub title: String,
     pub query: String,
+    pub sort: Option<SortOrder>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -503,6 +1266,8 @@ pub struct Segment {
 pub struct SegmentRequest {
     pub title: String,
     pub query: String,
+    #[serde(default)]
+    pub sort: Option<SortOrder>,
 }
 
 impl SegmentRequest {
@@ -622,6 +1387,18 @@ pub enum TaskKind {
         workspace: Workspace,
         process_name: String,
     },
+    HashMedia {
+        workspace: Workspace,
+    },
+    VerifyDownloads {
+        workspace: Workspace,
+    },
+    GenerateThumbnails {
+        workspace: Workspace,
+    },
+    DetectDuplicates {
+        workspace: Workspace,
+    },
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -667,6 +1444,7 @@ impl Task {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MethodologyReq {
     pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
     pub process: serde_json::Value,
 }
@@ -675,6 +1453,75 @@ impl MethodologyReq {
     pub fn slug(&self) -> String {
         slugify!(&self.title)
     }
+
+    /// Parse a methodology file. Files that start with `{` are read as JSON,
+    /// everything else as YAML.
+    pub fn from_file(data: &[u8]) -> Result<Self, MethodologyError> {
+        let is_json = data.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{');
+
+        let result = if is_json {
+            serde_json::from_slice(data).map_err(|e| e.to_string())
+        } else {
+            serde_yaml::from_slice(data).map_err(|e| e.to_string())
+        };
+
+        result.map_err(MethodologyError::InvalidFile)
+    }
+
+    /// Serialize a methodology as a standalone file.
+    pub fn to_file(&self, format: MethodologyFormat) -> Result<Vec<u8>, MethodologyError> {
+        let result = match format {
+            MethodologyFormat::Json => serde_json::to_vec_pretty(self).map_err(|e| e.to_string()),
+            MethodologyFormat::Yaml => serde_yaml::to_vec(self).map_err(|e| e.to_string()),
+        };
+
+        result.map_err(MethodologyError::InvalidFile)
+    }
+}
+
+/// Methodology files are shared between workspaces. They carry the title,
+/// description and process of a methodology, including the annotation schemas
+/// of its states, but nothing that belongs to a single workspace.
+impl From<Methodology> for MethodologyReq {
+    fn from(methodology: Methodology) -> Self {
+        Self {
+            title: methodology.title,
+            description: methodology.description,
+            process: methodology.process,
+        }
+    }
+}
+
+/// The formats of methodology files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MethodologyFormat {
+    #[default]
+    Json,
+    Yaml,
+}
+
+impl Display for MethodologyFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            MethodologyFormat::Json => "json",
+            MethodologyFormat::Yaml => "yaml",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// How an import handles a methodology file whose slug is taken already.
+/// `Rename` imports it under a numbered title, `Replace` updates the existing
+/// methodology to a new version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportConflict {
+    #[default]
+    Fail,
+    Rename,
+    Replace,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -685,10 +1532,360 @@ pub struct Methodology {
     pub description: Option<String>,
     pub process: serde_json::Value,
     pub initial_state: serde_json::Value,
+    /// Every change to the process creates a new version of the methodology.
+    pub version: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl Methodology {
+    pub fn machine(&self) -> Result<MethodologyProcess, MethodologyError> {
+        MethodologyProcess::from_json(&self.process)
+    }
+}
+
+/// The process of a methodology is a state machine in the configuration
+/// format of [`xstate`](https://xstate.js.org/). Only flat machines are
+/// supported, every transition names its target state directly. See
+/// `doc/methodology-format.md` for a complete example.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct MethodologyProcess {
+    pub id: String,
+    #[serde(default)]
+    pub initial: String,
+    pub states: BTreeMap<String, MethodologyState>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct MethodologyState {
+    #[serde(default)]
+    pub on: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<serde_json::Value>,
+}
+
+/// The states that are part of every methodology.
+pub const REQUIRED_STATES: [&str; 3] = ["incoming_data", "discarded_data", "verified_data"];
+
+impl MethodologyProcess {
+    /// Parse a process definition. The process is either a JSON object or,
+    /// as it is read from the database, a string containing the JSON object.
+    pub fn from_json(process: &serde_json::Value) -> Result<Self, MethodologyError> {
+        let result = match process {
+            serde_json::Value::String(json) => serde_json::from_str(json),
+            value => serde_json::from_value(value.clone()),
+        };
+
+        result.map_err(|e| MethodologyError::InvalidProcess(e.to_string()))
+    }
+
+    /// Check the structure of the process. The initial state and all targets
+    /// of transitions must be states of the process, the required states
+    /// must be present and every state must be reachable from the initial
+    /// state.
+    pub fn validate(&self) -> Result<(), MethodologyError> {
+        let invalid = |reason: String| Err(MethodologyError::InvalidProcess(reason));
+
+        if self.initial.is_empty() {
+            return invalid("the process has no initial state".into());
+        }
+
+        if !self.states.contains_key(&self.initial) {
+            return invalid(format!(
+                "the initial state `{}` is not a state of the process",
+                self.initial
+            ));
+        }
+
+        for state in REQUIRED_STATES.iter() {
+            if !self.states.contains_key(*state) {
+                return invalid(format!("the state `{}` is missing", state));
+            }
+        }
+
+        for (state, node) in &self.states {
+            for (event, target) in &node.on {
+                if !self.states.contains_key(target) {
+                    return invalid(format!(
+                        "the transition `{}` of state `{}` targets the unknown state `{}`",
+                        event, state, target
+                    ));
+                }
+            }
+
+            self.annotations(state)?;
+        }
+
+        let mut reachable = vec![self.initial.as_str()];
+        let mut i = 0;
+        while i < reachable.len() {
+            for target in self.states[reachable[i]].on.values() {
+                if !reachable.contains(&target.as_str()) {
+                    reachable.push(target);
+                }
+            }
+            i += 1;
+        }
+
+        let unreachable: Vec<String> = self
+            .states
+            .keys()
+            .filter(|state| !reachable.contains(&state.as_str()))
+            .map(|state| format!("`{}`", state))
+            .collect();
+
+        if !unreachable.is_empty() {
+            return invalid(format!(
+                "the states {} can't be reached from the initial state",
+                unreachable.join(", ")
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Compute the state that follows `state` when `event` happens. Events
+    /// that have no transition in the current state are rejected.
+    pub fn transition(&self, state: &str, event: &str) -> Result<&str, MethodologyError> {
+        let node = self
+            .states
+            .get(state)
+            .ok_or_else(|| MethodologyError::UnknownState(state.into()))?;
+        let target = node
+            .on
+            .get(event)
+            .ok_or_else(|| MethodologyError::IllegalTransition {
+                state: state.into(),
+                event: event.into(),
+            })?;
+
+        if !self.states.contains_key(target) {
+            return Err(MethodologyError::UnknownState(target.into()));
+        }
+
+        Ok(target)
+    }
+
+    /// Compute the state that follows the persisted `current` state when
+    /// `event` happens, and persist it in turn. A unit can only leave its
+    /// state once all required `annotations` of the state are present.
+    pub fn transition_persisted(
+        &self,
+        current: &serde_json::Value,
+        event: &str,
+        annotations: &[Annotation],
+    ) -> Result<serde_json::Value, MethodologyError> {
+        let state = persisted_value(current)?;
+        let next = self.transition(state, event)?;
+
+        let missing: Vec<String> = self
+            .annotations(state)?
+            .into_iter()
+            .filter(|schema| schema.is_required())
+            .filter(|schema| {
+                !annotations
+                    .iter()
+                    .any(|annotation| annotation.key == schema.key && is_present(&annotation.value))
+            })
+            .map(|schema| schema.key)
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(MethodologyError::MissingAnnotations {
+                state: state.into(),
+                keys: missing,
+            });
+        }
+
+        Ok(self.persist(next, event))
+    }
+
+    /// The annotation schema of `state`, as listed in `meta.annotations`.
+    pub fn annotations(&self, state: &str) -> Result<Vec<AnnotationSchema>, MethodologyError> {
+        let node = self
+            .states
+            .get(state)
+            .ok_or_else(|| MethodologyError::UnknownState(state.into()))?;
+
+        match node.meta.as_ref().and_then(|meta| meta.get("annotations")) {
+            Some(annotations) => serde_json::from_value(annotations.clone())
+                .map_err(|e| MethodologyError::InvalidProcess(e.to_string())),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Validate the `value` of the annotation `key` against the annotation
+    /// schema of the state that declares it. Units keep their annotations
+    /// when they move on, and annotations of earlier or later states can be
+    /// edited in any state. If the persisted `current` state declares the
+    /// key its schema wins, otherwise the first state that declares the key.
+    /// Keys that no state declares are rejected.
+    pub fn validate_annotation(
+        &self,
+        current: &serde_json::Value,
+        key: &str,
+        value: &serde_json::Value,
+    ) -> Result<(), MethodologyError> {
+        let current = persisted_value(current)?;
+        let states = std::iter::once(current).chain(
+            self.states
+                .keys()
+                .map(String::as_str)
+                .filter(|state| *state != current),
+        );
+
+        for state in states {
+            if let Some(schema) = self
+                .annotations(state)?
+                .into_iter()
+                .find(|schema| schema.key == key)
+            {
+                return schema.validate(value);
+            }
+        }
+
+        Err(MethodologyError::UnknownAnnotation(key.into()))
+    }
+
+    /// The initial state of the process as it is persisted for new units.
+    pub fn initial_state(&self) -> serde_json::Value {
+        self.persist(&self.initial, "xstate.init")
+    }
+
+    /// Serialize a state the way `xstate` does, so that the UI can resolve
+    /// it again with `machine.resolveState`.
+    fn persist(&self, state: &str, event: &str) -> serde_json::Value {
+        let mut meta = serde_json::Map::new();
+        if let Some(value) = self.states.get(state).and_then(|node| node.meta.clone()) {
+            meta.insert(format!("{}.{}", self.id, state), value);
+        }
+
+        let mut persisted = serde_json::json!({
+            "actions": [],
+            "activities": {},
+            "meta": meta,
+            "events": [],
+            "value": state,
+            "_event": {
+                "name": event,
+                "data": {"type": event},
+                "$$type": "scxml",
+                "type": "external",
+            },
+            "_sessionid": null,
+            "event": {"type": event},
+            "children": {},
+            "done": false,
+        });
+        if event != "xstate.init" {
+            persisted["changed"] = serde_json::Value::Bool(true);
+        }
+
+        persisted
+    }
+}
+
+/// The name of a state persisted by `xstate`.
+fn persisted_value(persisted: &serde_json::Value) -> Result<&str, MethodologyError> {
+    match persisted.get("value") {
+        Some(serde_json::Value::String(state)) => Ok(state),
+        value => {
+            let value = value.unwrap_or(&serde_json::Value::Null);
+            Err(MethodologyError::UnknownState(value.to_string()))
+        }
+    }
+}
+
+/// Annotations that are `null` or an empty string count as missing.
+fn is_present(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => false,
+        serde_json::Value::String(value) => !value.trim().is_empty(),
+        _ => true,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MethodologyError {
+    InvalidProcess(String),
+    InvalidFile(String),
+    UnknownState(String),
+    IllegalTransition { state: String, event: String },
+    UnknownAnnotation(String),
+    InvalidAnnotation { key: String, reason: String },
+    MissingAnnotations { state: String, keys: Vec<String> },
+}
+
+impl Display for MethodologyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MethodologyError::InvalidProcess(reason) => {
+                write!(f, "Methodology process is invalid: {}.", reason)
+            }
+            MethodologyError::InvalidFile(reason) => {
+                write!(f, "Methodology file is invalid: {}.", reason)
+            }
+            MethodologyError::UnknownState(state) => {
+                write!(f, "State `{}` is not part of the methodology.", state)
+            }
+            MethodologyError::IllegalTransition { state, event } => write!(
+                f,
+                "Transition `{}` is not allowed in state `{}`.",
+                event, state
+            ),
+            MethodologyError::UnknownAnnotation(key) => write!(
+                f,
+                "Annotation `{}` is not part of any state of the methodology.",
+                key
+            ),
+            MethodologyError::InvalidAnnotation { key, reason } => {
+                write!(f, "Annotation `{}` {}.", key, reason)
+            }
+            MethodologyError::MissingAnnotations { state, keys } => write!(
+                f,
+                "State `{}` requires the annotations {}.",
+                state,
+                keys.iter()
+                    .map(|key| format!("`{}`", key))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MethodologyError {}
+
+/// The event that moves a unit to its next state, e.g. `TO_DESK_RESEARCH`.
+/// Clients that persisted a whole `xstate` state before send it as it is, the
+/// event is taken from it and the rest is ignored. The optional note is kept
+/// in the history of the verification.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UnitStateReq {
+    pub event: UnitStateEvent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum UnitStateEvent {
+    Name(String),
+    Object {
+        #[serde(rename = "type")]
+        kind: String,
+    },
+}
+
+impl UnitStateReq {
+    pub fn event(&self) -> &str {
+        match &self.event {
+            UnitStateEvent::Name(name) => name,
+            UnitStateEvent::Object { kind } => kind,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InvestigationReq {
     pub title: String,
@@ -709,6 +1906,8 @@ pub struct Investigation {
     pub slug: String,
     pub description: Option<String>,
     pub methodology: String,
+    /// The version of the methodology the investigation started with.
+    pub methodology_version: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -729,7 +1928,31 @@ pub struct SegmentUnit {
     pub verification: i32,
 }
 
-#[derive(Debug, Serialize)]
+/// The verification of a unit of a segment in an investigation. `state` is
+/// the persisted process state of the unit.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Verification {
+    pub id: i32,
+    pub unit: i32,
+    pub state: serde_json::Value,
+}
+
+/// A state transition of a verification. `from_state` is empty for the
+/// initial state of a verification, and for the state of verifications that
+/// existed before transitions were recorded.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct VerificationTransition {
+    pub id: i32,
+    pub verification: i32,
+    pub from_state: Option<String>,
+    pub to_state: String,
+    pub event: String,
+    pub author: Option<String>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 #[serde(tag = "kind", rename_all = "lowercase")]
 pub enum AnnotationKind {
     String,
@@ -739,7 +1962,7 @@ pub enum AnnotationKind {
     Selection,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct AnnotationSchema {
     pub key: String,
     pub name: String,
@@ -750,12 +1973,72 @@ pub struct AnnotationSchema {
     pub selections: Option<Vec<String>>,
 }
 
+impl AnnotationSchema {
+    pub fn is_required(&self) -> bool {
+        self.required.unwrap_or(false)
+    }
+
+    /// Validate the value of an annotation. Optional annotations can be
+    /// `null`, datetimes are strings in the RFC 3339 format.
+    pub fn validate(&self, value: &serde_json::Value) -> Result<(), MethodologyError> {
+        let invalid = |reason: String| MethodologyError::InvalidAnnotation {
+            key: self.key.clone(),
+            reason,
+        };
+
+        if !is_present(value) {
+            return match value {
+                serde_json::Value::Null | serde_json::Value::String(_) if !self.is_required() => {
+                    Ok(())
+                }
+                _ => Err(invalid("is required".into())),
+            };
+        }
+
+        match (&self.kind, value) {
+            (AnnotationKind::String, serde_json::Value::String(_)) => Ok(()),
+            (AnnotationKind::Text, serde_json::Value::String(_)) => Ok(()),
+            (AnnotationKind::String, _) | (AnnotationKind::Text, _) => {
+                Err(invalid("must be a string".into()))
+            }
+            (AnnotationKind::Boolean, serde_json::Value::Bool(_)) => Ok(()),
+            (AnnotationKind::Boolean, _) => Err(invalid("must be a boolean".into())),
+            (AnnotationKind::Datetime, serde_json::Value::String(datetime))
+                if DateTime::parse_from_rfc3339(datetime).is_ok() =>
+            {
+                Ok(())
+            }
+            (AnnotationKind::Datetime, _) => Err(invalid(
+                "must be a date and time in the RFC 3339 format".into(),
+            )),
+            (AnnotationKind::Selection, serde_json::Value::String(selection))
+                if self.selections.iter().flatten().any(|s| s == selection) =>
+            {
+                Ok(())
+            }
+            (AnnotationKind::Selection, _) => Err(invalid(format!(
+                "must be one of {}",
+                self.selections
+                    .iter()
+                    .flatten()
+                    .map(|selection| format!("`{}`", selection))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ))),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Annotation {
     pub key: String,
     pub name: String,
     pub value: serde_json::Value,
     pub note: Option<String>,
+    /// The email of the account that annotated the unit, or `None` if the
+    /// unit was annotated locally.
+    #[serde(default)]
+    pub author: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -774,9 +2057,36 @@ pub struct ClientSubscription {
     pub url: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct FileMetadata {
     pub size_in_bytes: u64,
+    /// The SHA256 digest that was recorded when the file was downloaded.
+    pub sha256: Option<String>,
+    /// The embedded metadata of downloaded images and videos.
+    pub media: Option<MediaMetadata>,
+}
+
+/// A position in decimal degrees. The altitude is in meters above sea level.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct GeoLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+/// The metadata that is embedded in an image or video. `tags` are the EXIF
+/// fields of an image or the container tags of a video, `xmp` is the raw XMP
+/// packet of an image. `created_at` and `location` are taken from the tags
+/// as recorded by the camera, the time usually lacks a time zone.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+pub struct MediaMetadata {
+    pub tags: BTreeMap<String, String>,
+    pub xmp: Option<String>,
+    /// The duration of a video in seconds.
+    pub duration: Option<f64>,
+    pub codecs: Vec<String>,
+    pub created_at: Option<String>,
+    pub location: Option<GeoLocation>,
 }
 
 #[cfg(test)]
@@ -793,6 +2103,26 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn http_error_response_with_query_error() {
+        let response =
+            ErrorResponse::new(StatusCode::BAD_REQUEST, "Invalid!").with_query(QueryError {
+                position: 4,
+                expected: vec!["search term".into()],
+            });
+
+        let expected = "{\"status\":\"error\",\"code\":400,\"errors\":\"Invalid!\",\"query\":{\"position\":4,\"expected\":[\"search term\"]}}";
+        let result = serde_json::to_string(&response).unwrap();
+
+        assert_eq!(result, expected);
+        assert_eq!(
+            serde_json::from_str::<ErrorResponse>(&result)
+                .unwrap()
+                .query,
+            response.query
+        );
+    }
+
     #[test]
     fn http_success_response_envelope() {
         let response = SuccessResponse::new("I am data!");
@@ -802,4 +2132,392 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursors = vec![
+            Cursor::rank(-1.1139240506329113e-6, 3),
+            Cursor::rank(0.0, 1663020),
+            Cursor::rank(-0.0, 0),
+            Cursor::text("2020-01-05T10:00:00Z", 23),
+            Cursor::text("شبكة أوغاريت", 42),
+            Cursor::text("", 1),
+        ];
+
+        for cursor in cursors {
+            let encoded = cursor.to_string();
+            assert_eq!(encoded.parse::<Cursor>().unwrap(), cursor);
+            assert_eq!(
+                serde_json::from_str::<Cursor>(&serde_json::to_string(&cursor).unwrap()).unwrap(),
+                cursor
+            );
+        }
+
+        if let CursorKey::Rank(rank) = "80000000000000000000000a".parse::<Cursor>().unwrap().key {
+            assert_eq!(rank.to_bits(), (-0.0f64).to_bits());
+        }
+    }
+
+    #[test]
+    fn cursor_invalid() {
+        assert!("".parse::<Cursor>().is_err());
+        assert!("zzzzzzzzzzzzzzzzzzzzzzzz".parse::<Cursor>().is_err());
+        assert!("0000000000000000000000001".parse::<Cursor>().is_err());
+        assert!("t0000000".parse::<Cursor>().is_err());
+        assert!("t00000001f".parse::<Cursor>().is_err());
+        assert!("t00000001ff".parse::<Cursor>().is_err());
+        assert!(serde_json::from_str::<Cursor>("\"abc\"").is_err());
+    }
+
+    #[test]
+    fn workspaces_cursor_round_trip() {
+        let mut positions = BTreeMap::new();
+        positions.insert("iraq".to_string(), None);
+        positions.insert("syria".to_string(), Some(Cursor::rank(-4.21, 1663020)));
+        let cursor = WorkspacesCursor { positions };
+
+        let s = cursor.to_string();
+        assert_eq!(s.parse::<WorkspacesCursor>(), Ok(cursor.clone()));
+        let json = serde_json::to_string(&cursor).unwrap();
+        assert_eq!(
+            serde_json::from_str::<WorkspacesCursor>(&json).unwrap(),
+            cursor
+        );
+
+        assert!("".parse::<WorkspacesCursor>().is_err());
+        assert!("syria".parse::<WorkspacesCursor>().is_err());
+        assert!(":".parse::<WorkspacesCursor>().is_err());
+        assert!("syria:abc".parse::<WorkspacesCursor>().is_err());
+    }
+
+    #[test]
+    fn cursor_matches_sort_order() {
+        assert!(Cursor::rank(0.0, 1).matches(&SortOrder::Relevance));
+        assert!(!Cursor::rank(0.0, 1).matches(&SortOrder::CreatedAtDesc));
+        assert!(Cursor::text("x", 1).matches(&SortOrder::Author));
+        assert!(!Cursor::text("x", 1).matches(&SortOrder::Relevance));
+    }
+
+    #[test]
+    fn sort_order_names() {
+        let sort: SortOrder = serde_json::from_str("\"created_at_desc\"").unwrap();
+        assert_eq!(sort, SortOrder::CreatedAtDesc);
+        assert_eq!(sort.to_string(), "created_at_desc");
+        assert_eq!(
+            serde_json::to_string(&SortOrder::FetchedAtAsc).unwrap(),
+            "\"fetched_at_asc\""
+        );
+    }
+
+    #[test]
+    fn export_column_names() {
+        for column in ExportColumn::ALL.iter() {
+            let name = column.to_string();
+            assert_eq!(name.parse::<ExportColumn>().unwrap(), *column);
+            assert_eq!(
+                serde_json::to_string(column).unwrap(),
+                format!("\"{}\"", name)
+            );
+        }
+
+        assert_eq!(
+            ExportColumn::parse_list("id, title,,tags").unwrap(),
+            vec![ExportColumn::Id, ExportColumn::Title, ExportColumn::Tags]
+        );
+        assert_eq!(
+            ExportColumn::parse_list("").unwrap(),
+            ExportColumn::ALL.to_vec()
+        );
+        assert_eq!(
+            ExportColumn::parse_list("id,nope"),
+            Err(InvalidExportColumn("nope".into()))
+        );
+    }
+
+    #[test]
+    fn methodology_process_transitions() {
+        let process = serde_json::Value::String(
+            r#"{"id":"tutorial","initial":"incoming_data","states":{"incoming_data":{"on":{"TO_DESK_RESEARCH":"desk_research","TO_DISCARDED_DATA":"discarded_data"}},"discarded_data":{"on":{"TO_INCOMING_DATA":"incoming_data"}},"verified_data":{"on":{"TO_INCOMING_DATA":"incoming_data","TO_DISCARDED_DATA":"discarded_data"}},"desk_research":{"on":{"TO_SIGN_OFF":"sign_off","TO_DISCARDED_DATA":"discarded_data"},"meta":{"annotations":[]}},"sign_off":{"on":{"TO_VERIFIED_DATA":"verified_data","TO_DESK_RESEARCH":"desk_research","TO_DISCARDED_DATA":"discarded_data"}}}}"#.into(),
+        );
+        let machine = MethodologyProcess::from_json(&process).unwrap();
+
+        let initial_state: serde_json::Value = serde_json::from_str(r#"{"actions":[],"activities":{},"meta":{},"events":[],"value":"incoming_data","_event":{"name":"xstate.init","data":{"type":"xstate.init"},"$$type":"scxml","type":"external"},"_sessionid":null,"event":{"type":"xstate.init"},"children":{},"done":false}"#).unwrap();
+        assert_eq!(machine.initial_state(), initial_state);
+
+        assert_eq!(
+            machine.transition("incoming_data", "TO_DESK_RESEARCH"),
+            Ok("desk_research")
+        );
+        assert_eq!(
+            machine.transition("incoming_data", "TO_VERIFIED_DATA"),
+            Err(MethodologyError::IllegalTransition {
+                state: "incoming_data".into(),
+                event: "TO_VERIFIED_DATA".into()
+            })
+        );
+        assert_eq!(
+            machine.transition("nowhere", "TO_INCOMING_DATA"),
+            Err(MethodologyError::UnknownState("nowhere".into()))
+        );
+
+        let next = machine
+            .transition_persisted(&initial_state, "TO_DESK_RESEARCH", &[])
+            .unwrap();
+        assert_eq!(next["value"], "desk_research");
+        assert_eq!(next["event"]["type"], "TO_DESK_RESEARCH");
+        assert_eq!(next["changed"], true);
+        assert_eq!(
+            next["meta"]["tutorial.desk_research"],
+            serde_json::json!({"annotations": []})
+        );
+        assert!(machine
+            .transition_persisted(&next, "TO_VERIFIED_DATA", &[])
+            .is_err());
+
+        let req: UnitStateReq = serde_json::from_str(r#"{"event":"TO_SIGN_OFF"}"#).unwrap();
+        assert_eq!(req.event(), "TO_SIGN_OFF");
+        assert_eq!(req.note, None);
+        let req: UnitStateReq =
+            serde_json::from_str(r#"{"event":"TO_SIGN_OFF","note":"Checked twice."}"#).unwrap();
+        assert_eq!(req.note, Some("Checked twice.".into()));
+        let req: UnitStateReq = serde_json::from_value(next).unwrap();
+        assert_eq!(req.event(), "TO_DESK_RESEARCH");
+    }
+
+    #[test]
+    fn methodology_process_validation() {
+        let validate = |process: serde_json::Value| {
+            MethodologyProcess::from_json(&process)
+                .and_then(|machine| machine.validate())
+                .map_err(|e| e.to_string())
+        };
+        let states = |extra: serde_json::Value| {
+            let mut states = serde_json::json!({
+                "incoming_data": {"on": {"TO_DISCARDED_DATA": "discarded_data", "TO_VERIFIED_DATA": "verified_data"}},
+                "discarded_data": {"on": {"TO_INCOMING_DATA": "incoming_data"}},
+                "verified_data": {"on": {"TO_INCOMING_DATA": "incoming_data"}}
+            });
+            for (state, node) in extra.as_object().unwrap() {
+                states[state] = node.clone();
+            }
+            states
+        };
+
+        assert_eq!(
+            validate(
+                serde_json::json!({"id": "m", "initial": "incoming_data", "states": states(serde_json::json!({}))})
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            validate(serde_json::json!({"id": "m", "states": states(serde_json::json!({}))})),
+            Err("Methodology process is invalid: the process has no initial state.".into())
+        );
+        assert_eq!(
+            validate(serde_json::json!({"id": "m", "initial": "desk_research", "states": states(serde_json::json!({}))})),
+            Err("Methodology process is invalid: the initial state `desk_research` is not a state of the process.".into())
+        );
+        assert_eq!(
+            validate(
+                serde_json::json!({"id": "m", "initial": "incoming_data", "states": {"incoming_data": {}}})
+            ),
+            Err("Methodology process is invalid: the state `discarded_data` is missing.".into())
+        );
+        assert_eq!(
+            validate(serde_json::json!({
+                "id": "m",
+                "initial": "incoming_data",
+                "states": states(serde_json::json!({"verified_data": {"on": {"TO_SIGN_OFF": "sign_off"}}}))
+            })),
+            Err("Methodology process is invalid: the transition `TO_SIGN_OFF` of state `verified_data` targets the unknown state `sign_off`.".into())
+        );
+        assert_eq!(
+            validate(serde_json::json!({
+                "id": "m",
+                "initial": "incoming_data",
+                "states": states(serde_json::json!({"sign_off": {"on": {"TO_VERIFIED_DATA": "verified_data"}}}))
+            })),
+            Err("Methodology process is invalid: the states `sign_off` can't be reached from the initial state.".into())
+        );
+    }
+
+    #[test]
+    fn methodology_files() {
+        let req = MethodologyReq {
+            title: "Tutorial Methodology".into(),
+            description: None,
+            process: serde_json::json!({
+                "id": "tutorial",
+                "initial": "incoming_data",
+                "states": {"incoming_data": {"meta": {"annotations": [{"key": "location", "name": "Location", "kind": "string"}]}}}
+            }),
+        };
+
+        for format in &[MethodologyFormat::Json, MethodologyFormat::Yaml] {
+            let file = req.to_file(*format).unwrap();
+            let parsed = MethodologyReq::from_file(&file).unwrap();
+            assert_eq!(parsed.title, req.title);
+            assert_eq!(parsed.description, None);
+            assert_eq!(parsed.process, req.process);
+        }
+
+        let yaml = b"title: Tutorial\ndescription: Verify the incoming data.\nprocess:\n  id: tutorial\n  states: {}\n";
+        let parsed = MethodologyReq::from_file(yaml).unwrap();
+        assert_eq!(parsed.slug(), "tutorial");
+        assert_eq!(parsed.description, Some("Verify the incoming data.".into()));
+        assert_eq!(
+            parsed.process,
+            serde_json::json!({"id": "tutorial", "states": {}})
+        );
+
+        assert!(matches!(
+            MethodologyReq::from_file(b"  {\"title\": \"Tutorial\"}"),
+            Err(MethodologyError::InvalidFile(_))
+        ));
+        assert!(matches!(
+            MethodologyReq::from_file(b"- title"),
+            Err(MethodologyError::InvalidFile(_))
+        ));
+    }
+
+    #[test]
+    fn methodology_annotation_schema() {
+        let process = serde_json::json!({
+            "id": "tutorial",
+            "initial": "desk_research",
+            "states": {
+                "desk_research": {
+                    "on": {"TO_SIGN_OFF": "sign_off"},
+                    "meta": {"annotations": [
+                        {"key": "location", "name": "Location", "kind": "string", "required": true},
+                        {"key": "date-time", "name": "Date and Time", "kind": "datetime"},
+                        {"key": "has-batons", "name": "Armed with Baton", "kind": "boolean"},
+                        {"key": "target", "name": "Attacked Target", "kind": "selection", "selections": ["civilian", "journalist"]}
+                    ]}
+                },
+                "sign_off": {
+                    "meta": {"annotations": [
+                        {"key": "narrative", "name": "Narrative", "kind": "text"},
+                        {"key": "target", "name": "Attacked Target", "kind": "string"}
+                    ]}
+                }
+            }
+        });
+        let machine = MethodologyProcess::from_json(&process).unwrap();
+        let current = machine.initial_state();
+        let validate = |key: &str, value: serde_json::Value| {
+            machine.validate_annotation(&current, key, &value)
+        };
+
+        assert!(validate("location", "Homs".into()).is_ok());
+        assert!(validate("location", serde_json::Value::Null).is_err());
+        assert!(validate("location", " ".into()).is_err());
+        assert!(validate("location", 23.into()).is_err());
+        assert!(validate("date-time", "2020-08-13T07:54:40.659Z".into()).is_ok());
+        assert!(validate("date-time", serde_json::Value::Null).is_ok());
+        assert!(validate("date-time", "13.08.2020".into()).is_err());
+        assert!(validate("has-batons", false.into()).is_ok());
+        assert!(validate("has-batons", "yes".into()).is_err());
+        assert!(validate("target", "journalist".into()).is_ok());
+        assert!(validate("target", "police".into()).is_err());
+        assert!(validate("narrative", "Batons were used.".into()).is_ok());
+        assert!(validate("narrative", 23.into()).is_err());
+        assert_eq!(
+            validate("witness", "".into()),
+            Err(MethodologyError::UnknownAnnotation("witness".into()))
+        );
+
+        let annotation = |key: &str, value: serde_json::Value| Annotation {
+            key: key.into(),
+            name: key.into(),
+            value,
+            note: None,
+            author: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+
+        assert_eq!(
+            machine.transition_persisted(
+                &current,
+                "TO_SIGN_OFF",
+                &[annotation("location", "".into())]
+            ),
+            Err(MethodologyError::MissingAnnotations {
+                state: "desk_research".into(),
+                keys: vec!["location".into()]
+            })
+        );
+        let signed_off = machine
+            .transition_persisted(
+                &current,
+                "TO_SIGN_OFF",
+                &[annotation("location", "Homs".into())],
+            )
+            .unwrap();
+
+        // Annotations of earlier states keep their schema, the schema of the
+        // current state wins if both declare the key.
+        assert!(machine
+            .validate_annotation(&signed_off, "location", &"Aleppo".into())
+            .is_ok());
+        assert!(machine
+            .validate_annotation(&signed_off, "location", &23.into())
+            .is_err());
+        assert!(machine
+            .validate_annotation(&signed_off, "target", &"police".into())
+            .is_ok());
+    }
+
+    #[test]
+    fn page_request_next_cursor() {
+        let page = PageRequest {
+            after: None,
+            page: 3,
+            size: 2,
+        };
+
+        assert_eq!(
+            page.next(2, Some(Cursor::rank(0.0, 2))),
+            Some(Cursor::rank(0.0, 2))
+        );
+        assert_eq!(page.next(1, Some(Cursor::rank(0.0, 1))), None);
+        assert_eq!(page.next(0, None), None);
+        assert_eq!(page.offset(), 6);
+
+        let page = PageRequest {
+            after: Some(Cursor::rank(0.0, 2)),
+            ..page
+        };
+
+        assert_eq!(page.offset(), 0);
+    }
+
+    #[test]
+    fn integrity_check_status() {
+        let file = DownloadFile {
+            download: 1,
+            unit: 2,
+            id_hash: "abc".into(),
+            location: "data/abc/image.jpg".into(),
+            md5: Some("900150983CD24FB0D6963F7D28E17F72".into()),
+            sha256: None,
+        };
+        let digests = |md5: &str| Some((md5.to_string(), "ba7816bf".to_string()));
+
+        let check = IntegrityCheck::new(file.clone(), digests("900150983cd24fb0d6963f7d28e17f72"));
+        assert_eq!(check.status, IntegrityStatus::Intact);
+        assert_eq!(check.sha256, Some("ba7816bf".into()));
+
+        let check = IntegrityCheck::new(file.clone(), digests("d41d8cd98f00b204e9800998ecf8427e"));
+        assert_eq!(check.status, IntegrityStatus::Altered);
+
+        let check = IntegrityCheck::new(file.clone(), None);
+        assert_eq!(check.status, IntegrityStatus::Missing);
+        assert_eq!(check.md5, None);
+
+        let file = DownloadFile { md5: None, ..file };
+        let check = IntegrityCheck::new(file, digests("d41d8cd98f00b204e9800998ecf8427e"));
+        assert_eq!(check.status, IntegrityStatus::Unverified);
+    }
 }
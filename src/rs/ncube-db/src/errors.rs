@@ -1,4 +1,5 @@
 use ncube_data::ErrorResponse;
+use ncube_search::SearchParseError;
 use std::fmt::{Display, Formatter, Result};
 use thiserror::Error;
 
@@ -30,8 +31,10 @@ pub enum DatabaseError {
     Invalid(#[from] serde_rusqlite::error::Error),
     #[error(transparent)]
     SqliteConfig(#[from] SqliteConfigError),
+    // Migration errors are boxed, they are larger than all other errors
+    // combined.
     #[error(transparent)]
-    Upgrade(#[from] refinery::Error),
+    Upgrade(Box<refinery::Error>),
 
     #[error(transparent)]
     Http(#[from] reqwest::Error),
@@ -39,6 +42,8 @@ pub enum DatabaseError {
     Runtime(#[from] tokio::task::JoinError),
     #[error(transparent)]
     Resp(#[from] serde_json::error::Error),
+    #[error(transparent)]
+    Search(#[from] SearchParseError),
     #[error("{0}")]
     HttpConfig(String),
 
@@ -49,3 +54,9 @@ pub enum DatabaseError {
     #[error("{0:?}")]
     HttpFail(ErrorResponse),
 }
+
+impl From<refinery::Error> for DatabaseError {
+    fn from(err: refinery::Error) -> Self {
+        DatabaseError::Upgrade(Box::new(err))
+    }
+}
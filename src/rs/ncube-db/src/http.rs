@@ -6,8 +6,12 @@ use futures::Stream;
 use ncube_data::{
     ErrorResponse, HttpResponse, LoginRequest, LoginResponse, SuccessResponse, Workspace,
 };
-use reqwest::{Client, StatusCode};
+use reqwest::{
+    header::{self, HeaderMap},
+    Client, StatusCode,
+};
 use std::fmt::{self, Debug, Formatter};
+use std::pin::Pin;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, instrument};
@@ -239,24 +243,63 @@ impl Database {
         }
     }
 
+    /// Stream a file from the remote workspace. The `range` is passed on as
+    /// the `Range` header, the remote answers with the partial content of the
+    /// file. Any status other than success or an unsatisfiable range is an
+    /// error.
     #[instrument]
     pub async fn get_file(
         &self,
         url: Url,
-    ) -> Result<impl Stream<Item = reqwest::Result<Bytes>>, DatabaseError> {
-        debug!("HTTP GET BYTES ({:?})", url.as_str());
+        range: Option<String>,
+    ) -> Result<RemoteFile, DatabaseError> {
+        debug!("HTTP GET BYTES ({:?}) range {:?}", url.as_str(), range);
 
         self.ensure_login().await?;
         let lock = self.auth.read().await;
 
-        let req = self.client.get(url.as_str());
+        let mut req = self.client.get(url.as_str());
+        if let Some(range) = range {
+            req = req.header(header::RANGE, range);
+        }
 
-        Ok(match &*lock {
+        let resp = match &*lock {
             None => req,
             Some(auth) => req.bearer_auth(&auth.token),
         }
         .send()
-        .await?
-        .bytes_stream())
+        .await?;
+
+        let status = resp.status();
+
+        if status.is_success() || status == StatusCode::RANGE_NOT_SATISFIABLE {
+            Ok(RemoteFile {
+                status,
+                headers: resp.headers().clone(),
+                body: Box::pin(resp.bytes_stream()),
+            })
+        } else if status == StatusCode::NOT_FOUND {
+            Err(DatabaseError::NotFound(url.path().to_string()))
+        } else {
+            let data: ErrorResponse = resp.json().await?;
+
+            debug!("request failed: {:?}", data);
+
+            Err(DatabaseError::HttpFail(data))
+        }
+    }
+}
+
+/// A file that is streamed from a remote workspace. The status and headers are
+/// those of the remote response, they describe the content range of `body`.
+pub struct RemoteFile {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>,
+}
+
+impl Debug for RemoteFile {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f, "RemoteFile({})", self.status)
     }
 }
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+
+/// The number of consecutive words that form a shingle.
+const SHINGLE_SIZE: usize = 3;
+/// The number of hash functions that make up a MinHash signature.
+const NUM_HASHES: usize = 64;
+/// Signatures are split into bands to find candidate pairs without comparing
+/// every document with every other document. Two documents are compared if
+/// all rows of at least one of their bands agree.
+const BANDS: usize = 16;
+const ROWS: usize = NUM_HASHES / BANDS;
+
+/// The estimated similarity of two texts above which they are considered
+/// duplicates.
+pub const DEFAULT_THRESHOLD: f64 = 0.8;
+
+/// The parts of a unit that are compared to find duplicates. `text` is
+/// usually the title and the body of a unit.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub id: i32,
+    pub content_hash: String,
+    pub text: String,
+}
+
+// FNV-1a, the shingle hashes have to be stable across runs.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Split a text into lower cased words and hash every run of
+/// `SHINGLE_SIZE` words. Texts that are shorter than a single shingle have
+/// no shingles. A single shingle would be an estimate of 0% or 100%
+/// similarity, and short titles like "Video" or "Untitled" would all end up
+/// in the same cluster.
+fn shingles(text: &str) -> Vec<u64> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    if words.len() < SHINGLE_SIZE {
+        return vec![];
+    }
+
+    let mut shingles: Vec<u64> = words
+        .windows(SHINGLE_SIZE)
+        .map(|window| fnv1a(window.join(" ").as_bytes()))
+        .collect();
+
+    shingles.sort_unstable();
+    shingles.dedup();
+    shingles
+}
+
+/// The MinHash signature of a text, or `None` if the text contains fewer
+/// words than a single shingle.
+///
+/// # Example
+///
+/// ```
+/// # use ncube_dedup::{signature, similarity};
+/// let a = signature("Air strike on a hospital in Aleppo").unwrap();
+/// let b = signature("air strike on a Hospital in Aleppo!").unwrap();
+/// assert_eq!(similarity(&a, &b), 1.0);
+/// assert!(signature("Aleppo").is_none());
+/// ```
+pub fn signature(text: &str) -> Option<Vec<u64>> {
+    let shingles = shingles(text);
+
+    if shingles.is_empty() {
+        return None;
+    }
+
+    let signature = (0..NUM_HASHES)
+        .map(|i| {
+            let seed = splitmix64(i as u64);
+            shingles
+                .iter()
+                .map(|shingle| splitmix64(shingle ^ seed))
+                .min()
+                .unwrap()
+        })
+        .collect();
+
+    Some(signature)
+}
+
+/// Estimate the Jaccard similarity of the shingles of two texts from their
+/// signatures.
+pub fn similarity(a: &[u64], b: &[u64]) -> f64 {
+    let equal = a.iter().zip(b.iter()).filter(|(a, b)| a == b).count();
+
+    equal as f64 / NUM_HASHES as f64
+}
+
+struct UnionFind {
+    parents: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        Self {
+            parents: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+
+        let mut i = i;
+        while self.parents[i] != root {
+            let next = self.parents[i];
+            self.parents[i] = root;
+            i = next;
+        }
+
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+
+        if a < b {
+            self.parents[b] = a;
+        } else {
+            self.parents[a] = b;
+        }
+    }
+}
+
+/// Group documents that share the same content hash or whose texts are at
+/// least `threshold` similar. Texts that are too short to compare are only
+/// grouped by their content hash. Duplicates are transitive, if `a` duplicates
+/// `b` and `b` duplicates `c` all three are in the same cluster. Only
+/// clusters of two or more documents are returned. The ids of every cluster
+/// are sorted, and clusters are sorted by their first id.
+///
+/// # Example
+///
+/// ```
+/// # use ncube_dedup::{cluster, Document, DEFAULT_THRESHOLD};
+/// let documents = vec![
+///     Document { id: 1, content_hash: "a".into(), text: "hospital attack".into() },
+///     Document { id: 2, content_hash: "b".into(), text: "market attack".into() },
+///     Document { id: 3, content_hash: "a".into(), text: "".into() },
+/// ];
+/// assert_eq!(cluster(&documents, DEFAULT_THRESHOLD), vec![vec![1, 3]]);
+/// ```
+pub fn cluster(documents: &[Document], threshold: f64) -> Vec<Vec<i32>> {
+    let mut union_find = UnionFind::new(documents.len());
+
+    let mut content_hashes: HashMap<&str, usize> = HashMap::new();
+    for (i, document) in documents.iter().enumerate() {
+        if document.content_hash.is_empty() {
+            continue;
+        }
+
+        match content_hashes.get(document.content_hash.as_str()) {
+            Some(first) => union_find.union(*first, i),
+            None => {
+                content_hashes.insert(&document.content_hash, i);
+            }
+        }
+    }
+
+    let signatures: Vec<Option<Vec<u64>>> = documents
+        .iter()
+        .map(|document| signature(&document.text))
+        .collect();
+
+    for band in 0..BANDS {
+        let mut buckets: HashMap<&[u64], Vec<usize>> = HashMap::new();
+
+        for (i, signature) in signatures.iter().enumerate() {
+            if let Some(signature) = signature {
+                buckets
+                    .entry(&signature[band * ROWS..(band + 1) * ROWS])
+                    .or_default()
+                    .push(i);
+            }
+        }
+
+        for bucket in buckets.values().filter(|bucket| bucket.len() > 1) {
+            for (j, b) in bucket.iter().enumerate().skip(1) {
+                for a in &bucket[..j] {
+                    if union_find.find(*a) == union_find.find(*b) {
+                        continue;
+                    }
+
+                    let (sig_a, sig_b) = (&signatures[*a], &signatures[*b]);
+                    if let (Some(sig_a), Some(sig_b)) = (sig_a, sig_b) {
+                        if similarity(sig_a, sig_b) >= threshold {
+                            union_find.union(*a, *b);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<i32>> = HashMap::new();
+    for (i, document) in documents.iter().enumerate() {
+        groups
+            .entry(union_find.find(i))
+            .or_default()
+            .push(document.id);
+    }
+
+    let mut clusters: Vec<Vec<i32>> = groups
+        .into_values()
+        .map(|mut ids| {
+            ids.sort_unstable();
+            ids
+        })
+        .filter(|ids| ids.len() > 1)
+        .collect();
+
+    clusters.sort();
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = "Local activists reported that the main hospital in the \
+        eastern part of the city was hit by two air strikes early in the \
+        morning. The building was partially destroyed and several patients \
+        had to be evacuated to a field clinic nearby. Footage shared online \
+        shows smoke rising from the roof while civil defence teams search \
+        the rubble for survivors.";
+
+    fn document(id: i32, content_hash: &str, text: &str) -> Document {
+        Document {
+            id,
+            content_hash: content_hash.into(),
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn test_shingles() {
+        assert_eq!(shingles(""), vec![]);
+        assert_eq!(shingles("one two"), vec![]);
+        assert_eq!(shingles("one two three").len(), 1);
+        assert_eq!(shingles("one two three four").len(), 2);
+        assert_eq!(shingles("One, two three"), shingles("one two THREE"));
+    }
+
+    #[test]
+    fn test_signature_is_stable() {
+        assert_eq!(signature(REPORT), signature(REPORT));
+        assert_eq!(signature(REPORT).unwrap().len(), NUM_HASHES);
+    }
+
+    #[test]
+    fn test_identical_content_hashes() {
+        let documents = vec![
+            document(1, "a", "hospital"),
+            document(2, "b", "clinic"),
+            document(3, "a", "market"),
+            document(4, "", "school"),
+            document(5, "", "bakery"),
+        ];
+
+        assert_eq!(cluster(&documents, DEFAULT_THRESHOLD), vec![vec![1, 3]]);
+    }
+
+    #[test]
+    fn test_near_identical_texts() {
+        let repost = format!("RT {}", REPORT.replace("two air strikes", "2 air strikes"));
+        let documents = vec![
+            document(4, "a", &repost),
+            document(2, "b", "A completely different report about a market."),
+            document(1, "c", REPORT),
+        ];
+
+        assert_eq!(cluster(&documents, DEFAULT_THRESHOLD), vec![vec![1, 4]]);
+    }
+
+    #[test]
+    fn test_clusters_are_transitive() {
+        let documents = vec![
+            document(1, "a", REPORT),
+            document(2, "b", &format!("{} #Aleppo", REPORT)),
+            document(3, "b", ""),
+            document(4, "d", "Unrelated report from Idlib"),
+            document(5, "e", "unrelated report from idlib"),
+        ];
+
+        assert_eq!(
+            cluster(&documents, DEFAULT_THRESHOLD),
+            vec![vec![1, 2, 3], vec![4, 5]]
+        );
+    }
+
+    #[test]
+    fn test_short_texts_are_not_compared() {
+        let documents = vec![
+            document(1, "a", "Video"),
+            document(2, "b", "video"),
+            document(3, "c", "Untitled video"),
+            document(4, "d", "untitled video"),
+            document(5, "d", "Aleppo"),
+        ];
+
+        assert_eq!(cluster(&documents, DEFAULT_THRESHOLD), vec![vec![4, 5]]);
+    }
+}
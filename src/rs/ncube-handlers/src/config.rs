@@ -1,8 +1,9 @@
 use ncube_actors_common::Registry;
 use ncube_actors_host::{
-    AllSettings, EndpointSetting, HostActor, InsertSetting, IsBootstrapped, SecretKeySetting,
-    Settings,
+    AllSettings, EndpointSetting, HostActor, InsertSetting, IsBootstrapped, MediaCacheSetting,
+    SecretKeySetting, Settings,
 };
+use ncube_cache::{MediaCache, MediaCacheStats};
 use ncube_crypto::gen_secret_key;
 use ncube_data::ConfigSetting;
 use rand::{self, rngs::StdRng, SeedableRng};
@@ -117,3 +118,24 @@ pub async fn endpoint() -> Result<String, HandlerError> {
         .value
         .ok_or_else(|| HandlerError::NotFound("no endpoint".into()))?)
 }
+
+/// The media cache of remote workspaces, and the number and size of the files
+/// it holds.
+pub async fn show_media_cache() -> Result<(MediaCache, MediaCacheStats), HandlerError> {
+    let actor = HostActor::from_registry().await.unwrap();
+
+    let cache = actor.call(MediaCacheSetting).await??;
+    let stats = cache.stats()?;
+
+    Ok((cache, stats))
+}
+
+/// Remove all files from the media cache of remote workspaces.
+pub async fn purge_media_cache() -> Result<MediaCacheStats, HandlerError> {
+    let actor = HostActor::from_registry().await.unwrap();
+
+    let cache = actor.call(MediaCacheSetting).await??;
+    let purged = cache.purge()?;
+
+    Ok(purged)
+}
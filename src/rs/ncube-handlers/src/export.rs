@@ -0,0 +1,1135 @@
+use async_stream::try_stream;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use ncube_data::{
+    Annotation, CustodyEvent, ExportColumn, ExportFormat, ExportManifest, ExportOptions,
+    ExportUnit, MediaType, PageRequest, SegmentUnit, SortOrder, Unit, Workspace, WorkspaceKind,
+};
+use ncube_db::{http::RemoteFile, Database, DatabaseError};
+use ncube_search::{parse_query, parse_stored_query, SearchQuery};
+use ncube_stores::{
+    annotation_store, custody_store, investigation_store, search_store, segment_store, unit_store,
+};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::PathBuf,
+    pin::Pin,
+};
+use tracing::instrument;
+
+use crate::{lookup_workspace, workspace_database, HandlerError};
+
+/// The number of units that are fetched at once while exporting the units of
+/// a segment.
+const EXPORT_PAGE_SIZE: i32 = 100;
+
+/// An entry of the archive of a local export.
+#[derive(Debug)]
+pub enum ExportEntry {
+    /// A download at its location in the workspace.
+    Download { location: String, path: PathBuf },
+    /// A manifest of the export that was spooled to a temporary file.
+    Manifest { name: String, file: File },
+}
+
+pub type ExportStream = Pin<Box<dyn Stream<Item = Result<ExportEntry, HandlerError>> + Send>>;
+
+/// The archive of a local export is assembled while the units are read, remote
+/// workspaces stream the archive they assembled themselves.
+pub enum Export {
+    Local {
+        created_at: DateTime<Utc>,
+        entries: ExportStream,
+    },
+    Remote(RemoteFile),
+}
+
+impl fmt::Debug for Export {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Export::Local { created_at, .. } => write!(f, "Export::Local({})", created_at),
+            Export::Remote(file) => write!(f, "Export::Remote({:?})", file),
+        }
+    }
+}
+
+fn remote_not_found(what: String) -> impl FnOnce(DatabaseError) -> HandlerError {
+    move |err| match err {
+        DatabaseError::NotFound(_) => HandlerError::NotFound(what),
+        err => err.into(),
+    }
+}
+
+/// The manifest of an export is complete except for its units and the missing
+/// downloads, which are added while the units are read.
+async fn export_manifest(
+    workspace: &Workspace,
+    database: Database,
+    segment: &str,
+    investigation: Option<String>,
+    state: Option<String>,
+) -> Result<ExportManifest, HandlerError> {
+    Ok(ExportManifest {
+        workspace: workspace.slug.clone(),
+        segment: segment.to_string(),
+        investigation,
+        state,
+        created_at: Utc::now(),
+        // There is no anchor to verify the chain against, see `ExportManifest`.
+        custody_chain: custody_store(database).verify_chain(None).await?,
+        units: vec![],
+        missing: vec![],
+    })
+}
+
+/// Read the units of a source a page at a time.
+fn unit_pages(
+    database: Database,
+    source: UnitSource,
+) -> impl Stream<Item = Result<Vec<Unit>, HandlerError>> + Send {
+    try_stream! {
+        match source {
+            UnitSource::Search { query, sort } => {
+                let search_store = search_store(database);
+                let mut page = PageRequest {
+                    after: None,
+                    page: 0,
+                    size: EXPORT_PAGE_SIZE,
+                };
+
+                loop {
+                    let data = search_store.data(&query, &sort, &page).await?;
+                    yield data.data;
+
+                    match data.next {
+                        Some(cursor) => page.after = Some(cursor),
+                        None => break,
+                    }
+                }
+            }
+            UnitSource::Investigation { units, .. } => {
+                let unit_store = unit_store(database);
+
+                for chunk in units.chunks(EXPORT_PAGE_SIZE as usize) {
+                    let ids = chunk.iter().map(|unit| unit.id).collect();
+                    let units = unit_store.list_ids(ids).await?;
+                    yield units;
+                }
+            }
+        }
+    }
+}
+
+/// The downloads of the units are part of the archive as soon as a page of
+/// units is read, the manifests follow once all units were read.
+fn export_stream(
+    database: Database,
+    root: PathBuf,
+    source: UnitSource,
+    manifest: ExportManifest,
+) -> ExportStream {
+    Box::pin(try_stream! {
+        let custody_store = custody_store(database.clone());
+        let mut writer = ManifestWriter::new(&manifest)?;
+        // Several units can share a download.
+        let mut locations = HashSet::new();
+        let mut missing = vec![];
+
+        let mut pages = Box::pin(unit_pages(database, source));
+        while let Some(page) = pages.next().await {
+            for unit in page? {
+                for location in unit.downloads.iter().filter_map(|d| d.location.as_ref()) {
+                    if !locations.insert(location.clone()) {
+                        continue;
+                    }
+
+                    let path = root.join(location);
+                    if tokio::fs::metadata(&path).await.is_ok() {
+                        yield ExportEntry::Download {
+                            location: location.clone(),
+                            path,
+                        };
+                    } else {
+                        missing.push(location.clone());
+                    }
+                }
+
+                let custody = custody_store.list(unit.id).await?;
+                writer.push(&ExportUnit { unit, custody })?;
+            }
+        }
+
+        let (json, csv) = writer.finish(&missing)?;
+        yield ExportEntry::Manifest {
+            name: "manifest.json".into(),
+            file: json,
+        };
+        yield ExportEntry::Manifest {
+            name: "manifest.csv".into(),
+            file: csv,
+        };
+    })
+}
+
+/// Export all units that match the query of a segment.
+#[instrument]
+pub async fn export_segment(workspace: &str, segment: &str) -> Result<Export, HandlerError> {
+    let workspace = lookup_workspace(workspace).await?;
+    let database = workspace_database(&workspace.slug).await?;
+
+    let location = match &workspace.kind {
+        WorkspaceKind::Local(location) => location,
+        WorkspaceKind::Remote(_) => {
+            return segment_store(database)
+                .export(segment)
+                .await
+                .map(Export::Remote)
+                .map_err(remote_not_found(format!(
+                    "Segment '{}' could not be found.",
+                    segment
+                )))
+        }
+    };
+
+    let segment = segment_store(database.clone())
+        .show(segment)
+        .await?
+        .ok_or_else(|| {
+            HandlerError::NotFound(format!("Segment '{}' could not be found.", segment))
+        })?;
+
+    let source = UnitSource::Search {
+        query: parse_stored_query(&segment.query),
+        sort: segment.sort.unwrap_or_default(),
+    };
+
+    let manifest = export_manifest(&workspace, database.clone(), &segment.slug, None, None).await?;
+
+    Ok(Export::Local {
+        created_at: manifest.created_at,
+        entries: export_stream(database, PathBuf::from(location), source, manifest),
+    })
+}
+
+/// Export the units of a segment of an investigation, optionally only those
+/// units that are in a certain verification state.
+#[instrument]
+pub async fn export_investigation(
+    workspace: &str,
+    investigation: &str,
+    segment: &str,
+    state: Option<String>,
+) -> Result<Export, HandlerError> {
+    let workspace = lookup_workspace(workspace).await?;
+    let database = workspace_database(&workspace.slug).await?;
+    let investigation_store = investigation_store(database.clone());
+
+    let location = match &workspace.kind {
+        WorkspaceKind::Local(location) => location,
+        WorkspaceKind::Remote(_) => {
+            return investigation_store
+                .export(investigation, segment, state.as_deref())
+                .await
+                .map(Export::Remote)
+                .map_err(remote_not_found(format!(
+                    "Investigation '{}' could not be found.",
+                    investigation
+                )))
+        }
+    };
+
+    if investigation_store.show(investigation).await?.is_none() {
+        return Err(HandlerError::NotFound(format!(
+            "Investigation '{}' could not be found.",
+            investigation
+        )));
+    };
+
+    let units = match &state {
+        Some(state) => {
+            investigation_store
+                .units_by_state(investigation, segment, state)
+                .await?
+        }
+        _ => investigation_store.units(investigation, segment).await?,
+    };
+    let source = UnitSource::Investigation {
+        investigation: investigation.to_string(),
+        units,
+    };
+
+    let manifest = export_manifest(
+        &workspace,
+        database.clone(),
+        segment,
+        Some(investigation.to_string()),
+        state,
+    )
+    .await?;
+
+    Ok(Export::Local {
+        created_at: manifest.created_at,
+        entries: export_stream(database, PathBuf::from(location), source, manifest),
+    })
+}
+
+fn media_type(kind: &MediaType) -> &'static str {
+    match kind {
+        MediaType::Video => "video",
+        MediaType::Image => "image",
+        MediaType::Url => "url",
+    }
+}
+
+/// Pretty print a JSON value that is nested at `indent`.
+fn pretty_json<T: Serialize>(value: &T, indent: &str) -> Result<String, std::io::Error> {
+    let json = serde_json::to_string_pretty(value)?;
+
+    // Line breaks in strings are escaped, every line break is part of the
+    // layout.
+    Ok(json.replace('\n', &format!("\n{}", indent)))
+}
+
+/// Spool the manifests of an export to temporary files while its units are
+/// read. The JSON manifest is laid out like a pretty printed `ExportManifest`.
+/// In the CSV manifest every download has its own row, units without any
+/// downloads have a single row with empty download columns.
+struct ManifestWriter {
+    json: BufWriter<File>,
+    csv: csv::Writer<File>,
+    units: usize,
+}
+
+impl ManifestWriter {
+    /// Start the manifests with the fields of `manifest` that precede its
+    /// units. The units and missing downloads of `manifest` are ignored.
+    fn new(manifest: &ExportManifest) -> Result<Self, std::io::Error> {
+        let mut json = BufWriter::new(tempfile::tempfile()?);
+        write!(
+            json,
+            "{{\n  \"workspace\": {},\n  \"segment\": {},\n  \"investigation\": {},\n  \"state\": {},\n  \"created_at\": {},\n  \"custody_chain\": {},\n  \"units\": [",
+            pretty_json(&manifest.workspace, "  ")?,
+            pretty_json(&manifest.segment, "  ")?,
+            pretty_json(&manifest.investigation, "  ")?,
+            pretty_json(&manifest.state, "  ")?,
+            pretty_json(&manifest.created_at, "  ")?,
+            pretty_json(&manifest.custody_chain, "  ")?,
+        )?;
+
+        let mut csv = csv::Writer::from_writer(tempfile::tempfile()?);
+        csv.write_record([
+            "id",
+            "id_hash",
+            "source",
+            "unit_id",
+            "title",
+            "author",
+            "href",
+            "language",
+            "created_at",
+            "fetched_at",
+            "tags",
+            "sources",
+            "type",
+            "location",
+            "md5",
+            "sha256",
+        ])
+        .map_err(std::io::Error::from)?;
+
+        Ok(Self {
+            json,
+            csv,
+            units: 0,
+        })
+    }
+
+    fn push(&mut self, export_unit: &ExportUnit) -> Result<(), std::io::Error> {
+        if self.units > 0 {
+            self.json.write_all(b",")?;
+        }
+        write!(self.json, "\n    {}", pretty_json(export_unit, "    ")?)?;
+        self.units += 1;
+
+        let unit = &export_unit.unit;
+        let tags = unit
+            .tags
+            .iter()
+            .map(|tag| tag.label.as_str())
+            .collect::<Vec<&str>>()
+            .join(";");
+        let sources = unit
+            .sources
+            .iter()
+            .map(|source| source.term.as_str())
+            .collect::<Vec<&str>>()
+            .join(";");
+        let columns = vec![
+            unit.id.to_string(),
+            unit.id_hash.clone(),
+            unit.source.clone(),
+            unit.unit_id.clone().unwrap_or_default(),
+            unit.title.clone().unwrap_or_default(),
+            unit.author.clone().unwrap_or_default(),
+            unit.href.clone().unwrap_or_default(),
+            unit.language.clone().unwrap_or_default(),
+            unit.created_at.map(|d| d.to_rfc3339()).unwrap_or_default(),
+            unit.fetched_at.to_rfc3339(),
+            tags,
+            sources,
+        ];
+
+        if unit.downloads.is_empty() {
+            let mut record = columns.clone();
+            record.extend(vec![String::new(); 4]);
+            self.csv
+                .write_record(&record)
+                .map_err(std::io::Error::from)?;
+        }
+
+        for download in &unit.downloads {
+            let mut record = columns.clone();
+            record.extend(vec![
+                media_type(&download.kind).to_string(),
+                download.location.clone().unwrap_or_default(),
+                download.md5.clone().unwrap_or_default(),
+                download.sha256.clone().unwrap_or_default(),
+            ]);
+            self.csv
+                .write_record(&record)
+                .map_err(std::io::Error::from)?;
+        }
+
+        Ok(())
+    }
+
+    /// Complete the manifests and return the JSON and the CSV manifest, ready
+    /// to be read from the start.
+    fn finish(mut self, missing: &[String]) -> Result<(File, File), std::io::Error> {
+        if self.units > 0 {
+            self.json.write_all(b"\n  ")?;
+        }
+        write!(
+            self.json,
+            "],\n  \"missing\": {}\n}}",
+            pretty_json(&missing, "  ")?
+        )?;
+
+        let mut json = self.json.into_inner().map_err(|e| e.into_error())?;
+        let mut csv = self
+            .csv
+            .into_inner()
+            .map_err(|e| std::io::Error::new(e.error().kind(), e.to_string()))?;
+        json.seek(SeekFrom::Start(0))?;
+        csv.seek(SeekFrom::Start(0))?;
+
+        Ok((json, csv))
+    }
+}
+
+/// The units of a unit export.
+#[derive(Debug, Clone)]
+pub enum UnitSelection {
+    /// All units that match a search query.
+    Search { query: String, sort: SortOrder },
+    /// All units that match the query of a segment.
+    Segment(String),
+    /// The units of a segment of an investigation, optionally only those in a
+    /// certain verification state.
+    Investigation {
+        investigation: String,
+        segment: String,
+        state: Option<String>,
+    },
+}
+
+pub type UnitStream = Pin<Box<dyn Stream<Item = Result<Bytes, HandlerError>> + Send>>;
+
+/// A unit export is encoded while the units are read from a local workspace,
+/// remote workspaces stream the export they encoded themselves.
+pub enum UnitExport {
+    Local(UnitStream),
+    Remote(RemoteFile),
+}
+
+impl fmt::Debug for UnitExport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnitExport::Local(_) => write!(f, "UnitExport::Local"),
+            UnitExport::Remote(file) => write!(f, "UnitExport::Remote({:?})", file),
+        }
+    }
+}
+
+enum UnitSource {
+    Search {
+        query: SearchQuery,
+        sort: SortOrder,
+    },
+    Investigation {
+        investigation: String,
+        units: Vec<SegmentUnit>,
+    },
+}
+
+/// Export units as CSV or NDJSON. The selection is validated before the export
+/// starts, errors while the units are read end the stream.
+#[instrument]
+pub async fn export_units(
+    workspace: &str,
+    selection: UnitSelection,
+    options: ExportOptions,
+) -> Result<UnitExport, HandlerError> {
+    let workspace = lookup_workspace(workspace).await?;
+    let database = workspace_database(&workspace.slug).await?;
+
+    if let WorkspaceKind::Remote(_) = workspace.kind {
+        let file = match &selection {
+            UnitSelection::Search { query, sort } => search_store(database)
+                .export(&parse_query(query)?, sort, &options)
+                .await
+                .map_err(remote_not_found(format!(
+                    "Workspace '{}' could not be found.",
+                    workspace.slug
+                )))?,
+            UnitSelection::Segment(segment) => segment_store(database)
+                .export_units(segment, &options)
+                .await
+                .map_err(remote_not_found(format!(
+                    "Segment '{}' could not be found.",
+                    segment
+                )))?,
+            UnitSelection::Investigation {
+                investigation,
+                segment,
+                state,
+            } => investigation_store(database)
+                .export_units(investigation, segment, state.as_deref(), &options)
+                .await
+                .map_err(remote_not_found(format!(
+                    "Investigation '{}' could not be found.",
+                    investigation
+                )))?,
+        };
+
+        return Ok(UnitExport::Remote(file));
+    }
+
+    let source = match selection {
+        UnitSelection::Search { query, sort } => UnitSource::Search {
+            query: parse_query(&query)?,
+            sort,
+        },
+        UnitSelection::Segment(segment) => {
+            let segment = segment_store(database.clone())
+                .show(&segment)
+                .await?
+                .ok_or_else(|| {
+                    HandlerError::NotFound(format!("Segment '{}' could not be found.", segment))
+                })?;
+
+            UnitSource::Search {
+                query: parse_stored_query(&segment.query),
+                sort: segment.sort.unwrap_or_default(),
+            }
+        }
+        UnitSelection::Investigation {
+            investigation,
+            segment,
+            state,
+        } => {
+            let investigation_store = investigation_store(database.clone());
+
+            if investigation_store.show(&investigation).await?.is_none() {
+                return Err(HandlerError::NotFound(format!(
+                    "Investigation '{}' could not be found.",
+                    investigation
+                )));
+            };
+
+            let units = match state {
+                Some(state) => {
+                    investigation_store
+                        .units_by_state(&investigation, &segment, &state)
+                        .await?
+                }
+                _ => investigation_store.units(&investigation, &segment).await?,
+            };
+
+            UnitSource::Investigation {
+                investigation,
+                units,
+            }
+        }
+    };
+
+    Ok(UnitExport::Local(unit_stream(database, source, options)))
+}
+
+fn unit_stream(database: Database, source: UnitSource, options: ExportOptions) -> UnitStream {
+    let with_custody = options.columns.contains(&ExportColumn::Custody);
+    let with_annotations = options.columns.contains(&ExportColumn::Annotations);
+
+    Box::pin(try_stream! {
+        let custody_store = custody_store(database.clone());
+        let encoder = UnitEncoder::new(options);
+
+        let header = encoder.header()?;
+        if !header.is_empty() {
+            yield header;
+        }
+
+        match source {
+            UnitSource::Search { query, sort } => {
+                let search_store = search_store(database);
+                let mut page = PageRequest {
+                    after: None,
+                    page: 0,
+                    size: EXPORT_PAGE_SIZE,
+                };
+
+                loop {
+                    let data = search_store.data(&query, &sort, &page).await?;
+
+                    for unit in data.data {
+                        let custody = if with_custody {
+                            custody_store.list(unit.id).await?
+                        } else {
+                            vec![]
+                        };
+
+                        let row = encoder.encode(&unit, &[], &custody)?;
+                        yield row;
+                    }
+
+                    match data.next {
+                        Some(cursor) => page.after = Some(cursor),
+                        None => break,
+                    }
+                }
+            }
+            UnitSource::Investigation { investigation, units } => {
+                let unit_store = unit_store(database.clone());
+                let annotation_store = annotation_store(database);
+
+                for chunk in units.chunks(EXPORT_PAGE_SIZE as usize) {
+                    let ids = chunk.iter().map(|unit| unit.id).collect();
+
+                    for unit in unit_store.list_ids(ids).await? {
+                        let verification = chunk
+                            .iter()
+                            .find(|segment_unit| segment_unit.id == unit.id)
+                            .map(|segment_unit| segment_unit.verification);
+                        let annotations = match verification {
+                            Some(verification) if with_annotations => {
+                                annotation_store.list(&investigation, verification).await?
+                            }
+                            _ => vec![],
+                        };
+                        let custody = if with_custody {
+                            custody_store.list(unit.id).await?
+                        } else {
+                            vec![]
+                        };
+
+                        let row = encoder.encode(&unit, &annotations, &custody)?;
+                        yield row;
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Spreadsheets evaluate cells that start with one of these characters as
+/// formulas. Units are fetched from untrusted sources, such cells are
+/// prefixed with a `'` to be shown as text.
+fn escape_formula(cell: String) -> String {
+    if cell.starts_with(&['=', '+', '-', '@', '\t', '\r'][..]) {
+        format!("'{}", cell)
+    } else {
+        cell
+    }
+}
+
+fn annotation_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn join<T, F>(items: &[T], f: F) -> String
+where
+    F: Fn(&T) -> String,
+{
+    items.iter().map(f).collect::<Vec<String>>().join(";")
+}
+
+fn text_cell(
+    column: ExportColumn,
+    unit: &Unit,
+    annotations: &[Annotation],
+    custody: &[CustodyEvent],
+) -> String {
+    match column {
+        ExportColumn::Id => unit.id.to_string(),
+        ExportColumn::IdHash => unit.id_hash.clone(),
+        ExportColumn::ContentHash => unit.content_hash.clone(),
+        ExportColumn::Source => unit.source.clone(),
+        ExportColumn::UnitId => unit.unit_id.clone().unwrap_or_default(),
+        ExportColumn::Title => unit.title.clone().unwrap_or_default(),
+        ExportColumn::Description => unit.description.clone().unwrap_or_default(),
+        ExportColumn::Body => unit.body.clone().unwrap_or_default(),
+        ExportColumn::Author => unit.author.clone().unwrap_or_default(),
+        ExportColumn::Href => unit.href.clone().unwrap_or_default(),
+        ExportColumn::Language => unit.language.clone().unwrap_or_default(),
+        ExportColumn::CreatedAt => unit.created_at.map(|d| d.to_rfc3339()).unwrap_or_default(),
+        ExportColumn::FetchedAt => unit.fetched_at.to_rfc3339(),
+        ExportColumn::Media => join(&unit.media, |media| media.term.clone()),
+        ExportColumn::Downloads => join(&unit.downloads, |download| {
+            download
+                .location
+                .clone()
+                .unwrap_or_else(|| download.term.clone())
+        }),
+        ExportColumn::Sources => join(&unit.sources, |source| source.term.clone()),
+        ExportColumn::Tags => join(&unit.tags, |tag| tag.label.clone()),
+        ExportColumn::Annotations => join(annotations, |annotation| {
+            format!("{}={}", annotation.key, annotation_value(&annotation.value))
+        }),
+        ExportColumn::Custody => join(custody, |event| {
+            format!("{} {}", event.created_at.to_rfc3339(), event.kind)
+        }),
+    }
+}
+
+fn csv_record(record: &[String]) -> Result<Vec<u8>, std::io::Error> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(record).map_err(std::io::Error::from)?;
+
+    let record = writer
+        .into_inner()
+        .map_err(|e| std::io::Error::new(e.error().kind(), e.to_string()))?;
+
+    Ok(record)
+}
+
+struct UnitEncoder {
+    options: ExportOptions,
+}
+
+impl UnitEncoder {
+    fn new(options: ExportOptions) -> Self {
+        Self { options }
+    }
+
+    /// The CSV header starts with a byte order mark, spreadsheets need it to
+    /// detect UTF-8.
+    fn header(&self) -> Result<Bytes, std::io::Error> {
+        match self.options.format {
+            ExportFormat::Csv => {
+                let names = self
+                    .options
+                    .columns
+                    .iter()
+                    .map(|column| column.to_string())
+                    .collect::<Vec<String>>();
+                let mut header = "\u{feff}".as_bytes().to_vec();
+                header.extend(csv_record(&names)?);
+
+                Ok(header.into())
+            }
+            ExportFormat::Ndjson => Ok(Bytes::new()),
+        }
+    }
+
+    fn encode(
+        &self,
+        unit: &Unit,
+        annotations: &[Annotation],
+        custody: &[CustodyEvent],
+    ) -> Result<Bytes, std::io::Error> {
+        match self.options.format {
+            ExportFormat::Csv => {
+                let record = self
+                    .options
+                    .columns
+                    .iter()
+                    .map(|column| escape_formula(text_cell(*column, unit, annotations, custody)))
+                    .collect::<Vec<String>>();
+                Ok(csv_record(&record)?.into())
+            }
+            ExportFormat::Ndjson => {
+                let mut fields = match serde_json::to_value(unit)? {
+                    serde_json::Value::Object(fields) => fields,
+                    _ => unreachable!(),
+                };
+
+                // The fields are written in the order of the columns.
+                let mut line = vec![b'{'];
+                for (i, column) in self.options.columns.iter().enumerate() {
+                    let value = match column {
+                        ExportColumn::Annotations => serde_json::to_value(annotations)?,
+                        ExportColumn::Custody => serde_json::to_value(custody)?,
+                        column => fields
+                            .remove(&column.to_string())
+                            .unwrap_or(serde_json::Value::Null),
+                    };
+
+                    if i > 0 {
+                        line.push(b',');
+                    }
+                    serde_json::to_writer(&mut line, &column.to_string())?;
+                    line.push(b':');
+                    serde_json::to_writer(&mut line, &value)?;
+                }
+                line.extend_from_slice(b"}\n");
+
+                Ok(line.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use ncube_data::{
+        BrokenLink, ChainBreak, CustodyChain, CustodyEventKind, Download, QueryTag, Source,
+    };
+    use serde_json::json;
+    use std::io::Read;
+
+    fn unit(id: i32, downloads: Vec<Download>) -> Unit {
+        Unit {
+            id,
+            id_hash: format!("hash-{}", id),
+            content_hash: format!("content-{}", id),
+            source: "twitter_tweet".into(),
+            unit_id: Some(format!("{}", 1000 + id)),
+            body: Some("Airstrike on\nthe \"old\" market".into()),
+            href: Some(format!("https://twitter.com/syria/status/{}", 1000 + id)),
+            author: Some("syria_news".into()),
+            title: None,
+            description: None,
+            language: Some("en".into()),
+            created_at: Some(Utc.ymd(2020, 5, 17).and_hms(8, 30, 0)),
+            fetched_at: Utc.ymd(2021, 3, 14).and_hms(15, 9, 26),
+            media: vec![],
+            downloads,
+            sources: vec![Source {
+                id: 1,
+                kind: "twitter_user".into(),
+                term: "syria_news".into(),
+                tags: vec![],
+            }],
+            tags: vec![
+                QueryTag {
+                    label: "airstrike".into(),
+                    description: None,
+                },
+                QueryTag {
+                    label: "aleppo".into(),
+                    description: Some("The city".into()),
+                },
+            ],
+            highlights: None,
+            rank: None,
+        }
+    }
+
+    fn download(location: &str) -> Download {
+        Download {
+            id_hash: format!("hash-{}", location),
+            kind: MediaType::Video,
+            term: format!("https://example.com/{}", location),
+            md5: Some("md5".into()),
+            sha256: Some("sha256".into()),
+            location: Some(location.into()),
+        }
+    }
+
+    fn export_manifest(units: Vec<ExportUnit>, missing: Vec<String>) -> ExportManifest {
+        ExportManifest {
+            workspace: "syria".into(),
+            segment: "aleppo".into(),
+            investigation: Some("airstrikes".into()),
+            state: None,
+            created_at: Utc.ymd(2021, 3, 15).and_hms(9, 0, 0),
+            custody_chain: CustodyChain {
+                length: 3,
+                unsealed: 1,
+                head: Some("head".into()),
+                broken: Some(BrokenLink {
+                    event: 2,
+                    reason: ChainBreak::Altered,
+                    expected: Some("expected".into()),
+                    found: None,
+                }),
+                anchored: None,
+            },
+            units,
+            missing,
+        }
+    }
+
+    fn write_manifests(manifest: &ExportManifest) -> (String, String) {
+        let mut writer = ManifestWriter::new(manifest).unwrap();
+        for export_unit in &manifest.units {
+            writer.push(export_unit).unwrap();
+        }
+        let (mut json_file, mut csv_file) = writer.finish(&manifest.missing).unwrap();
+
+        let mut json = String::new();
+        json_file.read_to_string(&mut json).unwrap();
+        let mut csv = String::new();
+        csv_file.read_to_string(&mut csv).unwrap();
+
+        (json, csv)
+    }
+
+    #[test]
+    fn manifest_writer_writes_the_json_manifest() {
+        let custody = vec![CustodyEvent {
+            id: 1,
+            unit: 1,
+            kind: CustodyEventKind::Fetched,
+            data: json!({"source": "twitter_tweet", "run": null}),
+            created_at: Utc.ymd(2021, 3, 14).and_hms(15, 9, 26),
+        }];
+        let manifest = export_manifest(
+            vec![
+                ExportUnit {
+                    unit: unit(1, vec![download("files/a.mp4")]),
+                    custody,
+                },
+                ExportUnit {
+                    unit: unit(2, vec![]),
+                    custody: vec![],
+                },
+            ],
+            vec!["files/a.mp4".into(), "files/b.mp4".into()],
+        );
+
+        let (json, _) = write_manifests(&manifest);
+
+        assert_eq!(json, serde_json::to_string_pretty(&manifest).unwrap());
+    }
+
+    #[test]
+    fn manifest_writer_writes_an_empty_json_manifest() {
+        let manifest = export_manifest(vec![], vec![]);
+
+        let (json, csv) = write_manifests(&manifest);
+
+        assert_eq!(json, serde_json::to_string_pretty(&manifest).unwrap());
+        assert_eq!(
+            csv,
+            "id,id_hash,source,unit_id,title,author,href,language,created_at,fetched_at,tags,sources,type,location,md5,sha256\n"
+        );
+    }
+
+    #[test]
+    fn manifest_writer_writes_a_row_per_download() {
+        let manifest = export_manifest(
+            vec![
+                ExportUnit {
+                    unit: unit(1, vec![download("files/a.mp4"), download("files/b.mp4")]),
+                    custody: vec![],
+                },
+                ExportUnit {
+                    unit: unit(2, vec![]),
+                    custody: vec![],
+                },
+            ],
+            vec![],
+        );
+
+        let (_, csv) = write_manifests(&manifest);
+        let rows: Vec<&str> = csv.lines().skip(1).collect();
+
+        let unit_columns = |id: i32| {
+            format!(
+                "{},hash-{},twitter_tweet,{},,syria_news,https://twitter.com/syria/status/{},en,2020-05-17T08:30:00+00:00,2021-03-14T15:09:26+00:00,airstrike;aleppo,syria_news",
+                id,
+                id,
+                1000 + id,
+                1000 + id
+            )
+        };
+        assert_eq!(
+            rows,
+            vec![
+                format!("{},video,files/a.mp4,md5,sha256", unit_columns(1)),
+                format!("{},video,files/b.mp4,md5,sha256", unit_columns(1)),
+                format!("{},,,,", unit_columns(2)),
+            ]
+        );
+    }
+
+    fn encoder(format: ExportFormat, columns: Vec<ExportColumn>) -> UnitEncoder {
+        UnitEncoder::new(ExportOptions { format, columns })
+    }
+
+    fn annotation(key: &str, value: serde_json::Value) -> Annotation {
+        Annotation {
+            key: key.into(),
+            name: key.into(),
+            value,
+            note: None,
+            author: None,
+            created_at: Utc.ymd(2021, 3, 16).and_hms(10, 0, 0),
+            updated_at: Utc.ymd(2021, 3, 16).and_hms(10, 0, 0),
+        }
+    }
+
+    fn fetched(unit: i32) -> CustodyEvent {
+        CustodyEvent {
+            id: 1,
+            unit,
+            kind: CustodyEventKind::Fetched,
+            data: json!({"source": "twitter_tweet", "run": null}),
+            created_at: Utc.ymd(2021, 3, 14).and_hms(15, 9, 26),
+        }
+    }
+
+    #[test]
+    fn unit_encoder_writes_the_csv_header() {
+        let csv = encoder(
+            ExportFormat::Csv,
+            vec![
+                ExportColumn::Title,
+                ExportColumn::Id,
+                ExportColumn::CreatedAt,
+            ],
+        );
+        let ndjson = encoder(ExportFormat::Ndjson, vec![ExportColumn::Id]);
+
+        assert_eq!(
+            csv.header().unwrap(),
+            Bytes::from("\u{feff}title,id,created_at\n")
+        );
+        assert!(csv.header().unwrap().starts_with(&[0xef, 0xbb, 0xbf]));
+        assert_eq!(ndjson.header().unwrap(), Bytes::new());
+    }
+
+    #[test]
+    fn unit_encoder_writes_csv_cells_in_the_order_of_the_columns() {
+        let encoder = encoder(
+            ExportFormat::Csv,
+            vec![
+                ExportColumn::Author,
+                ExportColumn::Id,
+                ExportColumn::Tags,
+                ExportColumn::Title,
+                ExportColumn::Body,
+            ],
+        );
+
+        assert_eq!(
+            encoder.encode(&unit(1, vec![]), &[], &[]).unwrap(),
+            Bytes::from(
+                "syria_news,1,airstrike;aleppo,,\"Airstrike on\nthe \"\"old\"\" market\"\n"
+            )
+        );
+    }
+
+    #[test]
+    fn unit_encoder_escapes_csv_formulas() {
+        let mut unit = unit(1, vec![]);
+        unit.title = Some("=HYPERLINK(\"https://example.com\")".into());
+        unit.author = Some("@syria_news".into());
+        let encoder = encoder(
+            ExportFormat::Csv,
+            vec![ExportColumn::Title, ExportColumn::Author],
+        );
+
+        assert_eq!(
+            encoder.encode(&unit, &[], &[]).unwrap(),
+            Bytes::from("\"'=HYPERLINK(\"\"https://example.com\"\")\",'@syria_news\n")
+        );
+    }
+
+    #[test]
+    fn escape_formula_prefixes_formulas() {
+        assert_eq!(escape_formula("=1+1".into()), "'=1+1");
+        assert_eq!(escape_formula("+49 30 1234".into()), "'+49 30 1234");
+        assert_eq!(escape_formula("-5".into()), "'-5");
+        assert_eq!(escape_formula("@SUM(A1:A3)".into()), "'@SUM(A1:A3)");
+        assert_eq!(escape_formula("\t=1+1".into()), "'\t=1+1");
+        assert_eq!(escape_formula("\r=1+1".into()), "'\r=1+1");
+
+        assert_eq!(escape_formula("1+1=2".into()), "1+1=2");
+        assert_eq!(escape_formula("syria_news".into()), "syria_news");
+        assert_eq!(escape_formula("".into()), "");
+    }
+
+    #[test]
+    fn unit_encoder_writes_ndjson_fields_in_the_order_of_the_columns() {
+        let encoder = encoder(
+            ExportFormat::Ndjson,
+            vec![
+                ExportColumn::Author,
+                ExportColumn::Id,
+                ExportColumn::Title,
+                ExportColumn::CreatedAt,
+            ],
+        );
+
+        assert_eq!(
+            encoder.encode(&unit(1, vec![]), &[], &[]).unwrap(),
+            Bytes::from(
+                "{\"author\":\"syria_news\",\"id\":1,\"title\":null,\"created_at\":\"2020-05-17T08:30:00Z\"}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn unit_encoder_writes_annotations_and_custody_only_when_selected() {
+        let unit = unit(1, vec![]);
+        let annotations = vec![
+            annotation("verified", json!(true)),
+            annotation("note", json!("=looks fine")),
+        ];
+        let custody = vec![fetched(1)];
+
+        let csv = encoder(ExportFormat::Csv, vec![ExportColumn::Id]);
+        let ndjson = encoder(ExportFormat::Ndjson, vec![ExportColumn::Id]);
+        assert_eq!(
+            csv.encode(&unit, &annotations, &custody).unwrap(),
+            Bytes::from("1\n")
+        );
+        assert_eq!(
+            ndjson.encode(&unit, &annotations, &custody).unwrap(),
+            Bytes::from("{\"id\":1}\n")
+        );
+
+        let columns = vec![
+            ExportColumn::Id,
+            ExportColumn::Annotations,
+            ExportColumn::Custody,
+        ];
+        let csv = encoder(ExportFormat::Csv, columns.clone());
+        let ndjson = encoder(ExportFormat::Ndjson, columns);
+        assert_eq!(
+            csv.encode(&unit, &annotations, &custody).unwrap(),
+            Bytes::from("1,verified=true;note==looks fine,2021-03-14T15:09:26+00:00 fetched\n")
+        );
+
+        let line = ndjson.encode(&unit, &annotations, &custody).unwrap();
+        let fields: serde_json::Value = serde_json::from_slice(&line).unwrap();
+        assert_eq!(fields["id"], json!(1));
+        assert_eq!(
+            fields["annotations"],
+            serde_json::to_value(&annotations).unwrap()
+        );
+        assert_eq!(fields["custody"], serde_json::to_value(&custody).unwrap());
+    }
+}
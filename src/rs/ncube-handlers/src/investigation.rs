@@ -1,8 +1,12 @@
-use ncube_data::{Annotation, AnnotationReq, Segment, SegmentUnit, VerifySegmentReq};
-use ncube_stores::{annotation_store, investigation_store};
+use ncube_data::{
+    Annotation, AnnotationReq, Investigation, MethodologyProcess, Segment, SegmentUnit,
+    UnitStateReq, VerificationTransition, VerifySegmentReq, WorkspaceKind,
+};
+use ncube_db::Database;
+use ncube_stores::{annotation_store, custody_store, investigation_store, methodology_store};
 use tracing::instrument;
 
-use crate::{ensure_workspace, workspace_database, HandlerError};
+use crate::{ensure_workspace, lookup_workspace, remote_error, workspace_database, HandlerError};
 
 #[instrument]
 pub async fn verify_segment(
@@ -26,6 +30,8 @@ pub async fn verify_segment(
         .verify_segment(&investigation, &segment_req.segment)
         .await?;
 
+    custody_store(database).seal().await?;
+
     Ok(())
 }
 
@@ -82,48 +88,126 @@ pub async fn list_units(
     Ok(units)
 }
 
+/// Move a unit to its next state in the process of the methodology of the
+/// investigation. Local workspaces compute the next state from the current one
+/// and reject events that the process doesn't allow, remote workspaces do the
+/// same on their side.
 #[instrument]
 pub async fn update_unit_state(
     workspace: &str,
     investigation: &str,
     segment: &str,
     unit: i32,
-    state: &serde_json::Value,
-) -> Result<(), HandlerError> {
-    ensure_workspace(&workspace).await?;
-
-    let database = workspace_database(&workspace).await?;
+    state_req: &UnitStateReq,
+    author: Option<String>,
+) -> Result<serde_json::Value, HandlerError> {
+    let workspace = lookup_workspace(workspace).await?;
+    let database = workspace_database(&workspace.slug).await?;
     let investigation_store = investigation_store(database.clone());
 
-    if let None = investigation_store.show(&investigation).await? {
-        return Err(HandlerError::NotFound(format!(
-            "Investigation '{}' could not be found.",
-            investigation
-        )));
+    let investigation = match investigation_store.show(investigation).await? {
+        Some(investigation) => investigation,
+        None => {
+            return Err(HandlerError::NotFound(format!(
+                "Investigation '{}' could not be found.",
+                investigation
+            )))
+        }
     };
 
-    // The update just doesn't happen if the unit doesn't exist (fails the where
-    // clause).
-    investigation_store
-        .update_unit_state(&investigation, &segment, unit, &state)
+    if let WorkspaceKind::Remote(_) = workspace.kind {
+        let state = investigation_store
+            .transition_unit(&investigation.slug, segment, unit, state_req)
+            .await
+            .map_err(remote_error)?;
+
+        return Ok(state);
+    }
+
+    let machine = methodology_machine(database.clone(), &investigation).await?;
+
+    let verification = investigation_store
+        .unit_verification(&investigation.slug, segment, unit)
+        .await?
+        .ok_or_else(|| {
+            HandlerError::NotFound(format!(
+                "Unit '{}' could not be found in segment '{}'.",
+                unit, segment
+            ))
+        })?;
+    let annotations = annotation_store(database.clone())
+        .list(&investigation.slug, verification.id)
         .await?;
+    let state = machine
+        .transition_persisted(&verification.state, state_req.event(), &annotations)
+        .map_err(|e| HandlerError::Invalid(e.to_string()))?;
 
-    Ok(())
+    let updated = investigation_store
+        .update_unit_state(
+            &investigation.slug,
+            segment,
+            unit,
+            &verification.state,
+            &state,
+            &author,
+            &state_req.note,
+        )
+        .await?;
+
+    if !updated {
+        return Err(HandlerError::Conflict(format!(
+            "The state of unit '{}' changed in the meantime.",
+            unit
+        )));
+    }
+
+    custody_store(database).seal().await?;
+
+    Ok(state)
 }
 
+/// Annotate the verification of a unit. Local workspaces validate the
+/// annotation against the annotation schema of the state that declares it,
+/// remote workspaces do the same on their side.
 #[instrument]
 pub async fn set_annotation(
     workspace: &str,
     investigation: &str,
     verification: i32,
     annotation_req: &AnnotationReq,
+    author: Option<String>,
 ) -> Result<(), HandlerError> {
-    ensure_workspace(&workspace).await?;
+    let workspace = lookup_workspace(workspace).await?;
+    let database = workspace_database(&workspace.slug).await?;
+    let annotation_store = annotation_store(database.clone());
 
-    // FIXME: verify this verification exists for this workspace and investigation
+    if let WorkspaceKind::Local(_) = workspace.kind {
+        let investigation_store = investigation_store(database.clone());
 
-    let database = workspace_database(&workspace).await?;
-    let annotation_store = annotation_store(database.clone());
+        let investigation = match investigation_store.show(investigation).await? {
+            Some(investigation) => investigation,
+            None => {
+                return Err(HandlerError::NotFound(format!(
+                    "Investigation '{}' could not be found.",
+                    investigation
+                )))
+            }
+        };
+        let current = investigation_store
+            .verification(&investigation.slug, verification)
+            .await?
+            .ok_or_else(|| {
+                HandlerError::NotFound(format!(
+                    "Verification '{}' could not be found.",
+                    verification
+                ))
+            })?;
+
+        methodology_machine(database.clone(), &investigation)
+            .await?
+            .validate_annotation(&current.state, &annotation_req.key, &annotation_req.value)
+            .map_err(|e| HandlerError::Invalid(e.to_string()))?;
+    }
 
     annotation_store
         .create(
@@ -131,11 +215,14 @@ pub async fn set_annotation(
             &annotation_req.value,
             &annotation_req.name,
             &annotation_req.note,
+            &author,
             &investigation,
             verification,
         )
         .await?;
 
+    custody_store(database).seal().await?;
+
     Ok(())
 }
 
@@ -156,3 +243,66 @@ pub async fn list_annotations(
 
     Ok(annotations)
 }
+
+/// The state transitions of a verification, oldest first.
+#[instrument]
+pub async fn verification_history(
+    workspace: &str,
+    investigation: &str,
+    verification: i32,
+) -> Result<Vec<VerificationTransition>, HandlerError> {
+    let workspace = lookup_workspace(workspace).await?;
+    let database = workspace_database(&workspace.slug).await?;
+    let investigation_store = investigation_store(database);
+
+    // Remote workspaces check the verification on their side.
+    if let WorkspaceKind::Local(_) = workspace.kind {
+        if investigation_store.show(investigation).await?.is_none() {
+            return Err(HandlerError::NotFound(format!(
+                "Investigation '{}' could not be found.",
+                investigation
+            )));
+        }
+
+        if investigation_store
+            .verification(investigation, verification)
+            .await?
+            .is_none()
+        {
+            return Err(HandlerError::NotFound(format!(
+                "Verification '{}' could not be found.",
+                verification
+            )));
+        }
+    }
+
+    let history = investigation_store
+        .history(investigation, verification)
+        .await?;
+
+    Ok(history)
+}
+
+/// The process of the version of the methodology the investigation started
+/// with.
+async fn methodology_machine(
+    database: Database,
+    investigation: &Investigation,
+) -> Result<MethodologyProcess, HandlerError> {
+    let methodology = methodology_store(database)
+        .show_version(
+            &investigation.methodology,
+            investigation.methodology_version,
+        )
+        .await?
+        .ok_or_else(|| {
+            HandlerError::NotFound(format!(
+                "Version {} of methodology '{}' could not be found.",
+                investigation.methodology_version, investigation.methodology
+            ))
+        })?;
+
+    methodology
+        .machine()
+        .map_err(|e| HandlerError::Invalid(e.to_string()))
+}
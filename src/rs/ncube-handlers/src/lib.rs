@@ -4,12 +4,14 @@ use ncube_actors_host::{HostActor, RequirePool};
 use ncube_data::{Workspace, WorkspaceKind};
 use ncube_db::{Database, DatabaseError};
 use ncube_errors::HostError;
+use ncube_search::SearchParseError;
 use ncube_stores::{workspace_store, WorkspaceStore};
 use thiserror::Error;
 use tracing::{error, instrument};
 
 pub mod account;
 pub mod config;
+pub mod export;
 pub mod host;
 pub mod investigation;
 pub mod source;
@@ -29,6 +31,9 @@ pub enum HandlerError {
     #[error(transparent)]
     Host(#[from] HostError),
 
+    #[error(transparent)]
+    Search(#[from] SearchParseError),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 
@@ -40,6 +45,9 @@ pub enum HandlerError {
 
     #[error("{0}")]
     NotFound(String),
+
+    #[error("{0}")]
+    Conflict(String),
 }
 
 impl From<ActorError> for HandlerError {
@@ -53,6 +61,21 @@ impl From<ActorError> for HandlerError {
 
 impl warp::reject::Reject for HandlerError {}
 
+/// Pass on the errors that a remote workspace answered with the same status,
+/// instead of failing with an internal error. Other errors are kept as they
+/// are.
+pub(crate) fn remote_error(err: DatabaseError) -> HandlerError {
+    match err {
+        DatabaseError::HttpFail(resp) => match resp.code {
+            400 => HandlerError::Invalid(resp.errors),
+            404 => HandlerError::NotFound(resp.errors),
+            409 => HandlerError::Conflict(resp.errors),
+            _ => HandlerError::Database(DatabaseError::HttpFail(resp)),
+        },
+        err => HandlerError::Database(err),
+    }
+}
+
 #[instrument]
 pub async fn ensure_workspace(workspace: &str) -> Result<(), HandlerError> {
     let host_actor = HostActor::from_registry().await.unwrap();
@@ -1,4 +1,4 @@
-use ncube_data::{QueryTag, Source, SourceRequest};
+use ncube_data::{Page, PageRequest, QueryTag, Source, SourceRequest};
 use ncube_stores::{search_store, source_store};
 use tracing::{error, instrument};
 
@@ -34,9 +34,8 @@ pub async fn show_source(workspace: &str, id: i32) -> Result<Option<Source>, Han
 #[instrument]
 pub async fn list_sources(
     workspace: &str,
-    page: i32,
-    page_size: i32,
-) -> Result<Vec<Source>, HandlerError> {
+    page: &PageRequest,
+) -> Result<Page<Source>, HandlerError> {
     ensure_workspace(&workspace).await?;
 
     let mut database = workspace_database(&workspace).await?;
@@ -44,7 +43,7 @@ pub async fn list_sources(
     database.login().await?;
 
     let store = source_store(database);
-    let sources = store.list(page, page_size).await?;
+    let sources = store.list(page).await?;
 
     Ok(sources)
 }
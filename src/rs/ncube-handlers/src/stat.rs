@@ -1,5 +1,5 @@
 use ncube_data::Stat;
-use ncube_search::parse_query;
+use ncube_search::{parse_query, parse_stored_query};
 use ncube_stores::stat_store;
 use tracing::instrument;
 
@@ -40,7 +40,7 @@ pub async fn stat_data_total(workspace: &str, query: Option<String>) -> Result<S
     let stat_store = stat_store(database);
 
     let stats = stat_store
-        .data_total(query.map(|q| parse_query(&q)))
+        .data_total(query.map(|q| parse_query(&q)).transpose()?)
         .await?;
 
     Ok(stats)
@@ -100,7 +100,7 @@ pub async fn stat_segment_units(workspace: &str, segment: &str) -> Result<Stat,
     let database = workspace_database(&workspace).await?;
     let stat_store = stat_store(database);
     let stats = stat_store
-        .data_total(Some(parse_query(&segment.query)))
+        .data_total(Some(parse_stored_query(&segment.query)))
         .await?;
 
     Ok(stats)
@@ -1,5 +1,5 @@
-use ncube_data::Unit;
-use ncube_stores::unit_store;
+use ncube_data::{CustodyEvent, Unit};
+use ncube_stores::{custody_store, unit_store};
 use tracing::instrument;
 
 use crate::{ensure_workspace, workspace_database, HandlerError};
@@ -26,3 +26,18 @@ pub async fn list_data_ids(workspace: &str, ids: Vec<i32>) -> Result<Vec<Unit>,
 
     Ok(data)
 }
+
+#[instrument]
+pub async fn list_custody_events(
+    workspace: &str,
+    id: i32,
+) -> Result<Vec<CustodyEvent>, HandlerError> {
+    ensure_workspace(workspace).await?;
+
+    let database = workspace_database(workspace).await?;
+    let custody_store = custody_store(database);
+
+    let events = custody_store.list(id).await?;
+
+    Ok(events)
+}
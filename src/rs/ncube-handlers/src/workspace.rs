@@ -1,22 +1,33 @@
 use ncube_actors_common::Registry;
-use ncube_actors_host::{HostActor, RequirePool, WorkspaceRootSetting};
-use ncube_actors_task::{RemoveLocation, SetupWorkspace, TaskActor};
+use ncube_actors_host::{HostActor, MediaCacheSetting, RequirePool, WorkspaceRootSetting};
+use ncube_actors_task::{
+    DetectDuplicates, GenerateThumbnails, HashMedia, RemoveLocation, SetupWorkspace, TaskActor,
+    VerifyDownloads,
+};
 
+use futures::future::join_all;
 use ncube_data::{
-    AccountRequest, DatabaseRequest, FileMetadata, Investigation, InvestigationReq, Methodology,
-    MethodologyReq, Segment, SegmentRequest, Unit, Workspace, WorkspaceDatabase, WorkspaceKind,
-    WorkspaceKindRequest, WorkspaceRequest,
+    AccountRequest, Cursor, CustodyChain, DatabaseRequest, DuplicateCluster, Facets,
+    FailedWorkspace, FileMetadata, ImportConflict, IntegrityReport, Investigation,
+    InvestigationReq, Methodology, MethodologyError, MethodologyFormat, MethodologyProcess,
+    MethodologyReq, Page, PageRequest, Segment, SegmentRequest, SimilarMedia, SortOrder, Unit,
+    Workspace, WorkspaceDatabase, WorkspaceKind, WorkspaceKindRequest, WorkspaceRequest,
+    WorkspaceUnit, WorkspacesCursor, WorkspacesSearchResponse,
 };
-use ncube_db::{migrations, sqlite, DatabaseError};
-use ncube_search::parse_query;
+use ncube_db::{http::RemoteFile, migrations, sqlite, Database, DatabaseError};
+use ncube_search::{parse_query, SearchQuery};
 use ncube_stores::{
-    investigation_store, methodology_store, search_store, segment_store, unit_store,
-    workspace_store, WorkspaceStore,
+    custody_store, duplicate_store, integrity_store, investigation_store, media_store,
+    methodology_store, search_store, segment_store, stat_store, unit_store, workspace_store,
+    WorkspaceStore,
 };
-use tokio::fs::File;
-use tracing::{debug, instrument};
+use std::collections::BTreeMap;
+use tokio::{fs::File, sync::mpsc};
+use tracing::{debug, error, info, instrument};
 
-use crate::{account, ensure_workspace, lookup_workspace, workspace_database, HandlerError};
+use crate::{
+    account, ensure_workspace, lookup_workspace, remote_error, workspace_database, HandlerError,
+};
 
 #[instrument]
 pub async fn create_workspace(workspace_req: WorkspaceRequest) -> Result<Workspace, HandlerError> {
@@ -197,15 +208,24 @@ pub async fn update_workspace(
 #[instrument]
 pub async fn list_data(
     workspace: &str,
-    page: i32,
-    page_size: i32,
-) -> Result<Vec<Unit>, HandlerError> {
+    sort: &SortOrder,
+    page: &PageRequest,
+) -> Result<Page<Unit>, HandlerError> {
     ensure_workspace(&workspace).await?;
 
+    if let Some(cursor) = &page.after {
+        if !cursor.matches(sort) {
+            return Err(HandlerError::Invalid(format!(
+                "The cursor can't be used to sort by {}.",
+                sort
+            )));
+        }
+    }
+
     let database = workspace_database(&workspace).await?;
     let unit_store = unit_store(database);
 
-    let data = unit_store.list(page, page_size).await?;
+    let data = unit_store.list(sort, page).await?;
 
     Ok(data)
 }
@@ -214,20 +234,337 @@ pub async fn list_data(
 pub async fn search_data(
     workspace: &str,
     query: &str,
-    page: i32,
-    page_size: i32,
-) -> Result<Vec<Unit>, HandlerError> {
+    sort: &SortOrder,
+    page: &PageRequest,
+) -> Result<Page<Unit>, HandlerError> {
     ensure_workspace(&workspace).await?;
 
+    if let Some(cursor) = &page.after {
+        if !cursor.matches(sort) {
+            return Err(HandlerError::Invalid(format!(
+                "The cursor can't be used to sort by {}.",
+                sort
+            )));
+        }
+    }
+
     let database = workspace_database(&workspace).await?;
     let search_store = search_store(database);
 
-    let search_query = parse_query(&query);
-    let data = search_store.data(&search_query, page, page_size).await?;
+    let search_query = parse_query(query)?;
+    let data = search_store.data(&search_query, sort, page).await?;
 
     Ok(data)
 }
 
+async fn search_workspace(
+    workspace: &Workspace,
+    query: &SearchQuery,
+    page: &PageRequest,
+) -> Result<(Page<Unit>, i32), HandlerError> {
+    let database = workspace_database(&workspace.slug).await?;
+    let search_store = search_store(database.clone());
+    let stat_store = stat_store(database);
+
+    let data = search_store
+        .data(query, &SortOrder::Relevance, page)
+        .await?;
+    let total = stat_store.data_total(Some(query.clone())).await?;
+
+    Ok((data, total.value))
+}
+
+/// Search all local workspaces, and remote workspaces as well if `remote` is
+/// set. The `bm25` rank of a unit depends on the term statistics of its own
+/// workspace, ranks of different workspaces can't be compared. The results
+/// are interleaved round-robin instead, the best match of every workspace
+/// comes first, followed by the second best match of every workspace and so
+/// on.
+///
+/// Every workspace is paged by its own cursor, the cursor of the next page
+/// keeps the positions of all workspaces that have more units. A workspace
+/// that fails to search is reported in the response and left out of all
+/// following pages.
+#[instrument]
+pub async fn search_workspaces(
+    query: &str,
+    remote: bool,
+    after: Option<&WorkspacesCursor>,
+    size: i32,
+) -> Result<WorkspacesSearchResponse, HandlerError> {
+    let search_query = parse_query(query)?;
+    let search_query = &search_query;
+    let workspaces: Vec<(Workspace, Option<Cursor>)> = list_workspaces()
+        .await?
+        .into_iter()
+        .filter(|workspace| workspace.is_created)
+        .filter(|workspace| remote || matches!(workspace.kind, WorkspaceKind::Local(_)))
+        .filter_map(|workspace| match after {
+            None => Some((workspace, None)),
+            Some(cursor) => cursor
+                .positions
+                .get(&workspace.slug)
+                .cloned()
+                .map(|position| (workspace, position)),
+        })
+        .collect();
+
+    let results = join_all(workspaces.iter().map(|(workspace, position)| {
+        let page = PageRequest {
+            after: position.clone(),
+            page: 0,
+            size,
+        };
+
+        async move { search_workspace(workspace, search_query, &page).await }
+    }))
+    .await;
+
+    let mut pages = vec![];
+    let mut failed_workspaces = vec![];
+    let mut total = 0;
+
+    for ((workspace, position), result) in workspaces.into_iter().zip(results) {
+        match result {
+            Ok((page, workspace_total)) => {
+                total += workspace_total;
+                pages.push((workspace, position, page.data.into_iter(), page.next));
+            }
+            Err(e) => {
+                error!("Failed to search workspace {}: {:?}", workspace.slug, e);
+                failed_workspaces.push(FailedWorkspace {
+                    workspace: workspace.slug,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+
+    let mut data: Vec<WorkspaceUnit> = vec![];
+    let mut positions: Vec<Option<Cursor>> = pages
+        .iter()
+        .map(|(_, position, _, _)| position.clone())
+        .collect();
+
+    'pages: loop {
+        let mut taken = false;
+
+        for ((workspace, _, units, _), position) in pages.iter_mut().zip(positions.iter_mut()) {
+            if data.len() >= size as usize {
+                break 'pages;
+            }
+
+            if let Some(unit) = units.next() {
+                *position = Some(Cursor::rank(unit.rank.unwrap_or_default(), unit.id));
+                data.push(WorkspaceUnit {
+                    workspace: workspace.slug.clone(),
+                    unit,
+                });
+                taken = true;
+            }
+        }
+
+        if !taken {
+            break;
+        }
+    }
+
+    // A workspace that has units left on its page continues after the last
+    // unit that was taken, all others continue with the next page.
+    let mut next = BTreeMap::new();
+    for ((workspace, _, mut units, page_next), position) in pages.into_iter().zip(positions) {
+        if units.next().is_some() {
+            next.insert(workspace.slug, position);
+        } else if page_next.is_some() {
+            next.insert(workspace.slug, page_next);
+        }
+    }
+
+    Ok(WorkspacesSearchResponse {
+        data,
+        total,
+        next: if next.is_empty() {
+            None
+        } else {
+            Some(WorkspacesCursor { positions: next })
+        },
+        failed_workspaces,
+    })
+}
+
+#[instrument]
+pub async fn search_facets(workspace: &str, query: &str) -> Result<Facets, HandlerError> {
+    ensure_workspace(workspace).await?;
+
+    let database = workspace_database(workspace).await?;
+    let search_store = search_store(database);
+
+    let search_query = parse_query(query)?;
+    let facets = search_store.facets(&search_query).await?;
+
+    Ok(facets)
+}
+
+/// Cluster all units of a workspace by their duplicates. Local workspaces
+/// cluster their units in a background task.
+#[instrument]
+pub async fn detect_duplicates(workspace: &str) -> Result<(), HandlerError> {
+    let workspace = lookup_workspace(workspace).await?;
+
+    match workspace.kind {
+        WorkspaceKind::Local(_) => {
+            let actor = TaskActor::from_registry().await.unwrap();
+            actor.call(DetectDuplicates { workspace }).await??;
+        }
+        WorkspaceKind::Remote(_) => {
+            let database = workspace_database(&workspace.slug).await?;
+            let duplicate_store = duplicate_store(database);
+            duplicate_store.detect().await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[instrument]
+pub async fn list_duplicates(workspace: &str) -> Result<Vec<DuplicateCluster>, HandlerError> {
+    ensure_workspace(workspace).await?;
+
+    let database = workspace_database(workspace).await?;
+    let duplicate_store = duplicate_store(database);
+
+    let clusters = duplicate_store.list().await?;
+
+    Ok(clusters)
+}
+
+/// Compute the perceptual hashes of all downloads that aren't hashed yet. Local
+/// workspaces hash their downloads in a background task.
+#[instrument]
+pub async fn hash_media(workspace: &str) -> Result<(), HandlerError> {
+    let workspace = lookup_workspace(workspace).await?;
+
+    match workspace.kind {
+        WorkspaceKind::Local(_) => {
+            let actor = TaskActor::from_registry().await.unwrap();
+            actor.call(HashMedia { workspace }).await??;
+        }
+        WorkspaceKind::Remote(_) => {
+            let database = workspace_database(&workspace.slug).await?;
+            let media_store = media_store(database);
+            media_store.hash().await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[instrument]
+pub async fn list_similar_media(
+    workspace: &str,
+    unit: i32,
+    max_distance: u32,
+) -> Result<Vec<SimilarMedia>, HandlerError> {
+    ensure_workspace(workspace).await?;
+
+    let database = workspace_database(workspace).await?;
+    let media_store = media_store(database);
+
+    let similar = media_store.similar(unit, max_distance).await?;
+
+    Ok(similar)
+}
+
+/// Verify the digests of all downloads of a workspace. Local workspaces verify
+/// their downloads in a background task.
+#[instrument]
+pub async fn verify_downloads(workspace: &str) -> Result<(), HandlerError> {
+    let workspace = lookup_workspace(workspace).await?;
+
+    match workspace.kind {
+        WorkspaceKind::Local(_) => {
+            let actor = TaskActor::from_registry().await.unwrap();
+            actor.call(VerifyDownloads { workspace }).await??;
+        }
+        WorkspaceKind::Remote(_) => {
+            let database = workspace_database(&workspace.slug).await?;
+            let integrity_store = integrity_store(database);
+            integrity_store.verify().await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify the digests of all downloads of a local workspace and wait for the
+/// report.
+#[instrument]
+pub async fn run_download_verification(workspace: &str) -> Result<IntegrityReport, HandlerError> {
+    let workspace = lookup_workspace(workspace).await?;
+    let database = workspace_database(&workspace.slug).await?;
+
+    let (mut tx, mut rx) = mpsc::channel(100);
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            info!("{}", msg);
+        }
+    });
+
+    let id = ncube_tasks::verify_downloads(workspace, database.clone(), &mut tx).await?;
+
+    let integrity_store = integrity_store(database);
+    let report = integrity_store
+        .show(id)
+        .await?
+        .ok_or_else(|| HandlerError::NotFound(format!("Integrity report {}", id)))?;
+
+    Ok(report)
+}
+
+#[instrument]
+pub async fn list_integrity_reports(workspace: &str) -> Result<Vec<IntegrityReport>, HandlerError> {
+    ensure_workspace(workspace).await?;
+
+    let database = workspace_database(workspace).await?;
+    let integrity_store = integrity_store(database);
+
+    let reports = integrity_store.list().await?;
+
+    Ok(reports)
+}
+
+#[instrument]
+pub async fn show_integrity_report(
+    workspace: &str,
+    id: i32,
+) -> Result<Option<IntegrityReport>, HandlerError> {
+    ensure_workspace(workspace).await?;
+
+    let database = workspace_database(workspace).await?;
+    let integrity_store = integrity_store(database);
+
+    let report = integrity_store.show(id).await?;
+
+    Ok(report)
+}
+
+/// Replay the custody chain of a workspace. `anchor` is a head of the chain
+/// that was recorded earlier outside of the workspace.
+#[instrument]
+pub async fn verify_custody_chain(
+    workspace: &str,
+    anchor: Option<&str>,
+) -> Result<CustodyChain, HandlerError> {
+    ensure_workspace(workspace).await?;
+
+    let database = workspace_database(workspace).await?;
+    let custody_store = custody_store(database);
+
+    let chain = custody_store.verify_chain(anchor).await?;
+
+    Ok(chain)
+}
+
 #[instrument]
 pub async fn migrate(workspace: &str) -> Result<(), HandlerError> {
     let host_actor = HostActor::from_registry().await.unwrap();
@@ -248,6 +585,13 @@ pub async fn migrate(workspace: &str) -> Result<(), HandlerError> {
                 .await
                 .map_err(|e| HandlerError::Database(DatabaseError::SqlitePool(e)))?;
             migrations::migrate_workspace(&mut **conn)?;
+            // The pool holds a single connection.
+            drop(conn);
+
+            // Migrations record custody events for existing units.
+            let custody_store = custody_store(Database::Sqlite(Box::new(db)));
+            custody_store.seal().await?;
+
             Ok(())
         }
         _ => Err(HandlerError::Invalid(format!(
@@ -274,8 +618,16 @@ pub async fn create_segment(
         )));
     };
 
+    // Reject segments that could never be searched.
+    parse_query(&segment_req.query)?;
+
     segment_store
-        .create(&segment_req.query, &segment_req.title, &segment_req.slug())
+        .create(
+            &segment_req.query,
+            &segment_req.title,
+            &segment_req.slug(),
+            segment_req.sort,
+        )
         .await?;
 
     Ok(())
@@ -332,21 +684,41 @@ pub async fn update_segment(
         )));
     }
 
+    parse_query(&segment_req.query)?;
+
     segment_store
-        .update(&slug, &segment_req.query, &segment_req.title)
+        .update(
+            slug,
+            &segment_req.query,
+            &segment_req.title,
+            segment_req.sort,
+        )
         .await?;
 
     Ok(())
 }
 
+/// A download is either read from the disk of a local workspace or streamed
+/// from a remote workspace.
+#[derive(Debug)]
+pub enum ServedFile {
+    Local(File),
+    /// The remote workspace already answered any range request.
+    Remote(RemoteFile),
+}
+
 #[instrument]
-pub async fn show_download(workspace: &str, file_path: &str) -> Result<File, HandlerError> {
+pub async fn show_download(
+    workspace: &str,
+    file_path: &str,
+    range: Option<String>,
+) -> Result<ServedFile, HandlerError> {
     let workspace = lookup_workspace(workspace).await?;
 
     match workspace.kind {
         WorkspaceKind::Local(location) => {
             match File::open(format!("{}/data/{}", &location, &file_path)).await {
-                Ok(file) => Ok(file),
+                Ok(file) => Ok(ServedFile::Local(file)),
                 Err(err) => match err.kind() {
                     std::io::ErrorKind::NotFound => Err(HandlerError::NotFound(file_path.into())),
                     _ => Err(HandlerError::NotAllowed(file_path.into())),
@@ -354,10 +726,131 @@ pub async fn show_download(workspace: &str, file_path: &str) -> Result<File, Han
             }
         }
         WorkspaceKind::Remote(_) => {
-            // FIXME: Downloads only work on local workspaces right now. To
-            // support remote workspaces as well I will probably need to
-            // refactor to return something else than `File`.
-            todo!()
+            let database = workspace_database(&workspace.slug).await?;
+            let media_store = media_store(database.clone());
+
+            let metadata = match media_store.download_metadata(file_path).await {
+                Ok(metadata) => metadata,
+                Err(DatabaseError::NotFound(_)) => {
+                    return Err(HandlerError::NotFound(file_path.into()))
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            // Downloads with a known digest are kept in the media cache, and
+            // served from there like the downloads of local workspaces.
+            if let Some(sha256) = metadata.sha256 {
+                let host_actor = HostActor::from_registry().await.unwrap();
+                let cache = host_actor.call(MediaCacheSetting).await??;
+
+                if let Some(path) = cache.get(&sha256) {
+                    if let Ok(file) = File::open(path).await {
+                        return Ok(ServedFile::Local(file));
+                    }
+                }
+
+                if metadata.size_in_bytes <= cache.max_size() {
+                    let file_path = file_path.to_string();
+
+                    if range.is_some() {
+                        // A range of a download can't fill the cache, the
+                        // whole download is fetched in the background.
+                        tokio::spawn(async move {
+                            if let Err(e) =
+                                ncube_tasks::cache_download(cache, database, file_path, sha256)
+                                    .await
+                            {
+                                error!("failed to cache download: {}", e);
+                            }
+                        });
+                    } else if let Some(reserved) = cache.reserve(&sha256)? {
+                        // The download that is served is written into the
+                        // cache as well. Requests that arrive while the
+                        // cache is filled are served from the remote
+                        // workspace, the cache is only filled once.
+                        let remote = match media_store.download(&file_path, None).await {
+                            Ok(remote) => remote,
+                            Err(err) => {
+                                let _ = std::fs::remove_file(&reserved.0);
+                                return match err {
+                                    DatabaseError::NotFound(_) => {
+                                        Err(HandlerError::NotFound(file_path))
+                                    }
+                                    err => Err(err.into()),
+                                };
+                            }
+                        };
+
+                        return Ok(ServedFile::Remote(ncube_tasks::tee_download(
+                            cache, reserved, file_path, sha256, remote,
+                        )));
+                    }
+                }
+            }
+
+            match media_store.download(file_path, range).await {
+                Ok(file) => Ok(ServedFile::Remote(file)),
+                Err(DatabaseError::NotFound(_)) => Err(HandlerError::NotFound(file_path.into())),
+                Err(err) => Err(err.into()),
+            }
+        }
+    }
+}
+
+/// Generate the missing thumbnails of all downloads. Local workspaces generate
+/// their thumbnails in a background task.
+#[instrument]
+pub async fn generate_thumbnails(workspace: &str) -> Result<(), HandlerError> {
+    let workspace = lookup_workspace(workspace).await?;
+
+    match workspace.kind {
+        WorkspaceKind::Local(_) => {
+            let actor = TaskActor::from_registry().await.unwrap();
+            actor.call(GenerateThumbnails { workspace }).await??;
+        }
+        WorkspaceKind::Remote(_) => {
+            let database = workspace_database(&workspace.slug).await?;
+            let media_store = media_store(database);
+            media_store.thumbnails().await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Open the thumbnail of a download. Videos have a contact sheet in addition
+/// to their poster frame.
+#[instrument]
+pub async fn show_thumbnail(
+    workspace: &str,
+    file_path: &str,
+    sheet: bool,
+    range: Option<String>,
+) -> Result<ServedFile, HandlerError> {
+    let workspace = lookup_workspace(workspace).await?;
+
+    match workspace.kind {
+        WorkspaceKind::Local(location) => {
+            let thumbnail = ncube_tasks::thumbnail_location(file_path, sheet);
+            match File::open(format!("{}/{}", &location, &thumbnail)).await {
+                Ok(file) => Ok(ServedFile::Local(file)),
+                Err(err) => match err.kind() {
+                    std::io::ErrorKind::NotFound => Err(HandlerError::NotFound(thumbnail)),
+                    _ => Err(HandlerError::NotAllowed(thumbnail)),
+                },
+            }
+        }
+        WorkspaceKind::Remote(_) => {
+            let database = workspace_database(&workspace.slug).await?;
+            let media_store = media_store(database);
+
+            match media_store.thumbnail(file_path, sheet, range).await {
+                Ok(file) => Ok(ServedFile::Remote(file)),
+                Err(DatabaseError::NotFound(_)) => Err(HandlerError::NotFound(
+                    ncube_tasks::thumbnail_location(file_path, sheet),
+                )),
+                Err(err) => Err(err.into()),
+            }
         }
     }
 }
@@ -369,13 +862,23 @@ pub async fn show_download_meta(
 ) -> Result<FileMetadata, HandlerError> {
     let workspace = lookup_workspace(workspace).await?;
 
-    match workspace.kind {
+    match &workspace.kind {
         WorkspaceKind::Local(location) => {
             match File::open(format!("{}/data/{}", &location, &file_path)).await {
                 Ok(file) => {
                     let metadata = file.metadata().await?;
+
+                    let location = format!("data/{}", file_path);
+                    let database = workspace_database(&workspace.slug).await?;
+                    let sha256 = media_store(database.clone()).digest(&location).await?;
+                    let media =
+                        ncube_tasks::extract_metadata(workspace.clone(), database, &location)
+                            .await?;
+
                     Ok(FileMetadata {
                         size_in_bytes: metadata.len(),
+                        sha256,
+                        media,
                     })
                 }
                 Err(err) => match err.kind() {
@@ -385,10 +888,14 @@ pub async fn show_download_meta(
             }
         }
         WorkspaceKind::Remote(_) => {
-            // FIXME: Downloads only work on local workspaces right now. To
-            // support remote workspaces as well I will probably need to
-            // refactor to return something else than `File`.
-            todo!()
+            let database = workspace_database(&workspace.slug).await?;
+            let media_store = media_store(database);
+
+            match media_store.download_metadata(file_path).await {
+                Ok(metadata) => Ok(metadata),
+                Err(DatabaseError::NotFound(_)) => Err(HandlerError::NotFound(file_path.into())),
+                Err(err) => Err(err.into()),
+            }
         }
     }
 }
@@ -410,11 +917,15 @@ pub async fn create_methodology(
         )));
     };
 
+    let initial_state = initial_state(&methodology_req.process)
+        .map_err(|e| HandlerError::Invalid(e.to_string()))?;
+
     methodology_store
         .create(
             &methodology_req.title,
             &methodology_req.description,
             &methodology_req.process,
+            &initial_state,
             &methodology_req.slug(),
         )
         .await?;
@@ -422,6 +933,113 @@ pub async fn create_methodology(
     Ok(())
 }
 
+/// Update a methodology. Investigations that started with an earlier version
+/// of the methodology keep using it.
+#[instrument]
+pub async fn update_methodology(
+    workspace: &str,
+    slug: &str,
+    methodology_req: &MethodologyReq,
+) -> Result<(), HandlerError> {
+    ensure_workspace(workspace).await?;
+
+    let database = workspace_database(workspace).await?;
+    let methodology_store = methodology_store(database);
+
+    if let Ok(false) = methodology_store.exists(slug).await {
+        return Err(HandlerError::NotFound(format!(
+            "Methodology '{}' could not be found.",
+            slug
+        )));
+    }
+
+    if methodology_req.slug() != slug {
+        if let Ok(true) = methodology_store.exists(&methodology_req.slug()).await {
+            return Err(HandlerError::Invalid(format!(
+                "Methodology `{}` already exists.",
+                methodology_req.slug(),
+            )));
+        };
+    }
+
+    let initial_state = initial_state(&methodology_req.process)
+        .map_err(|e| HandlerError::Invalid(e.to_string()))?;
+
+    methodology_store
+        .update(
+            slug,
+            &methodology_req.title,
+            &methodology_req.description,
+            &methodology_req.process,
+            &initial_state,
+        )
+        .await
+        .map_err(remote_error)?;
+
+    Ok(())
+}
+
+/// Remove a methodology and all its versions. Methodologies that are used by
+/// investigations can't be removed.
+#[instrument]
+pub async fn remove_methodology(workspace: &str, slug: &str) -> Result<(), HandlerError> {
+    let workspace = lookup_workspace(workspace).await?;
+    let database = workspace_database(&workspace.slug).await?;
+    let methodology_store = methodology_store(database);
+
+    if let Ok(false) = methodology_store.exists(slug).await {
+        return Err(HandlerError::NotFound(format!(
+            "Methodology '{}' could not be found.",
+            slug
+        )));
+    }
+
+    // Remote workspaces check the investigations on their side, and answer
+    // with the same error.
+    if let WorkspaceKind::Local(_) = workspace.kind {
+        if methodology_store.in_use(slug).await? {
+            return Err(HandlerError::Invalid(format!(
+                "Methodology `{}` is used by investigations and can't be removed.",
+                slug
+            )));
+        }
+    }
+
+    methodology_store.delete(slug).await.map_err(remote_error)?;
+
+    Ok(())
+}
+
+#[instrument]
+pub async fn show_methodology_version(
+    workspace: &str,
+    slug: &str,
+    version: i32,
+) -> Result<Methodology, HandlerError> {
+    ensure_workspace(workspace).await?;
+
+    let database = workspace_database(workspace).await?;
+    let methodology_store = methodology_store(database);
+    let methodology = methodology_store.show_version(slug, version).await?;
+
+    methodology.ok_or_else(|| {
+        HandlerError::NotFound(format!(
+            "Version {} of methodology '{}' could not be found.",
+            version, slug
+        ))
+    })
+}
+
+/// Validate the process of a methodology and compute its initial state.
+pub(crate) fn initial_state(
+    process: &serde_json::Value,
+) -> Result<serde_json::Value, MethodologyError> {
+    let machine = MethodologyProcess::from_json(process)?;
+    machine.validate()?;
+
+    Ok(machine.initial_state())
+}
+
 #[instrument]
 pub async fn show_methodology(workspace: &str, slug: &str) -> Result<Methodology, HandlerError> {
     ensure_workspace(&workspace).await?;
@@ -446,6 +1064,73 @@ pub async fn list_methodologies(workspace: &str) -> Result<Vec<Methodology>, Han
     Ok(methodologies)
 }
 
+/// Export a methodology as a standalone file that can be imported into other
+/// workspaces.
+#[instrument]
+pub async fn export_methodology(
+    workspace: &str,
+    slug: &str,
+    format: MethodologyFormat,
+) -> Result<Vec<u8>, HandlerError> {
+    let methodology = show_methodology(workspace, slug).await?;
+
+    MethodologyReq::from(methodology)
+        .to_file(format)
+        .map_err(|e| HandlerError::Invalid(e.to_string()))
+}
+
+/// Import a methodology file. `conflict` decides what happens if the slug of
+/// the methodology is taken already.
+#[instrument(skip(data))]
+pub async fn import_methodology(
+    workspace: &str,
+    data: &[u8],
+    conflict: ImportConflict,
+) -> Result<Methodology, HandlerError> {
+    ensure_workspace(workspace).await?;
+
+    let mut methodology_req =
+        MethodologyReq::from_file(data).map_err(|e| HandlerError::Invalid(e.to_string()))?;
+    initial_state(&methodology_req.process).map_err(|e| HandlerError::Invalid(e.to_string()))?;
+
+    let database = workspace_database(workspace).await?;
+    let methodology_store = methodology_store(database);
+    let slug = methodology_req.slug();
+
+    if !methodology_store.exists(&slug).await? {
+        create_methodology(workspace, &methodology_req).await?;
+        return show_methodology(workspace, &slug).await;
+    }
+
+    match conflict {
+        ImportConflict::Fail => {
+            return Err(HandlerError::Invalid(format!(
+                "Methodology `{}` already exists.",
+                slug
+            )))
+        }
+        ImportConflict::Replace => {
+            update_methodology(workspace, &slug, &methodology_req).await?;
+        }
+        ImportConflict::Rename => {
+            let title = methodology_req.title.clone();
+            let mut counter = 2;
+
+            loop {
+                methodology_req.title = format!("{} ({})", title, counter);
+                if !methodology_store.exists(&methodology_req.slug()).await? {
+                    break;
+                }
+                counter += 1;
+            }
+
+            create_methodology(workspace, &methodology_req).await?;
+        }
+    };
+
+    show_methodology(workspace, &methodology_req.slug()).await
+}
+
 #[instrument]
 pub async fn create_investigation(
     workspace: &str,
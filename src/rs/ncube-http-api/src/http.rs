@@ -1,10 +1,19 @@
-use futures::TryFutureExt;
+use futures::{StreamExt, TryFutureExt};
 use headers::{AcceptRanges, ContentLength, ContentRange, ContentType, HeaderMapExt, LastModified};
 use ncube_crypto::jwt_verify;
-use ncube_data::{ErrorResponse, ReqCtx};
-use ncube_db::DatabaseError;
+use ncube_data::{
+    ErrorResponse, ExportColumn, ExportFormat, ExportOptions, InvalidCursor, InvalidExportColumn,
+    PageRequest, QueryError, ReqCtx, SortOrder,
+};
+use ncube_db::{http::RemoteFile, DatabaseError};
 use ncube_errors::HostError;
-use ncube_handlers::{config::show_secret_key, HandlerError};
+use ncube_handlers::{
+    config::show_secret_key,
+    export::{Export, ExportEntry, UnitExport},
+    workspace::ServedFile,
+    HandlerError,
+};
+use serde::Deserialize;
 use std::{
     convert::Infallible,
     future::Future,
@@ -14,16 +23,80 @@ use std::{
 };
 use tokio::fs::File as TkFile;
 use tracing::{debug, error};
-use warp::{http::StatusCode, hyper::Body, reject::Rejection, reply::Response, Filter};
+use warp::{
+    http::{
+        header::{
+            ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+            LAST_MODIFIED,
+        },
+        StatusCode,
+    },
+    hyper::Body,
+    reject::Rejection,
+    reply::{Reply, Response},
+    Filter,
+};
 
 use crate::{
     fs::{bytes_range, file_metadata, file_stream, optimal_buf_size, File},
     headers::{HeaderParseError, HttpRange},
+    zip::{zip_stream, ZipSource},
 };
 
+/// Build the page request of a paginated list. The cursor takes precedence
+/// over the offset based page, an empty cursor requests the first page.
+pub(crate) fn page_request(
+    page: Option<i32>,
+    size: Option<i32>,
+    cursor: Option<&str>,
+) -> Result<PageRequest, Rejection> {
+    let after = match cursor {
+        None | Some("") => None,
+        Some(cursor) => Some(cursor.parse().map_err(|err: InvalidCursor| {
+            warp::reject::custom(HandlerError::Invalid(err.to_string()))
+        })?),
+    };
+
+    Ok(PageRequest {
+        after,
+        page: page.unwrap_or(0),
+        size: size.unwrap_or(20),
+    })
+}
+
+/// The query parameters of unit exports. `q` and `sort` select the units of a
+/// search, `state` the units of an investigation segment.
+#[derive(Debug, Deserialize)]
+pub(crate) struct UnitExportQuery {
+    pub q: Option<String>,
+    pub sort: Option<SortOrder>,
+    pub state: Option<String>,
+    pub format: Option<ExportFormat>,
+    pub columns: Option<String>,
+}
+
+impl UnitExportQuery {
+    pub(crate) fn options(&self) -> Result<ExportOptions, Rejection> {
+        let columns = match &self.columns {
+            Some(columns) => {
+                ExportColumn::parse_list(columns).map_err(|err: InvalidExportColumn| {
+                    warp::reject::custom(HandlerError::Invalid(err.to_string()))
+                })?
+            }
+            None => ExportColumn::ALL.to_vec(),
+        };
+
+        Ok(ExportOptions {
+            format: self.format.unwrap_or_default(),
+            columns,
+        })
+    }
+}
+
 pub(crate) async fn handle_rejection(err: warp::Rejection) -> Result<impl warp::Reply, Infallible> {
     let code;
     let message;
+    let mut query = None;
 
     error!("{:?}", err);
 
@@ -36,9 +109,36 @@ pub(crate) async fn handle_rejection(err: warp::Rejection) -> Result<impl warp::
     } else if let Some(HandlerError::Invalid(reason)) = err.find() {
         code = StatusCode::BAD_REQUEST;
         message = reason.into();
+    } else if let Some(HandlerError::Search(reason)) = err.find() {
+        code = StatusCode::BAD_REQUEST;
+        message = reason.to_string();
+        query = Some(QueryError {
+            position: reason.position,
+            expected: reason.expected.clone(),
+        });
+    } else if let Some(HandlerError::Database(DatabaseError::Search(reason))) = err.find() {
+        code = StatusCode::BAD_REQUEST;
+        message = reason.to_string();
+        query = Some(QueryError {
+            position: reason.position,
+            expected: reason.expected.clone(),
+        });
+    } else if let Some(HandlerError::Database(DatabaseError::HttpFail(ErrorResponse {
+        errors,
+        query: Some(reason),
+        ..
+    }))) = err.find()
+    {
+        // The search query of a remote workspace failed to parse.
+        code = StatusCode::BAD_REQUEST;
+        message = errors.clone();
+        query = Some(reason.clone());
     } else if let Some(HandlerError::NotFound(reason)) = err.find() {
         code = StatusCode::NOT_FOUND;
         message = reason.into();
+    } else if let Some(HandlerError::Conflict(reason)) = err.find() {
+        code = StatusCode::CONFLICT;
+        message = reason.into();
     } else if let Some(HandlerError::NotAllowed(reason)) = err.find() {
         code = StatusCode::FORBIDDEN;
         message = reason.to_string();
@@ -63,7 +163,11 @@ pub(crate) async fn handle_rejection(err: warp::Rejection) -> Result<impl warp::
         message = "UNHANDLED_REJECTION".into();
     }
 
-    let json = warp::reply::json(&ErrorResponse::new(code, &message));
+    let mut response = ErrorResponse::new(code, &message);
+    if let Some(query) = query {
+        response = response.with_query(query);
+    }
+    let json = warp::reply::json(&response);
 
     Ok(warp::reply::with_status(json, code))
 }
@@ -194,6 +298,11 @@ pub(crate) struct Conditionals {
 }
 
 impl Conditionals {
+    /// The raw `Range` header, remote workspaces parse it themselves.
+    pub(crate) fn range(&self) -> Option<String> {
+        self.range.clone()
+    }
+
     pub(crate) fn parse_range(&self, size: u64) -> Option<HttpRange> {
         match &self.range {
             // Only one range header is supported.
@@ -259,3 +368,97 @@ pub(crate) fn send_file(
         File { resp }
     })
 }
+
+/// Send a file that is streamed from a remote workspace. The remote workspace
+/// answered the range request already, its status and content headers are
+/// passed on.
+pub(crate) fn send_remote_file(file: RemoteFile) -> Response {
+    let mut resp = Response::new(Body::wrap_stream(file.body));
+    *resp.status_mut() = file.status;
+
+    for name in &[
+        CONTENT_TYPE,
+        CONTENT_LENGTH,
+        CONTENT_RANGE,
+        ACCEPT_RANGES,
+        LAST_MODIFIED,
+        CONTENT_DISPOSITION,
+    ] {
+        if let Some(value) = file.headers.get(name) {
+            resp.headers_mut().insert(name, value.clone());
+        }
+    }
+
+    resp
+}
+
+/// Send a download of a local or a remote workspace.
+pub(crate) async fn send_download(
+    file: ServedFile,
+    path: String,
+    conditionals: Conditionals,
+) -> Result<Response, Rejection> {
+    match file {
+        ServedFile::Local(file) => Ok(send_file(file, path, conditionals).await?.into_response()),
+        ServedFile::Remote(file) => Ok(send_remote_file(file)),
+    }
+}
+
+/// Send the ZIP archive of an export. The archive of a local workspace
+/// contains the downloads at their location in the workspace, followed by the
+/// manifest as JSON and CSV.
+pub(crate) fn send_export(export: Export, filename: &str) -> Response {
+    let (created_at, entries) = match export {
+        Export::Local {
+            created_at,
+            entries,
+        } => (created_at, entries),
+        Export::Remote(file) => return send_remote_file(file),
+    };
+
+    let entries = entries.map(|entry| match entry {
+        Ok(ExportEntry::Download { location, path }) => Ok((location, ZipSource::File(path))),
+        Ok(ExportEntry::Manifest { name, file }) => Ok((name, ZipSource::Spooled(file))),
+        Err(err) => Err(std::io::Error::other(err.to_string())),
+    });
+
+    let mut resp = Response::new(Body::wrap_stream(zip_stream(entries, created_at)));
+    resp.headers_mut().insert(
+        CONTENT_TYPE,
+        warp::http::HeaderValue::from_static("application/zip"),
+    );
+    if let Ok(value) =
+        warp::http::HeaderValue::from_str(&format!("attachment; filename=\"{}\"", filename))
+    {
+        resp.headers_mut().insert(CONTENT_DISPOSITION, value);
+    }
+
+    resp
+}
+
+/// Send a unit export. `name` is the file name of the export without its
+/// extension.
+pub(crate) fn send_unit_export(export: UnitExport, name: &str, format: ExportFormat) -> Response {
+    let stream = match export {
+        UnitExport::Local(stream) => stream,
+        UnitExport::Remote(file) => return send_remote_file(file),
+    };
+
+    let content_type = match format {
+        ExportFormat::Csv => "text/csv; charset=utf-8",
+        ExportFormat::Ndjson => "application/x-ndjson",
+    };
+
+    let mut resp = Response::new(Body::wrap_stream(stream));
+    resp.headers_mut().insert(
+        CONTENT_TYPE,
+        warp::http::HeaderValue::from_static(content_type),
+    );
+    if let Ok(value) =
+        warp::http::HeaderValue::from_str(&format!("attachment; filename=\"{}.{}\"", name, format))
+    {
+        resp.headers_mut().insert(CONTENT_DISPOSITION, value);
+    }
+
+    resp
+}
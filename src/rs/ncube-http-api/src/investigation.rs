@@ -1,11 +1,16 @@
-use ncube_data::{AnnotationReq, InvestigationReq, ReqCtx, SuccessResponse, VerifySegmentReq};
-use ncube_handlers::{investigation as investigation_handlers, workspace as handlers};
+use ncube_data::{
+    AnnotationReq, InvestigationReq, ReqCtx, SuccessResponse, UnitStateReq, VerifySegmentReq,
+};
+use ncube_handlers::{
+    export::{self as export_handlers, UnitSelection},
+    investigation as investigation_handlers, workspace as handlers,
+};
 use percent_encoding::percent_decode_str;
 use serde::Deserialize;
 use tracing::instrument;
 use warp::Filter;
 
-use crate::http::authenticate_remote_req;
+use crate::http::{authenticate_remote_req, send_export, send_unit_export, UnitExportQuery};
 
 #[derive(Debug, Deserialize)]
 pub struct UnitsOptions {
@@ -96,23 +101,77 @@ async fn list_units(
 }
 
 #[instrument]
-async fn update_state(
+async fn export(
+    _ctx: ReqCtx,
+    workspace: String,
+    investigation: String,
+    segment: String,
+    opts: UnitsOptions,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let state = opts
+        .state
+        .map(|value| percent_decode_str(&value).decode_utf8_lossy().to_string());
+
+    let export =
+        export_handlers::export_investigation(&workspace, &investigation, &segment, state).await?;
+
+    Ok(send_export(
+        export,
+        &format!("{}-{}-{}.zip", workspace, investigation, segment),
+    ))
+}
+
+#[instrument]
+async fn export_units(
     _ctx: ReqCtx,
     workspace: String,
     investigation: String,
     segment: String,
+    opts: UnitExportQuery,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let options = opts.options()?;
+    let format = options.format;
+    let state = opts
+        .state
+        .map(|value| percent_decode_str(&value).decode_utf8_lossy().to_string());
+
+    let name = format!("{}-{}-{}-units", workspace, investigation, segment);
+    let selection = UnitSelection::Investigation {
+        investigation,
+        segment,
+        state,
+    };
+    let export = export_handlers::export_units(&workspace, selection, options).await?;
+
+    Ok(send_unit_export(export, &name, format))
+}
+
+#[instrument]
+async fn update_state(
+    ctx: ReqCtx,
+    workspace: String,
+    investigation: String,
+    segment: String,
     unit: i32,
-    state: serde_json::Value,
+    state_req: UnitStateReq,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    investigation_handlers::update_unit_state(&workspace, &investigation, &segment, unit, &state)
-        .await?;
+    let state = investigation_handlers::update_unit_state(
+        &workspace,
+        &investigation,
+        &segment,
+        unit,
+        &state_req,
+        ctx.email,
+    )
+    .await?;
+    let response = SuccessResponse::new(state);
 
-    Ok(warp::reply())
+    Ok(warp::reply::json(&response))
 }
 
 #[instrument]
 async fn create_annotation(
-    _ctx: ReqCtx,
+    ctx: ReqCtx,
     workspace: String,
     investigation: String,
     verification: i32,
@@ -123,6 +182,7 @@ async fn create_annotation(
         &investigation,
         verification,
         &annotation_req,
+        ctx.email,
     )
     .await?;
 
@@ -144,6 +204,22 @@ async fn list_annotations(
     Ok(warp::reply::json(&response))
 }
 
+#[instrument]
+async fn history(
+    _ctx: ReqCtx,
+    workspace: String,
+    investigation: String,
+    verification: i32,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let history =
+        investigation_handlers::verification_history(&workspace, &investigation, verification)
+            .await?;
+
+    let response = SuccessResponse::new(history);
+
+    Ok(warp::reply::json(&response))
+}
+
 pub(crate) fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     authenticate_remote_req()
         .and(warp::path!("workspaces" / String / "investigations"))
@@ -189,14 +265,34 @@ pub(crate) fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::
             .and(warp::query::<UnitsOptions>())
             .and(warp::get())
             .and_then(list_units))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "investigations" / String / "segments" / String / "export"
+            ))
+            .and(warp::query::<UnitsOptions>())
+            .and(warp::get())
+            .and_then(export))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces"
+                    / String
+                    / "investigations"
+                    / String
+                    / "segments"
+                    / String
+                    / "units"
+                    / "export"
+            ))
+            .and(warp::query::<UnitExportQuery>())
+            .and(warp::get())
+            .and_then(export_units))
         .or(authenticate_remote_req()
             .and(warp::path!(
                 "workspaces" / String / "investigations" / String / "segments" / String / i32
             ))
             .and(warp::put())
             .and(warp::body::json())
-            .and_then(update_state)
-            .map(|reply| warp::reply::with_status(reply, warp::http::StatusCode::NO_CONTENT)))
+            .and_then(update_state))
         .or(authenticate_remote_req()
             .and(warp::path!(
                 "workspaces" / String / "investigations" / String / "annotations" / i32
@@ -211,4 +307,16 @@ pub(crate) fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::
             ))
             .and(warp::get())
             .and_then(list_annotations))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces"
+                    / String
+                    / "investigations"
+                    / String
+                    / "verifications"
+                    / i32
+                    / "history"
+            ))
+            .and(warp::get())
+            .and_then(history))
 }
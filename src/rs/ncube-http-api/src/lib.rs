@@ -21,6 +21,7 @@ mod unit;
 mod user;
 mod workspace;
 mod ws;
+mod zip;
 
 fn with_cors() -> warp::cors::Builder {
     warp::cors()
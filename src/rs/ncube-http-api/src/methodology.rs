@@ -1,5 +1,7 @@
-use ncube_data::{MethodologyReq, ReqCtx, SuccessResponse};
+use bytes::Bytes;
+use ncube_data::{ImportConflict, MethodologyFormat, MethodologyReq, ReqCtx, SuccessResponse};
 use ncube_handlers::workspace as handlers;
+use serde::Deserialize;
 use tracing::instrument;
 use warp::Filter;
 
@@ -28,6 +30,89 @@ async fn show(
     Ok(warp::reply::json(&response))
 }
 
+#[instrument]
+async fn show_version(
+    _ctx: ReqCtx,
+    workspace: String,
+    methodology: String,
+    version: i32,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let methodology = handlers::show_methodology_version(&workspace, &methodology, version).await?;
+    let response = SuccessResponse::new(methodology);
+
+    Ok(warp::reply::json(&response))
+}
+
+#[instrument]
+async fn update(
+    _ctx: ReqCtx,
+    workspace: String,
+    methodology: String,
+    methodology_req: MethodologyReq,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    handlers::update_methodology(&workspace, &methodology, &methodology_req).await?;
+
+    Ok(warp::reply())
+}
+
+#[instrument]
+async fn remove(
+    _ctx: ReqCtx,
+    workspace: String,
+    methodology: String,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    handlers::remove_methodology(&workspace, &methodology).await?;
+
+    Ok(warp::reply())
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportQuery {
+    format: Option<MethodologyFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportQuery {
+    conflict: Option<ImportConflict>,
+}
+
+#[instrument]
+async fn export(
+    _ctx: ReqCtx,
+    workspace: String,
+    methodology: String,
+    query: ExportQuery,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let format = query.format.unwrap_or_default();
+    let file = handlers::export_methodology(&workspace, &methodology, format).await?;
+
+    let content_type = match format {
+        MethodologyFormat::Json => "application/json",
+        MethodologyFormat::Yaml => "application/yaml",
+    };
+    let disposition = format!("attachment; filename=\"{}.{}\"", methodology, format);
+
+    Ok(warp::reply::with_header(
+        warp::reply::with_header(file, "content-type", content_type),
+        "content-disposition",
+        disposition,
+    ))
+}
+
+#[instrument(skip(data))]
+async fn import(
+    _ctx: ReqCtx,
+    workspace: String,
+    query: ImportQuery,
+    data: Bytes,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let conflict = query.conflict.unwrap_or_default();
+    let methodology = handlers::import_methodology(&workspace, &data, conflict).await?;
+    let response = SuccessResponse::new(methodology);
+
+    Ok(warp::reply::json(&response))
+}
+
 #[instrument]
 async fn list(_ctx: ReqCtx, workspace: String) -> Result<impl warp::Reply, warp::Rejection> {
     let methodologies = handlers::list_methodologies(&workspace).await?;
@@ -53,4 +138,41 @@ pub(crate) fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::
             ))
             .and(warp::get())
             .and_then(show))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "methodologies" / String
+            ))
+            .and(warp::put())
+            .and(warp::body::json())
+            .and_then(update)
+            .map(|reply| warp::reply::with_status(reply, warp::http::StatusCode::NO_CONTENT)))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "methodologies" / String
+            ))
+            .and(warp::delete())
+            .and_then(remove)
+            .map(|reply| warp::reply::with_status(reply, warp::http::StatusCode::NO_CONTENT)))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "methodologies" / String / "versions" / i32
+            ))
+            .and(warp::get())
+            .and_then(show_version))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "methodologies" / String / "export"
+            ))
+            .and(warp::get())
+            .and(warp::query::<ExportQuery>())
+            .and_then(export))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "methodologies" / "import"
+            ))
+            .and(warp::post())
+            .and(warp::query::<ImportQuery>())
+            .and(warp::body::bytes())
+            .and_then(import)
+            .map(|reply| warp::reply::with_status(reply, warp::http::StatusCode::CREATED)))
 }
@@ -1,9 +1,12 @@
 use ncube_data::{ReqCtx, SegmentRequest, SuccessResponse};
-use ncube_handlers::workspace as handlers;
+use ncube_handlers::{
+    export::{self as export_handlers, UnitSelection},
+    workspace as handlers,
+};
 use tracing::instrument;
 use warp::Filter;
 
-use crate::http::authenticate_remote_req;
+use crate::http::{authenticate_remote_req, send_export, send_unit_export, UnitExportQuery};
 
 #[instrument]
 async fn create(
@@ -59,6 +62,37 @@ async fn remove(
     Ok(warp::reply())
 }
 
+#[instrument]
+async fn export(
+    _ctx: ReqCtx,
+    workspace: String,
+    segment: String,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let export = export_handlers::export_segment(&workspace, &segment).await?;
+
+    Ok(send_export(
+        export,
+        &format!("{}-{}.zip", workspace, segment),
+    ))
+}
+
+#[instrument]
+async fn export_units(
+    _ctx: ReqCtx,
+    workspace: String,
+    segment: String,
+    opts: UnitExportQuery,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let options = opts.options()?;
+    let format = options.format;
+
+    let name = format!("{}-{}-units", workspace, segment);
+    let export =
+        export_handlers::export_units(&workspace, UnitSelection::Segment(segment), options).await?;
+
+    Ok(send_unit_export(export, &name, format))
+}
+
 pub(crate) fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     authenticate_remote_req()
         .and(warp::path!("workspaces" / String / "segments"))
@@ -85,4 +119,17 @@ pub(crate) fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::
             .and(warp::delete())
             .and_then(remove)
             .map(|reply| warp::reply::with_status(reply, warp::http::StatusCode::NO_CONTENT)))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "segments" / String / "export"
+            ))
+            .and(warp::get())
+            .and_then(export))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "segments" / String / "units" / "export"
+            ))
+            .and(warp::get())
+            .and(warp::query::<UnitExportQuery>())
+            .and_then(export_units))
 }
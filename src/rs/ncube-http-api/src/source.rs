@@ -6,7 +6,7 @@ use serde::Deserialize;
 use tracing::instrument;
 use warp::Filter;
 
-use crate::http::authenticate_remote_req;
+use crate::http::{authenticate_remote_req, page_request};
 
 // The query parameters for list source.
 #[derive(Debug, Deserialize)]
@@ -14,6 +14,7 @@ pub struct ListOptions {
     pub page: Option<i32>,
     pub size: Option<i32>,
     pub q: Option<String>,
+    pub cursor: Option<String>,
 }
 
 #[instrument]
@@ -50,15 +51,15 @@ async fn list(
     workspace_slug: String,
     opts: ListOptions,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let sources = handlers::list_sources(
-        &workspace_slug,
-        opts.page.unwrap_or(0),
-        opts.size.unwrap_or(20),
-    )
-    .await?;
-    let response = SuccessResponse::new(sources);
-
-    Ok(warp::reply::json(&response))
+    let page = page_request(opts.page, opts.size, opts.cursor.as_deref())?;
+    let sources = handlers::list_sources(&workspace_slug, &page).await?;
+
+    // Requests without a cursor are answered with a plain list of sources, as
+    // older clients expect it.
+    match opts.cursor {
+        Some(_) => Ok(warp::reply::json(&SuccessResponse::new(sources))),
+        None => Ok(warp::reply::json(&SuccessResponse::new(sources.data))),
+    }
 }
 
 #[instrument]
@@ -92,6 +93,7 @@ async fn search(
     let response = SuccessResponse::new(SearchResponse {
         data,
         total: total.value,
+        next: None,
     });
 
     Ok(warp::reply::json(&response))
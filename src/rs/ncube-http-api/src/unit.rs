@@ -1,6 +1,7 @@
 use futures::try_join;
-use ncube_data::{ReqCtx, SearchResponse, SuccessResponse};
+use ncube_data::{ReqCtx, SearchResponse, SortOrder, SuccessResponse};
 use ncube_handlers::{
+    export::{self as export_handlers, UnitSelection},
     stat as stat_handlers, unit as unit_handlers, workspace as handlers, HandlerError,
 };
 use percent_encoding::percent_decode_str;
@@ -8,7 +9,10 @@ use serde::Deserialize;
 use tracing::instrument;
 use warp::Filter;
 
-use crate::http::{authenticate_remote_req, conditionals, send_file, Conditionals};
+use crate::http::{
+    authenticate_remote_req, conditionals, page_request, send_download, send_unit_export,
+    Conditionals, UnitExportQuery,
+};
 
 // The query parameters for list data.
 #[derive(Debug, Deserialize)]
@@ -16,6 +20,8 @@ pub struct ListOptions {
     pub page: Option<i32>,
     pub size: Option<i32>,
     pub q: Option<String>,
+    pub cursor: Option<String>,
+    pub sort: Option<SortOrder>,
 }
 
 #[instrument]
@@ -24,11 +30,16 @@ async fn data(
     workspace: String,
     opts: ListOptions,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let data =
-        handlers::list_data(&workspace, opts.page.unwrap_or(0), opts.size.unwrap_or(20)).await?;
-    let response = SuccessResponse::new(data);
+    let page = page_request(opts.page, opts.size, opts.cursor.as_deref())?;
+    let sort = opts.sort.unwrap_or_default();
+    let data = handlers::list_data(&workspace, &sort, &page).await?;
 
-    Ok(warp::reply::json(&response))
+    // Requests without a cursor are answered with a plain list of units, as
+    // older clients expect it.
+    match opts.cursor {
+        Some(_) => Ok(warp::reply::json(&SuccessResponse::new(data))),
+        None => Ok(warp::reply::json(&SuccessResponse::new(data.data))),
+    }
 }
 
 #[instrument]
@@ -48,25 +59,40 @@ async fn search(
     }
 
     let query_str = query.clone().unwrap();
+    let page = page_request(opts.page, opts.size, opts.cursor.as_deref())?;
+    let sort = opts.sort.unwrap_or_default();
 
     let (data, total) = try_join!(
-        handlers::search_data(
-            &workspace,
-            &query_str,
-            opts.page.unwrap_or(0),
-            opts.size.unwrap_or(20),
-        ),
+        handlers::search_data(&workspace, &query_str, &sort, &page),
         stat_handlers::stat_data_total(&workspace, query)
     )?;
 
     let response = SuccessResponse::new(SearchResponse {
-        data,
+        data: data.data,
         total: total.value,
+        next: data.next,
     });
 
     Ok(warp::reply::json(&response))
 }
 
+#[instrument]
+async fn facets(
+    _ctx: ReqCtx,
+    workspace: String,
+    opts: ListOptions,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let query = opts
+        .q
+        .map(|query| percent_decode_str(&query).decode_utf8_lossy().to_string())
+        .ok_or_else(|| HandlerError::Invalid("facets require a query parameter".into()))?;
+
+    let data = handlers::search_facets(&workspace, &query).await?;
+    let response = SuccessResponse::new(data);
+
+    Ok(warp::reply::json(&response))
+}
+
 #[instrument]
 async fn download(
     _ctx: ReqCtx,
@@ -77,12 +103,52 @@ async fn download(
     file_conditionals: Conditionals,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     let file_path = format!("{}/{}/{}", &unit_id, &kind, &file);
-    let file = handlers::show_download(&workspace, &file_path).await?;
-    let response = send_file(file, file_path.to_string(), file_conditionals).await?;
+    let file = handlers::show_download(&workspace, &file_path, file_conditionals.range()).await?;
+    let response = send_download(file, file_path.to_string(), file_conditionals).await?;
+
+    Ok(response)
+}
+
+// The query parameters for thumbnails.
+#[derive(Debug, Deserialize)]
+pub struct ThumbnailOptions {
+    pub sheet: Option<bool>,
+}
+
+#[instrument]
+async fn thumbnail(
+    _ctx: ReqCtx,
+    workspace: String,
+    unit_id: String,
+    kind: String,
+    file: String,
+    opts: ThumbnailOptions,
+    file_conditionals: Conditionals,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let file_path = format!("{}/{}/{}", &unit_id, &kind, &file);
+    let file = handlers::show_thumbnail(
+        &workspace,
+        &file_path,
+        opts.sheet.unwrap_or(false),
+        file_conditionals.range(),
+    )
+    .await?;
+    // Thumbnails are always JPEG images, the file name sets the content type.
+    let response = send_download(file, format!("{}.jpg", file_path), file_conditionals).await?;
 
     Ok(response)
 }
 
+#[instrument]
+async fn generate_thumbnails(
+    _ctx: ReqCtx,
+    workspace: String,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    handlers::generate_thumbnails(&workspace).await?;
+
+    Ok(warp::reply())
+}
+
 #[instrument]
 async fn download_metadata(
     _ctx: ReqCtx,
@@ -111,6 +177,18 @@ async fn show(
     Ok(warp::reply::json(&response))
 }
 
+#[instrument]
+async fn custody(
+    _ctx: ReqCtx,
+    workspace: String,
+    id: i32,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let data = unit_handlers::list_custody_events(&workspace, id).await?;
+    let response = SuccessResponse::new(data);
+
+    Ok(warp::reply::json(&response))
+}
+
 #[instrument]
 async fn data_by_ids(
     _ctx: ReqCtx,
@@ -123,6 +201,132 @@ async fn data_by_ids(
     Ok(warp::reply::json(&response))
 }
 
+#[instrument]
+async fn duplicates(_ctx: ReqCtx, workspace: String) -> Result<impl warp::Reply, warp::Rejection> {
+    let data = handlers::list_duplicates(&workspace).await?;
+    let response = SuccessResponse::new(data);
+
+    Ok(warp::reply::json(&response))
+}
+
+#[instrument]
+async fn detect_duplicates(
+    _ctx: ReqCtx,
+    workspace: String,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    handlers::detect_duplicates(&workspace).await?;
+
+    Ok(warp::reply())
+}
+
+// The query parameters for similar media.
+#[derive(Debug, Deserialize)]
+pub struct SimilarOptions {
+    pub distance: Option<u32>,
+}
+
+#[instrument]
+async fn similar_media(
+    _ctx: ReqCtx,
+    workspace: String,
+    id: i32,
+    opts: SimilarOptions,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let distance = opts.distance.unwrap_or(10);
+    let data = handlers::list_similar_media(&workspace, id, distance).await?;
+    let response = SuccessResponse::new(data);
+
+    Ok(warp::reply::json(&response))
+}
+
+#[instrument]
+async fn hash_media(_ctx: ReqCtx, workspace: String) -> Result<impl warp::Reply, warp::Rejection> {
+    handlers::hash_media(&workspace).await?;
+
+    Ok(warp::reply())
+}
+
+#[instrument]
+async fn verify_downloads(
+    _ctx: ReqCtx,
+    workspace: String,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    handlers::verify_downloads(&workspace).await?;
+
+    Ok(warp::reply())
+}
+
+#[instrument]
+async fn integrity_reports(
+    _ctx: ReqCtx,
+    workspace: String,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let data = handlers::list_integrity_reports(&workspace).await?;
+    let response = SuccessResponse::new(data);
+
+    Ok(warp::reply::json(&response))
+}
+
+#[instrument]
+async fn integrity_report(
+    _ctx: ReqCtx,
+    workspace: String,
+    id: i32,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let data = handlers::show_integrity_report(&workspace, id).await?;
+    let response = SuccessResponse::new(data);
+
+    Ok(warp::reply::json(&response))
+}
+
+// The query parameters for verifying the custody chain.
+#[derive(Debug, Deserialize)]
+pub struct CustodyChainOptions {
+    pub anchor: Option<String>,
+}
+
+#[instrument]
+async fn custody_chain(
+    _ctx: ReqCtx,
+    workspace: String,
+    opts: CustodyChainOptions,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let data = handlers::verify_custody_chain(&workspace, opts.anchor.as_deref()).await?;
+    let response = SuccessResponse::new(data);
+
+    Ok(warp::reply::json(&response))
+}
+
+#[instrument]
+async fn export(
+    _ctx: ReqCtx,
+    workspace: String,
+    opts: UnitExportQuery,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let query = match opts.q.as_deref() {
+        Some(query) => percent_decode_str(query).decode_utf8_lossy().to_string(),
+        None => {
+            return Err(HandlerError::Invalid(
+                "export requires a query parameter".into(),
+            ))?
+        }
+    };
+    let options = opts.options()?;
+    let format = options.format;
+
+    let selection = UnitSelection::Search {
+        query,
+        sort: opts.sort.unwrap_or_default(),
+    };
+    let export = export_handlers::export_units(&workspace, selection, options).await?;
+
+    Ok(send_unit_export(
+        export,
+        &format!("{}-units", workspace),
+        format,
+    ))
+}
+
 pub(crate) fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     authenticate_remote_req()
         .and(warp::path!("workspaces" / String / "data"))
@@ -139,10 +343,78 @@ pub(crate) fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::
             .and(warp::get())
             .and(warp::query::<ListOptions>())
             .and_then(search))
+        .or(authenticate_remote_req()
+            .and(warp::path!("workspaces" / String / "data" / "export"))
+            .and(warp::get())
+            .and(warp::query::<UnitExportQuery>())
+            .and_then(export))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "data" / "search" / "facets"
+            ))
+            .and(warp::get())
+            .and(warp::query::<ListOptions>())
+            .and_then(facets))
+        .or(authenticate_remote_req()
+            .and(warp::path!("workspaces" / String / "duplicates"))
+            .and(warp::get())
+            .and_then(duplicates))
+        .or(authenticate_remote_req()
+            .and(warp::path!("workspaces" / String / "duplicates"))
+            .and(warp::post())
+            .and_then(detect_duplicates)
+            .map(|reply| warp::reply::with_status(reply, warp::http::StatusCode::ACCEPTED)))
+        .or(authenticate_remote_req()
+            .and(warp::path!("workspaces" / String / "media" / "hashes"))
+            .and(warp::post())
+            .and_then(hash_media)
+            .map(|reply| warp::reply::with_status(reply, warp::http::StatusCode::NO_CONTENT)))
+        .or(authenticate_remote_req()
+            .and(warp::path!("workspaces" / String / "media" / "thumbnails"))
+            .and(warp::post())
+            .and_then(generate_thumbnails)
+            .map(|reply| warp::reply::with_status(reply, warp::http::StatusCode::NO_CONTENT)))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "downloads" / "integrity"
+            ))
+            .and(warp::get())
+            .and_then(integrity_reports))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "downloads" / "integrity" / i32
+            ))
+            .and(warp::get())
+            .and_then(integrity_report))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "downloads" / "integrity"
+            ))
+            .and(warp::post())
+            .and_then(verify_downloads)
+            .map(|reply| warp::reply::with_status(reply, warp::http::StatusCode::NO_CONTENT)))
+        .or(authenticate_remote_req()
+            .and(warp::path!("workspaces" / String / "custody"))
+            .and(warp::get())
+            .and(warp::query::<CustodyChainOptions>())
+            .and_then(custody_chain))
         .or(authenticate_remote_req()
             .and(warp::path!("workspaces" / String / "data" / "units" / i32))
             .and(warp::get())
             .and_then(show))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "data" / "units" / i32 / "similar"
+            ))
+            .and(warp::get())
+            .and(warp::query::<SimilarOptions>())
+            .and_then(similar_media))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "data" / "units" / i32 / "custody"
+            ))
+            .and(warp::get())
+            .and_then(custody))
         .or(authenticate_remote_req()
             .and(warp::path!(
                 "workspaces" / String / "data" / String / String / String
@@ -156,4 +428,16 @@ pub(crate) fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::
             ))
             .and(warp::get())
             .and_then(download_metadata))
+        .or(authenticate_remote_req()
+            .and(warp::path!(
+                "workspaces" / String / "data" / String / String / String / "thumbnail"
+            ))
+            .and(warp::get())
+            .and(warp::query::<ThumbnailOptions>())
+            .and(conditionals())
+            .and_then(thumbnail)
+            // Thumbnails only change if they are generated again.
+            .map(|reply| {
+                warp::reply::with_header(reply, "cache-control", "private, max-age=86400")
+            }))
 }
@@ -1,6 +1,8 @@
-use ncube_data::{ReqCtx, SuccessResponse, WorkspaceRequest};
-use ncube_handlers::workspace as handlers;
+use ncube_data::{InvalidCursor, ReqCtx, SuccessResponse, WorkspaceRequest, WorkspacesCursor};
+use ncube_handlers::{workspace as handlers, HandlerError};
+use percent_encoding::percent_decode_str;
 use serde::Deserialize;
+use tracing::instrument;
 use warp::Filter;
 
 use crate::http::restrict_to_local_req;
@@ -11,6 +13,16 @@ pub struct DeleteOptions {
     pub remove_location: Option<bool>,
 }
 
+// The query parameters for searching all workspaces.
+#[derive(Debug, Deserialize)]
+pub struct SearchOptions {
+    pub page: Option<i32>,
+    pub size: Option<i32>,
+    pub cursor: Option<String>,
+    pub q: Option<String>,
+    pub remote: Option<bool>,
+}
+
 async fn create(
     _ctx: ReqCtx,
     workspace: WorkspaceRequest,
@@ -61,6 +73,36 @@ async fn update(
     Ok(warp::reply())
 }
 
+#[instrument]
+async fn search(_ctx: ReqCtx, opts: SearchOptions) -> Result<impl warp::Reply, warp::Rejection> {
+    let query = opts
+        .q
+        .map(|query| percent_decode_str(&query).decode_utf8_lossy().to_string())
+        .ok_or_else(|| HandlerError::Invalid("search requires a query parameter".into()))?;
+
+    // Workspaces are paged independently, an offset can't be mapped to the
+    // positions in every workspace.
+    if opts.page.unwrap_or(0) > 0 {
+        return Err(warp::reject::custom(HandlerError::Invalid(
+            "Searches across workspaces are paged by cursor.".into(),
+        )));
+    }
+
+    let after = match opts.cursor.as_deref() {
+        None | Some("") => None,
+        Some(cursor) => Some(cursor.parse::<WorkspacesCursor>().map_err(
+            |err: InvalidCursor| warp::reject::custom(HandlerError::Invalid(err.to_string())),
+        )?),
+    };
+    let remote = opts.remote.unwrap_or(false);
+
+    let data = handlers::search_workspaces(&query, remote, after.as_ref(), opts.size.unwrap_or(20))
+        .await?;
+    let response = SuccessResponse::new(data);
+
+    Ok(warp::reply::json(&response))
+}
+
 pub(crate) fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::path("workspaces")
         .and(warp::get())
@@ -93,4 +135,10 @@ pub(crate) fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::
             .and(warp::body::json())
             .and_then(update)
             .map(|reply| warp::reply::with_status(reply, warp::http::StatusCode::NO_CONTENT)))
+        .or(warp::any()
+            .and(restrict_to_local_req())
+            .and(warp::path!("search"))
+            .and(warp::get())
+            .and(warp::query::<SearchOptions>())
+            .and_then(search))
 }
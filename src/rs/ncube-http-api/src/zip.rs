@@ -0,0 +1,440 @@
+use async_stream::try_stream;
+use bytes::{BufMut, Bytes, BytesMut};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use futures::{Stream, StreamExt};
+use std::path::PathBuf;
+use tokio::fs::File as TkFile;
+use tracing::warn;
+
+use crate::fs::{file_stream, optimal_buf_size};
+
+// Archives are written in a single pass. Entries are stored without
+// compression, their checksum and size follow the data in a data descriptor.
+// Entries and archives that grow beyond the limits of the classic format use
+// the ZIP64 extensions.
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR: u32 = 0x0807_4b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x0201_4b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY: u32 = 0x0606_4b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR: u32 = 0x0706_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+
+const ZIP64_EXTRA_FIELD: u16 = 0x0001;
+const VERSION: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+/// The data descriptor follows the data and names are UTF-8.
+const FLAGS: u16 = 0x0808;
+const STORED: u16 = 0;
+
+const MAX_U16: u64 = 0xffff;
+const MAX_U32: u64 = 0xffff_ffff;
+
+/// Sizes and offsets from this limit on, and this many entries, are recorded
+/// in the ZIP64 extensions. Tests lower the limits to write ZIP64 archives of
+/// a few bytes.
+#[cfg(not(test))]
+const ZIP64_SIZE: u64 = MAX_U32;
+#[cfg(not(test))]
+const ZIP64_ENTRIES: u64 = MAX_U16;
+#[cfg(test)]
+const ZIP64_SIZE: u64 = 64;
+#[cfg(test)]
+const ZIP64_ENTRIES: u64 = 8;
+
+/// A size or offset of the classic format, values that are recorded in the
+/// ZIP64 extensions are masked.
+fn field_u32(value: u64) -> u32 {
+    if value >= ZIP64_SIZE {
+        MAX_U32 as u32
+    } else {
+        value as u32
+    }
+}
+
+fn field_u16(value: u64) -> u16 {
+    if value >= ZIP64_ENTRIES {
+        MAX_U16 as u16
+    } else {
+        value as u16
+    }
+}
+
+/// The contents of an entry of an archive.
+#[derive(Debug)]
+pub(crate) enum ZipSource {
+    File(PathBuf),
+    /// An open file that is read from its current position, e.g. a temporary
+    /// file that was just written and rewound.
+    Spooled(std::fs::File),
+}
+
+#[derive(Debug)]
+struct CentralEntry {
+    name: String,
+    crc: u32,
+    size: u64,
+    offset: u64,
+}
+
+/// The modification time of all entries in the MS-DOS format.
+#[derive(Debug, Clone, Copy)]
+struct DosTime {
+    time: u16,
+    date: u16,
+}
+
+impl From<DateTime<Utc>> for DosTime {
+    fn from(datetime: DateTime<Utc>) -> Self {
+        // MS-DOS dates start in 1980.
+        let year = datetime.year().max(1980) - 1980;
+
+        Self {
+            time: ((datetime.hour() << 11) | (datetime.minute() << 5) | (datetime.second() / 2))
+                as u16,
+            date: ((year as u32) << 9 | (datetime.month() << 5) | datetime.day()) as u16,
+        }
+    }
+}
+
+fn local_file_header(name: &str, zip64: bool, modified: DosTime) -> Bytes {
+    let mut buf = BytesMut::with_capacity(30 + name.len() + 20);
+
+    buf.put_u32_le(LOCAL_FILE_HEADER);
+    buf.put_u16_le(if zip64 { VERSION_ZIP64 } else { VERSION });
+    buf.put_u16_le(FLAGS);
+    buf.put_u16_le(STORED);
+    buf.put_u16_le(modified.time);
+    buf.put_u16_le(modified.date);
+    // The checksum and the sizes are recorded in the data descriptor.
+    buf.put_u32_le(0);
+    if zip64 {
+        buf.put_u32_le(MAX_U32 as u32);
+        buf.put_u32_le(MAX_U32 as u32);
+    } else {
+        buf.put_u32_le(0);
+        buf.put_u32_le(0);
+    }
+    buf.put_u16_le(name.len() as u16);
+    buf.put_u16_le(if zip64 { 20 } else { 0 });
+    buf.put_slice(name.as_bytes());
+    if zip64 {
+        buf.put_u16_le(ZIP64_EXTRA_FIELD);
+        buf.put_u16_le(16);
+        buf.put_u64_le(0);
+        buf.put_u64_le(0);
+    }
+
+    buf.freeze()
+}
+
+fn data_descriptor(crc: u32, size: u64, zip64: bool) -> Bytes {
+    let mut buf = BytesMut::with_capacity(24);
+
+    buf.put_u32_le(DATA_DESCRIPTOR);
+    buf.put_u32_le(crc);
+    if zip64 {
+        buf.put_u64_le(size);
+        buf.put_u64_le(size);
+    } else {
+        buf.put_u32_le(size as u32);
+        buf.put_u32_le(size as u32);
+    }
+
+    buf.freeze()
+}
+
+fn central_directory_header(entry: &CentralEntry, modified: DosTime) -> Bytes {
+    let large_size = entry.size >= ZIP64_SIZE;
+    let large_offset = entry.offset >= ZIP64_SIZE;
+
+    let mut extra = BytesMut::new();
+    if large_size {
+        extra.put_u64_le(entry.size);
+        extra.put_u64_le(entry.size);
+    }
+    if large_offset {
+        extra.put_u64_le(entry.offset);
+    }
+    let version = if extra.is_empty() {
+        VERSION
+    } else {
+        VERSION_ZIP64
+    };
+
+    let mut buf = BytesMut::with_capacity(46 + entry.name.len() + 4 + extra.len());
+
+    buf.put_u32_le(CENTRAL_DIRECTORY_HEADER);
+    buf.put_u16_le(version);
+    buf.put_u16_le(version);
+    buf.put_u16_le(FLAGS);
+    buf.put_u16_le(STORED);
+    buf.put_u16_le(modified.time);
+    buf.put_u16_le(modified.date);
+    buf.put_u32_le(entry.crc);
+    buf.put_u32_le(field_u32(entry.size));
+    buf.put_u32_le(field_u32(entry.size));
+    buf.put_u16_le(entry.name.len() as u16);
+    buf.put_u16_le(if extra.is_empty() {
+        0
+    } else {
+        4 + extra.len() as u16
+    });
+    // Comment length, disk number, internal and external attributes.
+    buf.put_u16_le(0);
+    buf.put_u16_le(0);
+    buf.put_u16_le(0);
+    buf.put_u32_le(0);
+    buf.put_u32_le(field_u32(entry.offset));
+    buf.put_slice(entry.name.as_bytes());
+    if !extra.is_empty() {
+        buf.put_u16_le(ZIP64_EXTRA_FIELD);
+        buf.put_u16_le(extra.len() as u16);
+        buf.put_slice(&extra);
+    }
+
+    buf.freeze()
+}
+
+fn end_of_central_directory(entries: u64, size: u64, offset: u64) -> Bytes {
+    let mut buf = BytesMut::with_capacity(56 + 20 + 22);
+
+    if entries >= ZIP64_ENTRIES || size >= ZIP64_SIZE || offset >= ZIP64_SIZE {
+        let zip64_offset = offset + size;
+
+        buf.put_u32_le(ZIP64_END_OF_CENTRAL_DIRECTORY);
+        // The size of the remaining record.
+        buf.put_u64_le(44);
+        buf.put_u16_le(VERSION_ZIP64);
+        buf.put_u16_le(VERSION_ZIP64);
+        buf.put_u32_le(0);
+        buf.put_u32_le(0);
+        buf.put_u64_le(entries);
+        buf.put_u64_le(entries);
+        buf.put_u64_le(size);
+        buf.put_u64_le(offset);
+
+        buf.put_u32_le(ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR);
+        buf.put_u32_le(0);
+        buf.put_u64_le(zip64_offset);
+        buf.put_u32_le(1);
+    }
+
+    buf.put_u32_le(END_OF_CENTRAL_DIRECTORY);
+    buf.put_u16_le(0);
+    buf.put_u16_le(0);
+    buf.put_u16_le(field_u16(entries));
+    buf.put_u16_le(field_u16(entries));
+    buf.put_u32_le(field_u32(size));
+    buf.put_u32_le(field_u32(offset));
+    buf.put_u16_le(0);
+
+    buf.freeze()
+}
+
+/// Stream a ZIP archive of `entries`. The entries are read as the archive is
+/// written, an error of `entries` ends the archive. Files that can't be opened
+/// anymore are left out of the archive.
+pub(crate) fn zip_stream<S>(
+    entries: S,
+    modified: DateTime<Utc>,
+) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Send
+where
+    S: Stream<Item = Result<(String, ZipSource), std::io::Error>> + Send,
+{
+    let modified = DosTime::from(modified);
+
+    try_stream! {
+        let mut offset: u64 = 0;
+        let mut central = vec![];
+        let mut entries = Box::pin(entries);
+
+        while let Some(entry) = entries.next().await {
+            let (name, source) = entry?;
+            let file = match source {
+                ZipSource::File(path) => match TkFile::open(&path).await {
+                    Ok(file) => file,
+                    Err(e) => {
+                        warn!("skipping {:?} in archive: {}", path, e.to_string());
+                        continue;
+                    }
+                },
+                ZipSource::Spooled(file) => TkFile::from_std(file),
+            };
+            let metadata = file.metadata().await?;
+            let zip64 = metadata.len() >= ZIP64_SIZE;
+            let header = local_file_header(&name, zip64, modified);
+
+            let entry_offset = offset;
+            offset += header.len() as u64;
+            yield header;
+
+            let mut hasher = crc32fast::Hasher::new();
+            let mut size: u64 = 0;
+            let mut chunks = Box::pin(file_stream(
+                file,
+                optimal_buf_size(&metadata),
+                (0, metadata.len()),
+            ));
+
+            while let Some(chunk) = chunks.next().await {
+                let chunk = chunk?;
+                hasher.update(&chunk);
+                size += chunk.len() as u64;
+                offset += chunk.len() as u64;
+                yield chunk;
+            }
+
+            let crc = hasher.finalize();
+            central.push(CentralEntry { name, crc, size, offset: entry_offset });
+
+            let descriptor = data_descriptor(crc, size, zip64);
+            offset += descriptor.len() as u64;
+            yield descriptor;
+        }
+
+        let directory_offset = offset;
+        let mut directory_size: u64 = 0;
+
+        for entry in &central {
+            let header = central_directory_header(entry, modified);
+            directory_size += header.len() as u64;
+            yield header;
+        }
+
+        yield end_of_central_directory(central.len() as u64, directory_size, directory_offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use futures::stream;
+    use futures::TryStreamExt;
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+    use zip::ZipArchive;
+
+    async fn archive(entries: Vec<(String, ZipSource)>) -> ZipArchive<Cursor<Vec<u8>>> {
+        let modified = Utc.ymd(2021, 3, 14).and_hms(15, 9, 26);
+        let entries = stream::iter(entries.into_iter().map(Ok));
+        let chunks: Vec<Bytes> = zip_stream(entries, modified).try_collect().await.unwrap();
+
+        ZipArchive::new(Cursor::new(chunks.concat())).unwrap()
+    }
+
+    fn contents(archive: &mut ZipArchive<Cursor<Vec<u8>>>) -> Vec<(String, Vec<u8>)> {
+        (0..archive.len())
+            .map(|i| {
+                let mut entry = archive.by_index(i).unwrap();
+                let mut data = vec![];
+                // Reading an entry to its end checks its CRC32.
+                entry.read_to_end(&mut data).unwrap();
+                assert_eq!(entry.size(), data.len() as u64);
+                (entry.name().to_string(), data)
+            })
+            .collect()
+    }
+
+    fn spooled(data: &[u8]) -> ZipSource {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(data).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        ZipSource::Spooled(file)
+    }
+
+    fn temp_file(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ncube-zip-{}-{}", name, std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn writes_an_empty_archive() {
+        let mut archive = archive(vec![]).await;
+
+        assert_eq!(archive.len(), 0);
+        assert_eq!(contents(&mut archive), vec![]);
+    }
+
+    #[tokio::test]
+    async fn writes_spooled_and_file_entries() {
+        let path = temp_file("entries", b"video data");
+        let missing = std::env::temp_dir().join("ncube-zip-missing");
+
+        let mut archive = archive(vec![
+            ("manifest.csv".into(), spooled(b"id,hash\n1,abc\n")),
+            ("files/video.mp4".into(), ZipSource::File(path.clone())),
+            ("files/missing.mp4".into(), ZipSource::File(missing)),
+            ("units/ünit.json".into(), spooled(b"")),
+        ])
+        .await;
+
+        assert_eq!(
+            contents(&mut archive),
+            vec![
+                ("manifest.csv".to_string(), b"id,hash\n1,abc\n".to_vec()),
+                ("files/video.mp4".to_string(), b"video data".to_vec()),
+                ("units/ünit.json".to_string(), vec![]),
+            ]
+        );
+
+        let entry = archive.by_index(0).unwrap();
+        let modified = entry.last_modified();
+        assert_eq!(
+            (modified.year(), modified.month(), modified.day()),
+            (2021, 3, 14)
+        );
+        assert_eq!(
+            (modified.hour(), modified.minute(), modified.second()),
+            (15, 9, 26)
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn writes_zip64_archives() {
+        let large = vec![7u8; ZIP64_SIZE as usize + 10];
+        let path = temp_file("zip64", &large);
+
+        let mut entries = vec![
+            ("large.bin".to_string(), spooled(&large)),
+            ("large-file.bin".to_string(), ZipSource::File(path.clone())),
+        ];
+        for i in 0..ZIP64_ENTRIES {
+            entries.push((
+                format!("small-{}.txt", i),
+                spooled(i.to_string().as_bytes()),
+            ));
+        }
+
+        let mut archive = archive(entries).await;
+        let contents = contents(&mut archive);
+
+        assert_eq!(contents.len() as u64, ZIP64_ENTRIES + 2);
+        assert_eq!(contents[0], ("large.bin".to_string(), large.clone()));
+        assert_eq!(contents[1], ("large-file.bin".to_string(), large));
+        for (i, (name, data)) in contents[2..].iter().enumerate() {
+            assert_eq!(name, &format!("small-{}.txt", i));
+            assert_eq!(data, i.to_string().as_bytes());
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn errors_of_the_entries_end_the_archive() {
+        let entries = stream::iter(vec![
+            Ok(("manifest.csv".to_string(), spooled(b"id\n"))),
+            Err(std::io::Error::other("failed")),
+            Ok(("manifest.json".to_string(), spooled(b"{}"))),
+        ]);
+        let modified = Utc.ymd(2021, 3, 14).and_hms(15, 9, 26);
+        let chunks: Vec<Result<Bytes, std::io::Error>> =
+            zip_stream(entries, modified).collect().await;
+
+        let error = chunks.iter().position(Result::is_err).unwrap();
+        assert_eq!(error, chunks.len() - 1);
+        assert_eq!(chunks[error].as_ref().unwrap_err().to_string(), "failed");
+    }
+}
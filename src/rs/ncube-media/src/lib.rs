@@ -0,0 +1,169 @@
+use image::{imageops::FilterType, DynamicImage};
+use std::path::Path;
+use std::process::Stdio;
+use thiserror::Error;
+use tokio::process::Command;
+use tracing::instrument;
+
+mod metadata;
+mod thumbnail;
+
+pub use metadata::{image_file_metadata, image_metadata, video_file_metadata};
+pub use thumbnail::{image_file_thumbnail, video_file_thumbnails, THUMBNAIL_SIZE};
+
+/// Images are scaled down to 9x8 pixels before they are hashed. Comparing
+/// every pixel to its right neighbour results in 64 bits.
+const HASH_WIDTH: usize = 9;
+const HASH_HEIGHT: usize = 8;
+
+/// Video frames are sampled every 10 seconds.
+const FRAME_INTERVAL: u32 = 10;
+
+#[derive(Debug, Error)]
+pub enum MediaError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+
+    #[error(transparent)]
+    Exif(#[from] exif::Error),
+
+    #[error("ffmpeg failed: {0}")]
+    Ffmpeg(String),
+}
+
+/// The difference hash of a grayscale image that is 9 pixels wide and 8
+/// pixels high. Every bit is set if a pixel is brighter than the pixel to its
+/// right. Images of a single color hash to `0`.
+///
+/// # Panics
+///
+/// Panics if `pixels` doesn't contain exactly 72 pixels.
+pub fn dhash(pixels: &[u8]) -> u64 {
+    assert_eq!(pixels.len(), HASH_WIDTH * HASH_HEIGHT);
+
+    let mut hash = 0;
+    for row in pixels.chunks_exact(HASH_WIDTH) {
+        for pair in row.windows(2) {
+            hash <<= 1;
+            if pair[0] > pair[1] {
+                hash |= 1;
+            }
+        }
+    }
+
+    hash
+}
+
+/// The perceptual hash of an image. Scaled or re-encoded copies of an image
+/// have the same or a very similar hash.
+pub fn image_hash(image: &DynamicImage) -> u64 {
+    let small = image
+        .resize_exact(HASH_WIDTH as u32, HASH_HEIGHT as u32, FilterType::Triangle)
+        .to_luma8();
+
+    dhash(small.as_raw())
+}
+
+#[instrument]
+pub fn image_file_hash(path: &Path) -> Result<u64, MediaError> {
+    let image = image::open(path)?;
+
+    Ok(image_hash(&image))
+}
+
+/// Hash up to `frames` frames of a video, sampled every 10 seconds. The
+/// frames are decoded and scaled down by `ffmpeg`. Frames of a single color,
+/// e.g. black frames at the start of a video, are left out since they match
+/// any other frame of a single color.
+#[instrument]
+pub async fn video_file_hashes(
+    ffmpeg: &Path,
+    path: &Path,
+    frames: usize,
+) -> Result<Vec<u64>, MediaError> {
+    let filter = format!(
+        "fps=1/{},scale={}:{},format=gray",
+        FRAME_INTERVAL, HASH_WIDTH, HASH_HEIGHT
+    );
+
+    let output = Command::new(ffmpeg)
+        .args(["-v", "error", "-i"])
+        .arg(path)
+        .args(["-vf", &filter, "-frames:v", &frames.to_string()])
+        .args(["-f", "rawvideo", "pipe:1"])
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(MediaError::Ffmpeg(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    let hashes = output
+        .stdout
+        .chunks_exact(HASH_WIDTH * HASH_HEIGHT)
+        .map(dhash)
+        .filter(|hash| *hash != 0)
+        .collect();
+
+    Ok(hashes)
+}
+
+/// The number of bits that differ between two hashes. Hashes with a distance
+/// of up to 10 are usually copies of the same image.
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    fn landscape(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+            let horizon = height / 2 + (x * 7 % 23);
+            if y < horizon {
+                Luma([(200 - y * 100 / height) as u8])
+            } else {
+                Luma([(40 + x * 120 / width) as u8])
+            }
+        }))
+    }
+
+    #[test]
+    fn test_dhash() {
+        assert_eq!(dhash(&[128; 72]), 0);
+
+        let falling: Vec<u8> = (0..72).map(|i| 255 - (i % 9) as u8 * 10).collect();
+        assert_eq!(dhash(&falling), u64::MAX);
+    }
+
+    #[test]
+    fn test_scaled_copies_are_similar() {
+        let original = landscape(640, 360);
+        let scaled = original.resize_exact(320, 180, FilterType::Nearest);
+
+        assert!(distance(image_hash(&original), image_hash(&scaled)) <= 4);
+    }
+
+    #[test]
+    fn test_different_images_are_not_similar() {
+        let original = landscape(640, 360);
+        let mirrored = original.fliph();
+
+        assert!(distance(image_hash(&original), image_hash(&mirrored)) > 10);
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance(0, 0), 0);
+        assert_eq!(distance(0b1011, 0b0001), 2);
+        assert_eq!(distance(0, u64::MAX), 64);
+    }
+}
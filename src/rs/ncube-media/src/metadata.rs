@@ -0,0 +1,385 @@
+use exif::{Exif, In, Reader, Tag, Value};
+use ncube_data::{GeoLocation, MediaMetadata};
+use std::io::Cursor;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::process::Command;
+use tracing::instrument;
+
+use crate::MediaError;
+
+/// EXIF values of an unknown format that are larger than this, e.g. maker
+/// notes, are left out.
+const MAX_UNDEFINED_LEN: usize = 64;
+
+const XMP_START: &[u8] = b"<x:xmpmeta";
+const XMP_END: &[u8] = b"</x:xmpmeta>";
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// The XMP packet of a file. Images embed XMP as plain XML, so the packet is
+/// found without parsing the image format.
+fn xmp_packet(data: &[u8]) -> Option<String> {
+    let start = find(data, XMP_START)?;
+    let end = start + find(&data[start..], XMP_END)? + XMP_END.len();
+
+    Some(String::from_utf8_lossy(&data[start..end]).to_string())
+}
+
+/// Convert degrees, minutes and seconds to decimal degrees. The reference is
+/// one of `N`, `S`, `E` or `W`.
+fn gps_coordinate(exif: &Exif, tag: Tag, reference: Tag) -> Option<f64> {
+    let degrees = match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(dms) if dms.len() >= 3 => {
+            dms[0].to_f64() + dms[1].to_f64() / 60.0 + dms[2].to_f64() / 3600.0
+        }
+        _ => return None,
+    };
+
+    let negative = match &exif.get_field(reference, In::PRIMARY)?.value {
+        Value::Ascii(reference) => reference
+            .first()
+            .is_some_and(|r| r.starts_with(b"S") || r.starts_with(b"W")),
+        _ => false,
+    };
+
+    Some(if negative { -degrees } else { degrees })
+}
+
+fn gps_location(exif: &Exif) -> Option<GeoLocation> {
+    let latitude = gps_coordinate(exif, Tag::GPSLatitude, Tag::GPSLatitudeRef)?;
+    let longitude = gps_coordinate(exif, Tag::GPSLongitude, Tag::GPSLongitudeRef)?;
+
+    let altitude = match exif.get_field(Tag::GPSAltitude, In::PRIMARY) {
+        Some(field) => match &field.value {
+            Value::Rational(altitude) if !altitude.is_empty() => {
+                // A reference of 1 means below sea level.
+                let below = exif
+                    .get_field(Tag::GPSAltitudeRef, In::PRIMARY)
+                    .and_then(|field| field.value.get_uint(0))
+                    == Some(1);
+                let altitude = altitude[0].to_f64();
+                Some(if below { -altitude } else { altitude })
+            }
+            _ => None,
+        },
+        None => None,
+    };
+
+    Some(GeoLocation {
+        latitude,
+        longitude,
+        altitude,
+    })
+}
+
+/// Extract the EXIF fields and the XMP packet of an image. Images without
+/// EXIF data, or in a format that can't carry EXIF like GIF, only have their
+/// XMP packet extracted.
+pub fn image_metadata(data: &[u8]) -> Result<MediaMetadata, MediaError> {
+    let mut metadata = MediaMetadata {
+        xmp: xmp_packet(data),
+        ..Default::default()
+    };
+
+    let exif = match Reader::new().read_from_container(&mut Cursor::new(data)) {
+        Ok(exif) => exif,
+        Err(exif::Error::NotFound(_)) | Err(exif::Error::InvalidFormat(_)) => return Ok(metadata),
+        Err(e) => return Err(e.into()),
+    };
+
+    for field in exif.fields().filter(|field| field.ifd_num == In::PRIMARY) {
+        if let Value::Undefined(value, _) = &field.value {
+            if value.len() > MAX_UNDEFINED_LEN {
+                continue;
+            }
+        }
+
+        metadata.tags.insert(
+            field.tag.to_string(),
+            field.display_value().with_unit(&exif).to_string(),
+        );
+    }
+
+    metadata.created_at = exif
+        .get_field(Tag::DateTimeOriginal, In::PRIMARY)
+        .or_else(|| exif.get_field(Tag::DateTime, In::PRIMARY))
+        .map(|field| field.display_value().to_string());
+    metadata.location = gps_location(&exif);
+
+    Ok(metadata)
+}
+
+#[instrument]
+pub fn image_file_metadata(path: &Path) -> Result<MediaMetadata, MediaError> {
+    let data = std::fs::read(path)?;
+
+    image_metadata(&data)
+}
+
+/// Parse a location in ISO 6709 notation as used by video containers, e.g.
+/// `+52.5200+013.4050+034.000/`.
+fn parse_iso6709(location: &str) -> Option<GeoLocation> {
+    let location = location.trim().trim_end_matches('/');
+
+    let mut parts = vec![];
+    let mut start = 0;
+    for (i, c) in location.char_indices().skip(1) {
+        if c == '+' || c == '-' {
+            parts.push(&location[start..i]);
+            start = i;
+        }
+    }
+    parts.push(&location[start..]);
+
+    let values = parts
+        .iter()
+        .map(|part| part.parse::<f64>().ok())
+        .collect::<Option<Vec<f64>>>()?;
+
+    match values[..] {
+        [latitude, longitude] => Some(GeoLocation {
+            latitude,
+            longitude,
+            altitude: None,
+        }),
+        [latitude, longitude, altitude] => Some(GeoLocation {
+            latitude,
+            longitude,
+            altitude: Some(altitude),
+        }),
+        _ => None,
+    }
+}
+
+/// Parse a duration like `00:01:02.03` to seconds.
+fn parse_duration(duration: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in duration.trim().split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+
+    Some(seconds)
+}
+
+/// Parse the description of the input file that `ffmpeg` prints when it is
+/// run without an output file. Only the tags of the container are kept, not
+/// the tags of the single streams.
+fn parse_ffmpeg_info(info: &str) -> Option<MediaMetadata> {
+    let mut lines = info
+        .lines()
+        .skip_while(|line| !line.starts_with("Input #0"));
+    lines.next()?;
+
+    let mut metadata = MediaMetadata::default();
+    let mut in_tags = false;
+    let mut last_tag: Option<String> = None;
+
+    // The description ends with the first line that isn't indented.
+    for line in lines.take_while(|line| line.starts_with(' ')) {
+        if line == "  Metadata:" {
+            in_tags = true;
+            continue;
+        }
+
+        let continued = line.trim_start().starts_with(':');
+        if in_tags && line.starts_with("    ") && (!line.starts_with("     ") || continued) {
+            if let Some((key, value)) = line.split_once(':') {
+                let (key, value) = (key.trim(), value.trim());
+
+                // Values that span several lines continue without a key.
+                match (key.is_empty(), &last_tag) {
+                    (true, Some(last)) => {
+                        if let Some(previous) = metadata.tags.get_mut(last) {
+                            previous.push('\n');
+                            previous.push_str(value);
+                        }
+                    }
+                    _ => {
+                        metadata.tags.insert(key.to_string(), value.to_string());
+                        last_tag = Some(key.to_string());
+                    }
+                }
+            }
+            continue;
+        }
+
+        in_tags = false;
+        let line = line.trim();
+
+        if let Some(duration) = line.strip_prefix("Duration:") {
+            metadata.duration = duration.split(',').next().and_then(parse_duration);
+        } else if line.starts_with("Stream #") {
+            for kind in &[": Video: ", ": Audio: ", ": Subtitle: "] {
+                if let Some(start) = line.find(kind) {
+                    let codec = line[start + kind.len()..]
+                        .split([' ', ','])
+                        .next()
+                        .unwrap_or_default();
+                    if !codec.is_empty() {
+                        metadata.codecs.push(codec.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    metadata.created_at = ["com.apple.quicktime.creationdate", "creation_time", "date"]
+        .iter()
+        .find_map(|key| metadata.tags.get(*key))
+        .cloned();
+    metadata.location = ["com.apple.quicktime.location.ISO6709", "location"]
+        .iter()
+        .find_map(|key| metadata.tags.get(*key))
+        .and_then(|location| parse_iso6709(location));
+
+    Some(metadata)
+}
+
+/// Extract the container metadata of a video, e.g. its duration, the codecs
+/// of its streams and the tags of the container. The video is probed by
+/// `ffmpeg`.
+#[instrument]
+pub async fn video_file_metadata(ffmpeg: &Path, path: &Path) -> Result<MediaMetadata, MediaError> {
+    // Without an output file ffmpeg describes the input and fails.
+    let output = Command::new(ffmpeg)
+        .args(["-hide_banner", "-i"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+
+    let info = String::from_utf8_lossy(&output.stderr);
+
+    parse_ffmpeg_info(&info).ok_or_else(|| MediaError::Ffmpeg(info.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::{experimental::Writer, Field, Rational};
+
+    const FFMPEG_INFO: &str = "Input #0, mov,mp4,m4a,3gp,3g2,mj2, from 'video.mp4':
+  Metadata:
+    major_brand     : isom
+    creation_time   : 2020-05-01T10:00:00.000000Z
+    location        : +36.2021+037.1343/
+    comment         : first line
+                    : second line
+  Duration: 00:01:02.50, start: 0.000000, bitrate: 1234 kb/s
+    Stream #0:0(und): Video: h264 (High) (avc1 / 0x31637661), yuv420p, 1280x720, 30 fps
+    Metadata:
+      handler_name    : VideoHandler
+    Stream #0:1(und): Audio: aac (LC) (mp4a / 0x6134706D), 44100 Hz, stereo, fltp
+At least one output file must be specified
+";
+
+    fn rationals(values: &[u32]) -> Value {
+        Value::Rational(
+            values
+                .iter()
+                .map(|num| Rational {
+                    num: *num,
+                    denom: 1,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_ffmpeg_info() {
+        let metadata = parse_ffmpeg_info(FFMPEG_INFO).unwrap();
+
+        assert_eq!(metadata.duration, Some(62.5));
+        assert_eq!(metadata.codecs, vec!["h264", "aac"]);
+        assert_eq!(
+            metadata.created_at.as_deref(),
+            Some("2020-05-01T10:00:00.000000Z")
+        );
+        assert_eq!(
+            metadata.location,
+            Some(GeoLocation {
+                latitude: 36.2021,
+                longitude: 37.1343,
+                altitude: None
+            })
+        );
+        assert_eq!(metadata.tags["comment"], "first line\nsecond line");
+        assert!(!metadata.tags.contains_key("handler_name"));
+
+        assert!(parse_ffmpeg_info("video.mp4: Invalid data found").is_none());
+    }
+
+    #[test]
+    fn test_iso6709() {
+        let location = parse_iso6709("+52.5200-013.4050+034.000/").unwrap();
+        assert_eq!(location.latitude, 52.52);
+        assert_eq!(location.longitude, -13.405);
+        assert_eq!(location.altitude, Some(34.0));
+
+        assert!(parse_iso6709("nowhere").is_none());
+    }
+
+    #[test]
+    fn test_xmp_packet() {
+        let data = b"\xff\xd8junk<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"></x:xmpmeta>junk";
+
+        assert_eq!(
+            xmp_packet(data).as_deref(),
+            Some("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"></x:xmpmeta>")
+        );
+        assert!(xmp_packet(b"<x:xmpmeta").is_none());
+    }
+
+    #[test]
+    fn test_image_metadata() {
+        let fields = vec![
+            Field {
+                tag: Tag::DateTimeOriginal,
+                ifd_num: In::PRIMARY,
+                value: Value::Ascii(vec![b"2016:09:23 11:32:09".to_vec()]),
+            },
+            Field {
+                tag: Tag::GPSLatitudeRef,
+                ifd_num: In::PRIMARY,
+                value: Value::Ascii(vec![b"N".to_vec()]),
+            },
+            Field {
+                tag: Tag::GPSLatitude,
+                ifd_num: In::PRIMARY,
+                value: rationals(&[36, 12, 36]),
+            },
+            Field {
+                tag: Tag::GPSLongitudeRef,
+                ifd_num: In::PRIMARY,
+                value: Value::Ascii(vec![b"W".to_vec()]),
+            },
+            Field {
+                tag: Tag::GPSLongitude,
+                ifd_num: In::PRIMARY,
+                value: rationals(&[37, 30, 0]),
+            },
+        ];
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = Cursor::new(vec![]);
+        writer.write(&mut tiff, false).unwrap();
+
+        let metadata = image_metadata(tiff.get_ref()).unwrap();
+
+        assert_eq!(metadata.created_at.as_deref(), Some("2016-09-23 11:32:09"));
+        let location = metadata.location.unwrap();
+        assert!((location.latitude - 36.21).abs() < 1e-9);
+        assert!((location.longitude + 37.5).abs() < 1e-9);
+        assert_eq!(location.altitude, None);
+        assert!(metadata.tags.contains_key("GPSLatitude"));
+
+        assert_eq!(image_metadata(b"GIF89a").unwrap(), MediaMetadata::default());
+    }
+}
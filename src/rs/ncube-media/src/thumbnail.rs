@@ -0,0 +1,122 @@
+use std::path::Path;
+use std::process::Stdio;
+use tokio::process::Command;
+use tracing::instrument;
+
+use crate::{video_file_metadata, MediaError};
+
+/// Thumbnails and poster frames fit into a square of this size.
+pub const THUMBNAIL_SIZE: u32 = 320;
+
+/// Contact sheets are a grid of frames, each frame as wide as this.
+const SHEET_FRAME_WIDTH: u32 = 160;
+const SHEET_COLUMNS: u32 = 4;
+const SHEET_ROWS: u32 = 4;
+
+/// Poster frames are taken at this fraction of the duration of a video, the
+/// first frames are often black.
+const POSTER_POSITION: f64 = 0.1;
+
+async fn ffmpeg_frame(ffmpeg: &Path, args: &[&str], dest: &Path) -> Result<(), MediaError> {
+    let output = Command::new(ffmpeg)
+        .args(["-v", "error", "-y"])
+        .args(args)
+        .args(["-frames:v", "1"])
+        .arg(dest)
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(MediaError::Ffmpeg(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Scale an image down to fit into a square of `THUMBNAIL_SIZE` and write it
+/// to `dest`. The format is taken from the extension of `dest`.
+#[instrument]
+pub fn image_file_thumbnail(path: &Path, dest: &Path) -> Result<(), MediaError> {
+    let image = image::open(path)?;
+
+    image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).save(dest)?;
+
+    Ok(())
+}
+
+/// Write a poster frame and a contact sheet of a video. The contact sheet is
+/// a grid of 16 frames that are spread evenly over the duration of the
+/// video. Videos of unknown duration are sampled every 10 seconds.
+#[instrument]
+pub async fn video_file_thumbnails(
+    ffmpeg: &Path,
+    path: &Path,
+    poster: &Path,
+    sheet: &Path,
+) -> Result<(), MediaError> {
+    let duration = video_file_metadata(ffmpeg, path)
+        .await?
+        .duration
+        .filter(|duration| *duration > 0.0);
+
+    let position = duration.map_or(0.0, |duration| duration * POSTER_POSITION);
+    let scale = format!(
+        "scale={size}:{size}:force_original_aspect_ratio=decrease",
+        size = THUMBNAIL_SIZE
+    );
+    ffmpeg_frame(
+        ffmpeg,
+        &[
+            "-ss",
+            &format!("{:.3}", position),
+            "-i",
+            &path.to_string_lossy(),
+            "-vf",
+            &scale,
+        ],
+        poster,
+    )
+    .await?;
+
+    let frames = SHEET_COLUMNS * SHEET_ROWS;
+    let rate = duration.map_or("1/10".to_string(), |duration| {
+        format!("{:.6}", f64::from(frames) / duration)
+    });
+    let filter = format!(
+        "fps={},scale={}:-2,tile={}x{}",
+        rate, SHEET_FRAME_WIDTH, SHEET_COLUMNS, SHEET_ROWS
+    );
+    ffmpeg_frame(
+        ffmpeg,
+        &["-i", &path.to_string_lossy(), "-vf", &filter],
+        sheet,
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, GenericImageView, RgbImage};
+
+    #[test]
+    fn test_image_thumbnail() {
+        let dir = std::env::temp_dir().join(format!("ncube-thumbnail-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (path, dest) = (dir.join("image.png"), dir.join("image.jpg"));
+
+        DynamicImage::ImageRgb8(RgbImage::new(1280, 640))
+            .save(&path)
+            .unwrap();
+        image_file_thumbnail(&path, &dest).unwrap();
+        let thumbnail = image::open(&dest).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(thumbnail.dimensions(), (THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2));
+    }
+}
@@ -1,15 +1,150 @@
 #[macro_use]
 extern crate pest_derive;
 
+use chrono::NaiveDate;
 use ncube_data::normalize_str;
-use pest::Parser;
+use pest::{
+    error::{Error as PestError, ErrorVariant, InputLocation},
+    iterators::Pair,
+    Parser,
+};
 use std::clone::Clone;
-use std::default::Default;
+use std::fmt::{self, Display};
+use thiserror::Error;
+
+/// A search query that failed to parse. The position is the offset in
+/// characters into the query string where parsing failed, `expected` lists
+/// the tokens that would have been valid at that position.
+///
+/// # Example
+///
+/// ```
+/// # use ncube_search::parse_query;
+/// let err = parse_query(r#"tag:"syria"#).unwrap_err();
+/// assert_eq!(err.position, 4);
+/// assert_eq!(
+///   err.to_string(),
+///   "Invalid search query at position 4, expected search term."
+/// );
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+#[error("Invalid search query at position {position}, expected {}.", .expected.join(" or "))]
+pub struct SearchParseError {
+    pub position: usize,
+    pub expected: Vec<String>,
+}
+
+impl SearchParseError {
+    fn new(input: &str, offset: usize, expected: Vec<String>) -> Self {
+        Self {
+            position: input[..offset].chars().count(),
+            expected,
+        }
+    }
+
+    fn from_pest(input: &str, err: PestError<Rule>) -> Self {
+        let offset = match err.location {
+            InputLocation::Pos(pos) => pos,
+            InputLocation::Span((start, _)) => start,
+        };
+
+        let mut expected: Vec<String> = vec![];
+
+        if let ErrorVariant::ParsingError { positives, .. } = err.variant {
+            for rule in positives {
+                let name = rule_name(rule).to_string();
+                if !expected.contains(&name) {
+                    expected.push(name);
+                }
+            }
+        }
+
+        if expected.is_empty() {
+            expected.push("search term".into());
+        }
+
+        Self::new(input, offset, expected)
+    }
+}
+
+fn rule_name(rule: Rule) -> &'static str {
+    match rule {
+        Rule::EOI => "end of query",
+        Rule::and_op => "AND",
+        Rule::or_op => "OR",
+        Rule::not_op | Rule::negation => "NOT",
+        Rule::neg_op => "-",
+        Rule::date_range | Rule::date_between | Rule::date_cmp | Rule::date => "date",
+        Rule::cmp_op => "comparison",
+        Rule::limit => "limit",
+        Rule::annotation_key => "annotation key",
+        Rule::annotation_eq => "=",
+        Rule::annotation_value => "annotation value",
+        Rule::dup_value => "any, copy or unit id",
+        _ => "search term",
+    }
+}
+
+/// A range of days to limit the date of units. All bounds are inclusive,
+/// except for `After` and `Before`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DateRange {
+    On(NaiveDate),
+    Between(NaiveDate, NaiveDate),
+    After(NaiveDate),
+    From(NaiveDate),
+    Before(NaiveDate),
+    Until(NaiveDate),
+}
+
+impl Display for DateRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::On(date) => write!(f, "{}", date),
+            Self::Between(from, to) => write!(f, "{}..{}", from, to),
+            Self::After(date) => write!(f, ">{}", date),
+            Self::From(date) => write!(f, ">={}", date),
+            Self::Before(date) => write!(f, "<{}", date),
+            Self::Until(date) => write!(f, "<={}", date),
+        }
+    }
+}
+
+/// Limit units by their duplicates.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Duplicates {
+    /// Units that have at least one duplicate.
+    Any,
+    /// All duplicates except the first unit of every cluster of duplicates.
+    Copy,
+    /// All units in the same cluster of duplicates as the unit with this id.
+    Of(i32),
+}
+
+impl Display for Duplicates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::Copy => write!(f, "copy"),
+            Self::Of(id) => write!(f, "{}", id),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Limit {
     Tag(String),
     Source(String),
+    Author(String),
+    Language(String),
+    Title(String),
+    Created(DateRange),
+    Fetched(DateRange),
+    State(String),
+    Investigation(String),
+    /// An annotation key and the value it is annotated with.
+    Annotation(String, String),
+    Duplicate(Duplicates),
 }
 
 impl Limit {
@@ -20,61 +155,239 @@ impl Limit {
     pub fn new_source(term: &str) -> Self {
         Self::Source(term.to_string())
     }
+
+    pub fn new_author(term: &str) -> Self {
+        Self::Author(term.to_string())
+    }
+
+    pub fn new_language(term: &str) -> Self {
+        Self::Language(term.to_string())
+    }
+
+    pub fn new_title(term: &str) -> Self {
+        Self::Title(term.to_string())
+    }
+
+    pub fn new_state(term: &str) -> Self {
+        Self::State(term.to_string())
+    }
+
+    pub fn new_investigation(term: &str) -> Self {
+        Self::Investigation(term.to_string())
+    }
+
+    pub fn new_annotation(key: &str, term: &str) -> Self {
+        Self::Annotation(key.to_string(), term.to_string())
+    }
+
+    /// The term of the limit without any enclosing quotes.
+    pub fn value(&self) -> String {
+        match self {
+            Self::Tag(term)
+            | Self::Source(term)
+            | Self::Author(term)
+            | Self::Language(term)
+            | Self::Title(term)
+            | Self::State(term)
+            | Self::Investigation(term)
+            | Self::Annotation(_, term) => unquote(term).to_string(),
+            Self::Created(range) | Self::Fetched(range) => range.to_string(),
+            Self::Duplicate(duplicates) => duplicates.to_string(),
+        }
+    }
 }
 
-impl std::string::ToString for Limit {
-    fn to_string(&self) -> String {
+impl Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Tag(term) => format!("tag:{}", term),
-            Self::Source(term) => format!("source:{}", term),
+            Self::Tag(term) => write!(f, "tag:{}", term),
+            Self::Source(term) => write!(f, "source:{}", term),
+            Self::Author(term) => write!(f, "author:{}", term),
+            Self::Language(term) => write!(f, "lang:{}", term),
+            Self::Title(term) => write!(f, "title:{}", term),
+            Self::Created(range) => write!(f, "created:{}", range),
+            Self::Fetched(range) => write!(f, "fetched:{}", range),
+            Self::State(term) => write!(f, "state:{}", term),
+            Self::Investigation(term) => write!(f, "investigation:{}", term),
+            Self::Annotation(key, term) => write!(f, "annotation:{}={}", key, term),
+            Self::Duplicate(duplicates) => write!(f, "dup:{}", duplicates),
         }
     }
 }
 
-#[derive(Debug, Default, Clone)]
-pub struct SearchQuery {
-    pub terms: Option<String>,
-    pub limits: Vec<Limit>,
+/// The abstract syntax tree of a search query.
+///
+/// Terms are either single words or quoted phrases and are kept exactly as
+/// they were entered. The string representation of a query can be parsed
+/// again into the same query, which is what remote workspaces rely on.
+///
+/// # Example
+///
+/// ```
+/// # use ncube_search::{parse_query, Limit, SearchQuery};
+/// let query = parse_query("tag:syria OR tag:iraq NOT tag:duplicate").unwrap();
+/// assert_eq!(
+///   query,
+///   SearchQuery::And(vec![
+///     SearchQuery::Or(vec![
+///       SearchQuery::Limit(Limit::new_tag("syria")),
+///       SearchQuery::Limit(Limit::new_tag("iraq")),
+///     ]),
+///     SearchQuery::Not(Box::new(SearchQuery::Limit(Limit::new_tag("duplicate")))),
+///   ])
+/// );
+/// assert_eq!(query.to_string(), "tag:syria OR tag:iraq -tag:duplicate");
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SearchQuery {
+    Term(String),
+    Limit(Limit),
+    Not(Box<SearchQuery>),
+    And(Vec<SearchQuery>),
+    Or(Vec<SearchQuery>),
 }
 
 impl SearchQuery {
-    pub fn new() -> Self {
-        Default::default()
-    }
+    fn and(mut exprs: Vec<SearchQuery>) -> Self {
+        if exprs.len() == 1 {
+            return exprs.remove(0);
+        }
 
-    pub fn append_term(&mut self, term: &str) {
-        match &self.terms {
-            Some(terms) => {
-                let mut new_terms = terms.clone();
-                new_terms.push_str(" ");
-                new_terms.push_str(term);
-                self.terms.replace(new_terms.clone());
+        let mut flattened = vec![];
+        for expr in exprs {
+            match expr {
+                Self::And(inner) => flattened.extend(inner),
+                _ => flattened.push(expr),
             }
+        }
+
+        Self::And(flattened)
+    }
 
-            None => {
-                self.terms = Some(term.to_string());
+    fn or(mut exprs: Vec<SearchQuery>) -> Self {
+        if exprs.len() == 1 {
+            return exprs.remove(0);
+        }
+
+        let mut flattened = vec![];
+        for expr in exprs {
+            match expr {
+                Self::Or(inner) => flattened.extend(inner),
+                _ => flattened.push(expr),
             }
-        };
+        }
+
+        Self::Or(flattened)
+    }
+
+    /// A query is a text query if it only consists of terms, `title:` and
+    /// `author:` limits combined by `AND` and `OR`. Text queries can be fully
+    /// expressed as a full text search expression.
+    pub fn is_text(&self) -> bool {
+        match self {
+            Self::Term(_) | Self::Limit(Limit::Title(_)) | Self::Limit(Limit::Author(_)) => true,
+            Self::Limit(_) | Self::Not(_) => false,
+            Self::And(exprs) | Self::Or(exprs) => exprs.iter().all(|expr| expr.is_text()),
+        }
     }
 
-    pub fn append_limit(&mut self, limit: Limit) {
-        self.limits.push(limit);
+    /// Render a text query as a SQLite FTS5 query expression. Every term is
+    /// quoted to avoid that user input is interpreted as FTS5 syntax, only a
+    /// trailing `*` is kept to allow prefix searches. Returns `None` if the
+    /// query is not a text query.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use ncube_search::parse_query;
+    /// let query = parse_query(r#"(hospital OR clinic*) "air strike""#).unwrap();
+    /// assert_eq!(
+    ///   query.to_fts().unwrap(),
+    ///   r#"("hospital" OR "clinic"*) AND "air strike""#
+    /// );
+    /// ```
+    pub fn to_fts(&self) -> Option<String> {
+        if !self.is_text() {
+            return None;
+        }
+
+        Some(self.fts_expr())
     }
-}
 
-impl std::string::ToString for SearchQuery {
-    fn to_string(&self) -> String {
-        let mut terms: Vec<String> = vec![];
+    fn fts_expr(&self) -> String {
+        match self {
+            Self::Term(term) => fts_term(term),
+            Self::Limit(Limit::Title(term)) => format!("title : {}", fts_term(term)),
+            Self::Limit(Limit::Author(term)) => format!("author : {}", fts_term(term)),
+            Self::And(exprs) => exprs
+                .iter()
+                .map(|expr| expr.fts_group())
+                .collect::<Vec<String>>()
+                .join(" AND "),
+            Self::Or(exprs) => exprs
+                .iter()
+                .map(|expr| expr.fts_group())
+                .collect::<Vec<String>>()
+                .join(" OR "),
+            Self::Limit(_) | Self::Not(_) => unreachable!(),
+        }
+    }
 
-        if let Some(term) = &self.terms {
-            terms.push(term.clone());
+    fn fts_group(&self) -> String {
+        match self {
+            Self::And(_) | Self::Or(_) => format!("({})", self.fts_expr()),
+            _ => self.fts_expr(),
         }
+    }
+}
 
-        for limit in &self.limits {
-            terms.push(limit.to_string());
+impl Display for SearchQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Term(term) => write!(f, "{}", term),
+            Self::Limit(limit) => write!(f, "{}", limit),
+            Self::Not(expr) => match **expr {
+                Self::And(_) | Self::Or(_) => write!(f, "-({})", expr),
+                _ => write!(f, "-{}", expr),
+            },
+            Self::And(exprs) => {
+                let exprs: Vec<String> = exprs.iter().map(|expr| expr.to_string()).collect();
+                write!(f, "{}", exprs.join(" "))
+            }
+            Self::Or(exprs) => {
+                let exprs: Vec<String> = exprs
+                    .iter()
+                    .map(|expr| match expr {
+                        Self::And(_) => format!("({})", expr),
+                        _ => expr.to_string(),
+                    })
+                    .collect();
+                write!(f, "{}", exprs.join(" OR "))
+            }
         }
+    }
+}
 
-        terms.join(" ")
+fn unquote(term: &str) -> &str {
+    if term.len() > 1 && term.starts_with('"') && term.ends_with('"') {
+        &term[1..term.len() - 1]
+    } else {
+        term
+    }
+}
+
+fn fts_term(term: &str) -> String {
+    let (term, is_prefix) = match term.strip_suffix('*') {
+        Some(stripped) if !term.starts_with('"') && !stripped.is_empty() => (stripped, true),
+        _ => (unquote(term), false),
+    };
+
+    let quoted = format!("\"{}\"", term.replace('"', "\"\""));
+
+    if is_prefix {
+        format!("{}*", quoted)
+    } else {
+        quoted
     }
 }
 
@@ -82,100 +395,577 @@ impl std::string::ToString for SearchQuery {
 #[grammar = "search.pest"]
 pub struct SearchParser;
 
-pub fn parse_query(input: &str) -> SearchQuery {
-    let query = SearchParser::parse(Rule::query, input).unwrap();
+fn parse_expr(input: &str, pair: Pair<Rule>) -> Result<SearchQuery, SearchParseError> {
+    let expr = match pair.as_rule() {
+        Rule::and_expr => SearchQuery::and(
+            pair.into_inner()
+                .filter(|pair| pair.as_rule() != Rule::and_op)
+                .map(|pair| parse_expr(input, pair))
+                .collect::<Result<Vec<SearchQuery>, SearchParseError>>()?,
+        ),
+
+        Rule::or_expr => SearchQuery::or(
+            pair.into_inner()
+                .filter(|pair| pair.as_rule() != Rule::or_op)
+                .map(|pair| parse_expr(input, pair))
+                .collect::<Result<Vec<SearchQuery>, SearchParseError>>()?,
+        ),
+
+        Rule::negation => {
+            let expr = parse_expr(input, pair.into_inner().last().unwrap())?;
+            SearchQuery::Not(Box::new(expr))
+        }
+
+        Rule::limit => {
+            let mut inner_rules = pair.into_inner();
+            let selector_rule = inner_rules.next().unwrap().as_rule();
+            let value = inner_rules.next().unwrap();
+            let term = value.as_str();
+
+            let limit = match selector_rule {
+                Rule::tag => Limit::new_tag(&normalize_str(term)),
+                Rule::source => Limit::new_source(term),
+                Rule::author => Limit::new_author(term),
+                Rule::language => Limit::new_language(&normalize_str(term)),
+                Rule::title => Limit::new_title(term),
+                Rule::created => Limit::Created(parse_date_range(input, value)?),
+                Rule::fetched => Limit::Fetched(parse_date_range(input, value)?),
+                Rule::dup => Limit::Duplicate(parse_duplicates(input, value)?),
+                Rule::state => Limit::new_state(term),
+                Rule::investigation => Limit::new_investigation(term),
+                Rule::annotation => {
+                    // Skip the `=` between the key and the value.
+                    let value = inner_rules.nth(1).unwrap();
+                    Limit::new_annotation(term, value.as_str())
+                }
+                _ => unreachable!(),
+            };
+
+            SearchQuery::Limit(limit)
+        }
+
+        Rule::term => SearchQuery::Term(pair.as_str().to_string()),
+
+        _ => unreachable!(),
+    };
 
-    let mut result = SearchQuery::new();
+    Ok(expr)
+}
 
-    for expr in query {
-        match expr.as_rule() {
-            Rule::limit => {
-                let mut inner_rules = expr.into_inner();
-                let selector = inner_rules.next().unwrap().as_rule();
-                let term = inner_rules.next().unwrap().as_str();
+fn parse_date(input: &str, pair: Pair<Rule>) -> Result<NaiveDate, SearchParseError> {
+    // The grammar only ensures the format of a date, e.g. `2020-02-30` would
+    // still pass the parser.
+    NaiveDate::parse_from_str(pair.as_str(), "%Y-%m-%d").map_err(|_| {
+        SearchParseError::new(input, pair.as_span().start(), vec!["valid date".into()])
+    })
+}
 
-                let limit = match selector {
-                    Rule::tag => Limit::new_tag(&normalize_str(term)),
-                    Rule::source => Limit::new_source(term),
-                    _ => unreachable!(),
-                };
+fn parse_duplicates(input: &str, pair: Pair<Rule>) -> Result<Duplicates, SearchParseError> {
+    let duplicates = match pair.as_str().to_lowercase().as_str() {
+        "any" => Duplicates::Any,
+        "copy" => Duplicates::Copy,
+        // The grammar only ensures digits, the id might still overflow.
+        id => Duplicates::Of(id.parse().map_err(|_| {
+            SearchParseError::new(input, pair.as_span().start(), vec!["unit id".into()])
+        })?),
+    };
 
-                result.append_limit(limit);
+    Ok(duplicates)
+}
+
+fn parse_date_range(input: &str, pair: Pair<Rule>) -> Result<DateRange, SearchParseError> {
+    let range = pair.into_inner().next().unwrap();
+
+    let date_range = match range.as_rule() {
+        Rule::date => DateRange::On(parse_date(input, range)?),
+
+        Rule::date_between => {
+            let is_open_start = range.as_str().starts_with("..");
+            let dates = range
+                .into_inner()
+                .map(|pair| parse_date(input, pair))
+                .collect::<Result<Vec<NaiveDate>, SearchParseError>>()?;
+            let mut dates = dates.into_iter();
+
+            match (dates.next(), dates.next()) {
+                (Some(from), Some(to)) => DateRange::Between(from, to),
+                (Some(date), None) if is_open_start => DateRange::Until(date),
+                (Some(date), None) => DateRange::From(date),
+                _ => unreachable!(),
             }
+        }
 
-            Rule::term => {
-                let term = expr.as_str();
-                result.append_term(term);
+        Rule::date_cmp => {
+            let mut inner_rules = range.into_inner();
+            let op = inner_rules.next().unwrap().as_str();
+            let date = parse_date(input, inner_rules.next().unwrap())?;
+
+            match op {
+                ">" => DateRange::After(date),
+                ">=" => DateRange::From(date),
+                "<" => DateRange::Before(date),
+                "<=" => DateRange::Until(date),
+                _ => unreachable!(),
             }
+        }
 
-            Rule::EOI => (),
-            _ => unreachable!(),
+        _ => unreachable!(),
+    };
+
+    Ok(date_range)
+}
+
+pub fn parse_query(input: &str) -> Result<SearchQuery, SearchParseError> {
+    let query = SearchParser::parse(Rule::query, input)
+        .map_err(|err| SearchParseError::from_pest(input, err))?
+        .next()
+        .unwrap();
+
+    let expr = query.into_inner().next().unwrap();
+
+    parse_expr(input, expr)
+}
+
+/// Parse a query that was stored before the current search grammar, e.g.
+/// the query of a segment. Those queries consist of terms, phrases and
+/// `tag:`/`source:` limits separated by whitespace. Terms could contain
+/// parentheses and `AND`/`OR` had no special meaning, which the current
+/// grammar rejects. If a query fails to parse it is therefore read the way
+/// it was read before: every word is a term and all of them must match.
+///
+/// # Example
+///
+/// ```
+/// # use ncube_search::{parse_stored_query, SearchQuery};
+/// let query = parse_stored_query("syria (2020) OR");
+/// assert_eq!(
+///   query,
+///   SearchQuery::And(vec![
+///     SearchQuery::Term("syria".into()),
+///     SearchQuery::Term("(2020)".into()),
+///     SearchQuery::Term("OR".into()),
+///   ])
+/// );
+/// ```
+pub fn parse_stored_query(input: &str) -> SearchQuery {
+    parse_query(input).unwrap_or_else(|_| parse_legacy_query(input))
+}
+
+fn parse_legacy_query(input: &str) -> SearchQuery {
+    let mut tokens: Vec<String> = vec![];
+    let mut token = String::new();
+    let mut in_phrase = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                in_phrase = !in_phrase;
+                token.push(c);
+            }
+            ' ' | '\r' | '\t' | '\n' if !in_phrase => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            _ => token.push(c),
         }
     }
 
-    result
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    let exprs = tokens
+        .iter()
+        .map(|token| match token.split_once(':') {
+            Some((selector, term)) if !term.is_empty() && selector.eq_ignore_ascii_case("tag") => {
+                SearchQuery::Limit(Limit::new_tag(&normalize_str(term)))
+            }
+            Some((selector, term))
+                if !term.is_empty() && selector.eq_ignore_ascii_case("source") =>
+            {
+                SearchQuery::Limit(Limit::new_source(term))
+            }
+            _ => SearchQuery::Term(token.to_string()),
+        })
+        .collect();
+
+    SearchQuery::and(exprs)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn term(term: &str) -> SearchQuery {
+        SearchQuery::Term(term.to_string())
+    }
+
+    fn tag(term: &str) -> SearchQuery {
+        SearchQuery::Limit(Limit::new_tag(term))
+    }
+
+    fn source(term: &str) -> SearchQuery {
+        SearchQuery::Limit(Limit::new_source(term))
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn not(expr: SearchQuery) -> SearchQuery {
+        SearchQuery::Not(Box::new(expr))
+    }
+
     #[test]
     fn test_a_single_term() {
         let input = "term";
-        let query = parse_query(&input);
-        assert_eq!(query.terms, Some(input.to_string()));
-        assert!(query.limits.is_empty());
+        let query = parse_query(input).unwrap();
+        assert_eq!(query, term(input));
     }
 
     #[test]
     fn test_a_compund_terms() {
         let input = "term1 term2";
-        let query = parse_query(&input);
-        assert_eq!(query.terms, Some(input.to_string()));
-        assert!(query.limits.is_empty());
+        let query = parse_query(input).unwrap();
+        assert_eq!(query, SearchQuery::And(vec![term("term1"), term("term2")]));
     }
 
     #[test]
     fn test_a_quoted_term() {
         let input = r#""term1 term2""#;
-        let query = parse_query(&input);
-        assert_eq!(query.terms, Some(input.to_string()));
-        assert!(query.limits.is_empty());
+        let query = parse_query(input).unwrap();
+        assert_eq!(query, term(input));
     }
 
     #[test]
     fn test_tag_selector() {
         let input = "tag:term";
-        let query = parse_query(&input);
-        assert_eq!(query.limits, vec![Limit::new_tag("term")]);
-        assert!(query.terms.is_none());
+        let query = parse_query(input).unwrap();
+        assert_eq!(query, tag("term"));
     }
 
     #[test]
     fn test_tag_selector_with_compound_term() {
         let input = r#"tag:"term1 term2""#;
-        let query = parse_query(&input);
-        assert_eq!(query.limits, vec![Limit::new_tag(r#""term1 term2""#)]);
-        assert!(query.terms.is_none());
+        let query = parse_query(input).unwrap();
+        assert_eq!(query, tag(r#""term1 term2""#));
+        assert_eq!(Limit::new_tag(r#""term1 term2""#).value(), "term1 term2");
     }
 
     #[test]
     fn test_source_selector() {
         let input = "source:term";
-        let query = parse_query(&input);
-        assert_eq!(query.limits, vec![Limit::new_source("term")]);
-        assert!(query.terms.is_none());
+        let query = parse_query(input).unwrap();
+        assert_eq!(query, source("term"));
     }
 
     #[test]
     fn test_advanced_query() {
         let input = "source:youtube_video term1 term2 tag:greg term3";
-        let query = parse_query(&input);
-        assert_eq!(query.terms, Some("term1 term2 term3".to_string()));
+        let query = parse_query(input).unwrap();
+        assert_eq!(
+            query,
+            SearchQuery::And(vec![
+                source("youtube_video"),
+                term("term1"),
+                term("term2"),
+                tag("greg"),
+                term("term3"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_boolean_operators() {
+        let query = parse_query("term1 AND term2 OR term3").unwrap();
+        assert_eq!(
+            query,
+            SearchQuery::And(vec![
+                term("term1"),
+                SearchQuery::Or(vec![term("term2"), term("term3")])
+            ])
+        );
+    }
+
+    #[test]
+    fn test_operators_are_case_sensitive() {
+        let query = parse_query("term1 or term2").unwrap();
+        assert_eq!(
+            query,
+            SearchQuery::And(vec![term("term1"), term("or"), term("term2")])
+        );
+
+        let query = parse_query("ANDROID ORCA").unwrap();
+        assert_eq!(query, SearchQuery::And(vec![term("ANDROID"), term("ORCA")]));
+    }
+
+    #[test]
+    fn test_negation() {
+        assert_eq!(parse_query("NOT term").unwrap(), not(term("term")));
+        assert_eq!(parse_query("-term").unwrap(), not(term("term")));
+        assert_eq!(
+            parse_query("-tag:duplicate").unwrap(),
+            not(tag("duplicate"))
+        );
+        assert_eq!(
+            parse_query("term1 - term2").unwrap(),
+            SearchQuery::And(vec![term("term1"), term("-"), term("term2")])
+        );
+        assert_eq!(parse_query("al-assad").unwrap(), term("al-assad"));
+    }
+
+    #[test]
+    fn test_groups() {
+        let query = parse_query("(tag:syria OR tag:iraq) -(source:youtube_video term)").unwrap();
+        assert_eq!(
+            query,
+            SearchQuery::And(vec![
+                SearchQuery::Or(vec![tag("syria"), tag("iraq")]),
+                not(SearchQuery::And(vec![
+                    source("youtube_video"),
+                    term("term")
+                ]))
+            ])
+        );
+    }
+
+    #[test]
+    fn test_query_round_trip() {
+        let inputs = vec![
+            "term",
+            r#""term1 term2" tag:"term1 term2""#,
+            "tag:syria OR tag:iraq -tag:duplicate",
+            "(term1 term2) OR term3",
+            "-(term1 OR term2) source:youtube_video",
+        ];
+
+        for input in inputs {
+            let query = parse_query(input).unwrap();
+            assert_eq!(query.to_string(), input);
+            assert_eq!(parse_query(&query.to_string()).unwrap(), query);
+        }
+    }
+
+    #[test]
+    fn test_fts_expression() {
+        assert_eq!(
+            parse_query("term1 OR term2").unwrap().to_fts(),
+            Some(r#""term1" OR "term2""#.into())
+        );
+        assert_eq!(
+            parse_query(r#"http://example.com prefix*"#)
+                .unwrap()
+                .to_fts(),
+            Some(r#""http://example.com" AND "prefix"*"#.into())
+        );
+        assert_eq!(parse_query("term tag:term").unwrap().to_fts(), None);
+        assert_eq!(parse_query("-term").unwrap().to_fts(), None);
+    }
+
+    #[test]
+    fn test_fielded_selectors() {
+        let query = parse_query(r#"author:"Syrian Archive" lang:AR title:hospital"#).unwrap();
+        assert_eq!(
+            query,
+            SearchQuery::And(vec![
+                SearchQuery::Limit(Limit::new_author(r#""Syrian Archive""#)),
+                SearchQuery::Limit(Limit::new_language("ar")),
+                SearchQuery::Limit(Limit::new_title("hospital")),
+            ])
+        );
+        assert_eq!(
+            parse_query("language:en").unwrap(),
+            SearchQuery::Limit(Limit::new_language("en"))
+        );
+    }
+
+    #[test]
+    fn test_date_selectors() {
+        assert_eq!(
+            parse_query("created:2020-01-01..2020-03-31").unwrap(),
+            SearchQuery::Limit(Limit::Created(DateRange::Between(
+                date("2020-01-01"),
+                date("2020-03-31")
+            )))
+        );
+        assert_eq!(
+            parse_query("fetched:>2021-05-01").unwrap(),
+            SearchQuery::Limit(Limit::Fetched(DateRange::After(date("2021-05-01"))))
+        );
+        assert_eq!(
+            parse_query("created:2020-01-01..").unwrap(),
+            SearchQuery::Limit(Limit::Created(DateRange::From(date("2020-01-01"))))
+        );
+        assert_eq!(
+            parse_query("created:..2020-01-01").unwrap(),
+            SearchQuery::Limit(Limit::Created(DateRange::Until(date("2020-01-01"))))
+        );
+        assert_eq!(
+            parse_query("created:<=2020-01-01").unwrap(),
+            SearchQuery::Limit(Limit::Created(DateRange::Until(date("2020-01-01"))))
+        );
+        assert_eq!(
+            parse_query("(created:2020-01-01)").unwrap(),
+            SearchQuery::Limit(Limit::Created(DateRange::On(date("2020-01-01"))))
+        );
+    }
+
+    #[test]
+    fn test_fielded_round_trip() {
+        let inputs = vec![
+            r#"author:"Syrian Archive" lang:ar title:hospital*"#,
+            "created:2020-01-01..2020-03-31 fetched:>2021-05-01",
+            "created:>=2020-01-01 -created:<2020-02-01 fetched:<=2020-03-01",
+        ];
+
+        for input in inputs {
+            let query = parse_query(input).unwrap();
+            assert_eq!(query.to_string(), input);
+            assert_eq!(parse_query(&query.to_string()).unwrap(), query);
+        }
+    }
+
+    #[test]
+    fn test_fielded_fts_expression() {
+        assert_eq!(
+            parse_query(r#"title:hospital OR author:"Syrian Archive""#)
+                .unwrap()
+                .to_fts(),
+            Some(r#"title : "hospital" OR author : "Syrian Archive""#.into())
+        );
+        assert_eq!(
+            parse_query("created:2020-01-01 hospital").unwrap().to_fts(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_verification_selectors() {
+        let query =
+            parse_query(r#"state:verified_data investigation:mf001 annotation:location="Aleppo""#)
+                .unwrap();
+        assert_eq!(
+            query,
+            SearchQuery::And(vec![
+                SearchQuery::Limit(Limit::new_state("verified_data")),
+                SearchQuery::Limit(Limit::new_investigation("mf001")),
+                SearchQuery::Limit(Limit::new_annotation("location", r#""Aleppo""#)),
+            ])
+        );
+        assert_eq!(
+            parse_query("annotation:has-teargas=true").unwrap(),
+            SearchQuery::Limit(Limit::new_annotation("has-teargas", "true"))
+        );
+        assert_eq!(
+            Limit::new_annotation("location", r#""Aleppo""#).value(),
+            "Aleppo"
+        );
+        assert_eq!(parse_query("state:desk_research").unwrap().to_fts(), None);
+    }
+
+    #[test]
+    fn test_verification_round_trip() {
+        let inputs = vec![
+            "state:desk_research -investigation:mf001",
+            r#"annotation:location="Aleppo" OR annotation:has-teargas=true"#,
+        ];
+
+        for input in inputs {
+            let query = parse_query(input).unwrap();
+            assert_eq!(query.to_string(), input);
+            assert_eq!(parse_query(&query.to_string()).unwrap(), query);
+        }
+    }
+
+    #[test]
+    fn test_duplicate_selectors() {
+        assert_eq!(
+            parse_query("dup:any").unwrap(),
+            SearchQuery::Limit(Limit::Duplicate(Duplicates::Any))
+        );
+        assert_eq!(
+            parse_query("hospital -dup:Copy").unwrap(),
+            SearchQuery::And(vec![
+                SearchQuery::Term("hospital".into()),
+                SearchQuery::Not(Box::new(SearchQuery::Limit(Limit::Duplicate(
+                    Duplicates::Copy
+                )))),
+            ])
+        );
+        assert_eq!(
+            parse_query("dup:42").unwrap(),
+            SearchQuery::Limit(Limit::Duplicate(Duplicates::Of(42)))
+        );
+        assert_eq!(
+            parse_query("hospital -dup:COPY").unwrap().to_string(),
+            "hospital -dup:copy"
+        );
+
+        let err = parse_query("dup:maybe").unwrap_err();
+        assert_eq!(err.position, 4);
+        assert_eq!(err.expected, vec!["any, copy or unit id".to_string()]);
+
+        let err = parse_query("dup:99999999999").unwrap_err();
+        assert_eq!(err.expected, vec!["unit id".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse_query("").unwrap_err();
+        assert_eq!(err.position, 0);
+
+        let err = parse_query(r#"tag:"syria"#).unwrap_err();
+        assert_eq!(err.position, 4);
+        assert_eq!(err.expected, vec!["search term".to_string()]);
+
+        let err = parse_query("foo OR").unwrap_err();
+        assert_eq!(err.position, 6);
+        assert!(err.expected.contains(&"search term".to_string()));
+
+        let err = parse_query("created:2020-02-30").unwrap_err();
+        assert_eq!(err.position, 8);
+        assert_eq!(
+            err.to_string(),
+            "Invalid search query at position 8, expected valid date."
+        );
+
+        let err = parse_query("annotation:location").unwrap_err();
+        assert_eq!(err.position, 19);
+        assert_eq!(
+            err.to_string(),
+            "Invalid search query at position 19, expected =."
+        );
+
+        let err = parse_query("läuft OR").unwrap_err();
+        assert_eq!(err.position, 8);
+    }
+
+    #[test]
+    fn test_stored_queries() {
+        assert_eq!(
+            parse_stored_query("tag:syria OR tag:iraq"),
+            parse_query("tag:syria OR tag:iraq").unwrap()
+        );
+
+        assert_eq!(
+            parse_stored_query("airstrike (aleppo"),
+            SearchQuery::And(vec![term("airstrike"), term("(aleppo")])
+        );
+
+        assert_eq!(
+            parse_stored_query(r#"AND "air strike" tag:Syria source:youtube_video"#),
+            SearchQuery::And(vec![
+                term("AND"),
+                term(r#""air strike""#),
+                tag("syria"),
+                source("youtube_video"),
+            ])
+        );
+
         assert_eq!(
-            query.limits,
-            vec![Limit::new_source("youtube_video"), Limit::new_tag("greg")]
+            parse_stored_query("foo OR").to_fts().unwrap(),
+            r#""foo" AND "OR""#
         );
     }
 }
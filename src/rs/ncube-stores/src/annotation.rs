@@ -15,12 +15,14 @@ pub fn annotation_store(wrapped_db: Database) -> Box<dyn AnnotationStore + Send
 
 #[async_trait]
 pub trait AnnotationStore {
+    #[allow(clippy::too_many_arguments)]
     async fn create(
         &self,
         key: &str,
         value: &serde_json::Value,
         name: &str,
         note: &Option<String>,
+        author: &Option<String>,
         investigation: &str,
         verification: i32,
     ) -> Result<(), DatabaseError>;
@@ -38,6 +40,7 @@ pub struct AnnotationStoreSqlite {
 
 #[async_trait]
 impl AnnotationStore for AnnotationStoreSqlite {
+    #[allow(clippy::too_many_arguments)]
     #[instrument]
     async fn create(
         &self,
@@ -45,6 +48,7 @@ impl AnnotationStore for AnnotationStoreSqlite {
         value: &serde_json::Value,
         name: &str,
         note: &Option<String>,
+        author: &Option<String>,
         _investigation: &str,
         verification: i32,
     ) -> Result<(), DatabaseError> {
@@ -59,6 +63,7 @@ impl AnnotationStore for AnnotationStoreSqlite {
             &value,
             &name,
             &note,
+            &author,
             &now.to_rfc3339(),
             &now.to_rfc3339()
         ])?;
@@ -90,12 +95,15 @@ pub struct AnnotationStoreHttp {
 
 #[async_trait]
 impl AnnotationStore for AnnotationStoreHttp {
+    #[allow(clippy::too_many_arguments)]
     async fn create(
         &self,
         key: &str,
         value: &serde_json::Value,
         name: &str,
         note: &Option<String>,
+        // The remote workspace records the account of the request as author.
+        _author: &Option<String>,
         investigation: &str,
         verification: i32,
     ) -> Result<(), DatabaseError> {
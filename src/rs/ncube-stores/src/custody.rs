@@ -0,0 +1,666 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use ncube_crypto::{chain_hash, CHAIN_ROOT};
+use ncube_data::{BrokenLink, ChainBreak, CustodyChain, CustodyEvent};
+use ncube_db::{errors::DatabaseError, http, sqlite, Database};
+use rusqlite::{params, types::ValueRef, Row, NO_PARAMS};
+use serde_rusqlite::from_row;
+use tracing::instrument;
+
+pub fn custody_store(wrapped_db: Database) -> Box<dyn CustodyStore + Send + Sync> {
+    match wrapped_db {
+        Database::Sqlite(db) => Box::new(CustodyStoreSqlite { db }),
+        Database::Http(client) => Box::new(CustodyStoreHttp { client }),
+    }
+}
+
+#[async_trait]
+pub trait CustodyStore {
+    /// Record the start of a data process. Units that are fetched until the
+    /// run is finished are attributed to this run. Runs that were never
+    /// finished, e.g. because Ncube was stopped, are finished first.
+    async fn start_run(&self, process: &str, task: &str) -> Result<i32, DatabaseError>;
+    async fn finish_run(&self, run: i32) -> Result<(), DatabaseError>;
+    async fn list(&self, unit: i32) -> Result<Vec<CustodyEvent>, DatabaseError>;
+    /// Link all custody events that were recorded since the last seal to the
+    /// custody chain.
+    async fn seal(&self) -> Result<(), DatabaseError>;
+    /// Replay the custody chain and stop at the first broken link. The links
+    /// are stored next to the events, a chain that was recomputed from the
+    /// start is intact as well. Only `anchor`, a head that was recorded
+    /// outside of the workspace, proves that the chain up to this head is
+    /// unchanged.
+    async fn verify_chain(&self, anchor: Option<&str>) -> Result<CustodyChain, DatabaseError>;
+}
+
+/// The bytes of a custody event that are hashed into the chain. The columns
+/// are taken as they are stored, a value that changes its type changes the
+/// record as well.
+fn custody_record(row: &Row) -> Result<String, rusqlite::Error> {
+    let mut fields = vec![];
+
+    for i in 0..5 {
+        let field = match row.get_raw_checked(i)? {
+            ValueRef::Null => "".to_string(),
+            ValueRef::Integer(value) => value.to_string(),
+            ValueRef::Real(value) => value.to_string(),
+            ValueRef::Text(value) => String::from_utf8_lossy(value).to_string(),
+            ValueRef::Blob(value) => value.iter().map(|b| format!("{:02x}", b)).collect(),
+        };
+        fields.push(field);
+    }
+
+    Ok(fields.join("\n"))
+}
+
+#[derive(Debug)]
+pub struct CustodyStoreSqlite {
+    db: Box<sqlite::Database>,
+}
+
+#[async_trait]
+impl CustodyStore for CustodyStoreSqlite {
+    #[instrument]
+    async fn start_run(&self, process: &str, task: &str) -> Result<i32, DatabaseError> {
+        let now = Utc::now();
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/custody/finish-stale-runs.sql"))?;
+        let mut stmt2 = conn.prepare_cached(include_str!("../sql/custody/create-run.sql"))?;
+
+        conn.execute_batch("BEGIN;")?;
+        stmt.execute(params![&now.to_rfc3339()])?;
+        stmt2.execute(params![&process, &task, &now.to_rfc3339()])?;
+        let run = conn.last_insert_rowid() as i32;
+        conn.execute_batch("COMMIT;")?;
+
+        Ok(run)
+    }
+
+    #[instrument]
+    async fn finish_run(&self, run: i32) -> Result<(), DatabaseError> {
+        let now = Utc::now();
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/custody/finish-run.sql"))?;
+
+        stmt.execute(params![run, &now.to_rfc3339()])?;
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn list(&self, unit: i32) -> Result<Vec<CustodyEvent>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/custody/list.sql"))?;
+
+        let mut events: Vec<CustodyEvent> = vec![];
+        let mut rows = stmt.query(params![unit])?;
+        while let Some(row) = rows.next()? {
+            // The data column holds JSON text, which has to be parsed instead
+            // of being deserialized as a string.
+            let data: serde_json::Value = row.get(3)?;
+            let mut event = from_row::<CustodyEvent>(row)?;
+            event.data = data;
+            events.push(event);
+        }
+
+        Ok(events)
+    }
+
+    #[instrument]
+    async fn seal(&self) -> Result<(), DatabaseError> {
+        let now = Utc::now();
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/custody/last-link.sql"))?;
+        let mut stmt2 = conn.prepare_cached(include_str!("../sql/custody/list-unsealed.sql"))?;
+        let mut stmt3 = conn.prepare_cached(include_str!("../sql/custody/create-link.sql"))?;
+
+        conn.execute_batch("BEGIN;")?;
+
+        let mut rows = stmt.query(NO_PARAMS)?;
+        let (last, mut previous): (i32, String) = match rows.next()? {
+            Some(row) => (row.get(0)?, row.get(1)?),
+            None => (0, CHAIN_ROOT.to_string()),
+        };
+
+        let records = stmt2
+            .query_map(params![last], |row| {
+                Ok((row.get::<_, i32>(0)?, custody_record(row)?))
+            })?
+            .collect::<Result<Vec<(i32, String)>, _>>()?;
+
+        // Links are derived from the events alone, concurrent seals insert
+        // the same links.
+        for (event, record) in records {
+            previous = chain_hash(&previous, record.as_bytes());
+            stmt3.execute(params![event, &previous, &now.to_rfc3339()])?;
+        }
+
+        conn.execute_batch("COMMIT;")?;
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn verify_chain(&self, anchor: Option<&str>) -> Result<CustodyChain, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/custody/chain.sql"))?;
+
+        let mut chain = CustodyChain {
+            length: 0,
+            unsealed: 0,
+            head: None,
+            broken: None,
+            anchored: anchor.map(|_| false),
+        };
+        let mut previous = CHAIN_ROOT.to_string();
+        let mut first_unsealed: Option<i32> = None;
+
+        // Links and events are ordered by event, events without a link come
+        // without a hash.
+        let mut rows = stmt.query(NO_PARAMS)?;
+        while let Some(row) = rows.next()? {
+            let event: i32 = row.get(0)?;
+            let exists: bool = row.get(5)?;
+            let found: Option<String> = row.get(6)?;
+
+            let found = match found {
+                Some(found) => found,
+                None => {
+                    chain.unsealed += 1;
+                    first_unsealed.get_or_insert(event);
+                    continue;
+                }
+            };
+
+            if chain.broken.is_some() {
+                continue;
+            }
+
+            if let Some(event) = first_unsealed {
+                chain.broken = Some(BrokenLink {
+                    event,
+                    reason: ChainBreak::Inserted,
+                    expected: None,
+                    found: None,
+                });
+                continue;
+            }
+
+            if !exists {
+                chain.broken = Some(BrokenLink {
+                    event,
+                    reason: ChainBreak::Deleted,
+                    expected: None,
+                    found: Some(found),
+                });
+                continue;
+            }
+
+            let expected = chain_hash(&previous, custody_record(row)?.as_bytes());
+            if expected != found {
+                chain.broken = Some(BrokenLink {
+                    event,
+                    reason: ChainBreak::Altered,
+                    expected: Some(expected),
+                    found: Some(found),
+                });
+                continue;
+            }
+
+            if let Some(anchor) = anchor {
+                if anchor.eq_ignore_ascii_case(&found) {
+                    chain.anchored = Some(true);
+                }
+            }
+
+            chain.length += 1;
+            chain.head = Some(found.clone());
+            previous = found;
+        }
+
+        Ok(chain)
+    }
+}
+
+#[derive(Debug)]
+pub struct CustodyStoreHttp {
+    client: Box<http::Database>,
+}
+
+#[async_trait]
+impl CustodyStore for CustodyStoreHttp {
+    async fn start_run(&self, _process: &str, _task: &str) -> Result<i32, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn finish_run(&self, _run: i32) -> Result<(), DatabaseError> {
+        unreachable!()
+    }
+
+    #[instrument]
+    async fn list(&self, unit: i32) -> Result<Vec<CustodyEvent>, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/data/units/{}/custody",
+            self.client.workspace.slug, unit
+        ));
+
+        let data: Vec<CustodyEvent> = self.client.get(url).await?.unwrap_or_default();
+
+        Ok(data)
+    }
+
+    // Remote workspaces seal their events themselves.
+    async fn seal(&self) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    #[instrument]
+    async fn verify_chain(&self, anchor: Option<&str>) -> Result<CustodyChain, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/custody",
+            self.client.workspace.slug
+        ));
+        if let Some(anchor) = anchor {
+            url.query_pairs_mut().append_pair("anchor", anchor);
+        }
+
+        let data: CustodyChain = self
+            .client
+            .get(url)
+            .await?
+            .ok_or_else(|| DatabaseError::NotFound("couldn't retrieve custody chain".into()))?;
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ncube_data::CustodyEventKind;
+    use serde_json::json;
+
+    use super::*;
+    use crate::tests::database;
+
+    // The fixture records 14 events: 7 units, 3 verifications and 4
+    // annotations.
+    const EVENTS: i32 = 14;
+
+    async fn link(db: &sqlite::Database, event: i32) -> String {
+        let conn = db.connection().await.unwrap();
+        conn.query_row(
+            "SELECT hash FROM custody_link WHERE event = ?1",
+            params![event],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    async fn record(db: &sqlite::Database, event: i32) -> String {
+        let conn = db.connection().await.unwrap();
+        conn.query_row(
+            "SELECT id, unit, kind, data, created_at FROM custody_event WHERE id = ?1",
+            params![event],
+            custody_record,
+        )
+        .unwrap()
+    }
+
+    /// Change the custody log behind the back of the immutability triggers.
+    async fn tamper(db: &sqlite::Database, sql: &str) {
+        let conn = db.connection().await.unwrap();
+        conn.execute_batch(
+            r#"
+            DROP TRIGGER custody_event_bu;
+            DROP TRIGGER custody_event_bd;
+            DROP TRIGGER custody_link_bu;
+            DROP TRIGGER custody_link_bd;
+            "#,
+        )
+        .unwrap();
+        conn.execute_batch(sql).unwrap();
+    }
+
+    async fn sealed() -> (Box<sqlite::Database>, CustodyStoreSqlite) {
+        let db = database().await;
+        let store = CustodyStoreSqlite { db: db.clone() };
+        store.seal().await.unwrap();
+        (db, store)
+    }
+
+    #[tokio::test]
+    async fn test_triggers_record_custody_events() {
+        let db = database().await;
+        let store = CustodyStoreSqlite { db: db.clone() };
+
+        let run = store.start_run("youtube_video", "search").await.unwrap();
+        db.connection()
+            .await
+            .unwrap()
+            .execute_batch(
+                r#"
+                INSERT INTO unit (id, id_hash, content_hash, source, body, href, fetched_at)
+                VALUES (8, 'h8', 'c8', 'youtube_video', 'protest in homs',
+                        'https://youtube.com/watch?v=8', '2020-07-01T10:00:00.000Z');
+                INSERT INTO download (id, unit, id_hash, type, term)
+                VALUES (1, 8, 'd1', 'video', 'https://youtube.com/watch?v=8');
+                UPDATE download SET location = '8/video.mp4', sha256 = 'abc' WHERE id = 1;
+                UPDATE download SET term = 'https://youtu.be/8' WHERE id = 1;
+                INSERT INTO verification (id, investigation, segment, unit, state)
+                VALUES (4, 1, 1, 8, '{"value":"incoming_data"}');
+                UPDATE verification SET state = '{"value":"desk_research"}' WHERE id = 4;
+                INSERT INTO annotation (verification, key, value, name, author)
+                VALUES (4, 'location', '"Homs"', 'Location', 'alice');
+                UPDATE annotation SET value = '"Homs City"' WHERE verification = 4;
+                "#,
+            )
+            .unwrap();
+        store.finish_run(run).await.unwrap();
+
+        let events = store.list(8).await.unwrap();
+        let kinds: Vec<CustodyEventKind> = events.iter().map(|event| event.kind).collect();
+
+        // Updates of a download that don't change the file aren't recorded.
+        assert_eq!(
+            kinds,
+            vec![
+                CustodyEventKind::Fetched,
+                CustodyEventKind::Downloaded,
+                CustodyEventKind::Downloaded,
+                CustodyEventKind::Verification,
+                CustodyEventKind::Verification,
+                CustodyEventKind::Annotation,
+                CustodyEventKind::Annotation,
+            ]
+        );
+        assert!(events.iter().all(|event| event.unit == 8));
+        assert_eq!(
+            events[0].data,
+            json!({
+                "source": "youtube_video",
+                "href": "https://youtube.com/watch?v=8",
+                "fetched_at": "2020-07-01T10:00:00.000Z",
+                "run": {"id": run, "process": "youtube_video", "task": "search"},
+            })
+        );
+        assert_eq!(events[1].data["location"], json!(null));
+        assert_eq!(events[2].data["location"], json!("8/video.mp4"));
+        assert_eq!(events[2].data["sha256"], json!("abc"));
+        assert_eq!(
+            events[4].data,
+            json!({"investigation": "aleppo", "segment": "aleppo", "state": "desk_research"})
+        );
+        assert_eq!(
+            events[6].data,
+            json!({
+                "investigation": "aleppo",
+                "key": "location",
+                "value": "Homs City",
+                "name": "Location",
+                "note": null,
+                "author": "alice",
+            })
+        );
+
+        // Units fetched outside of a process run aren't attributed to one.
+        db.connection()
+            .await
+            .unwrap()
+            .execute_batch(
+                r#"
+                UPDATE unit SET fetched_at = '2020-07-02T10:00:00.000Z' WHERE id = 8;
+                "#,
+            )
+            .unwrap();
+
+        let events = store.list(8).await.unwrap();
+        assert_eq!(events.len(), 8);
+        assert_eq!(events[7].kind, CustodyEventKind::Fetched);
+        assert_eq!(events[7].data["run"], json!(null));
+    }
+
+    #[tokio::test]
+    async fn test_custody_events_are_immutable() {
+        let db = database().await;
+        let conn = db.connection().await.unwrap();
+
+        let err = conn
+            .execute(
+                "UPDATE custody_event SET data = '{}' WHERE id = 1",
+                NO_PARAMS,
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("custody events are immutable"));
+
+        let err = conn
+            .execute("DELETE FROM custody_event WHERE id = 1", NO_PARAMS)
+            .unwrap_err();
+        assert!(err.to_string().contains("custody events are immutable"));
+
+        let events: i32 = conn
+            .query_row("SELECT count(*) FROM custody_event", NO_PARAMS, |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(events, 14);
+    }
+
+    #[tokio::test]
+    async fn test_seal_links_every_event() {
+        let db = database().await;
+        let store = CustodyStoreSqlite { db: db.clone() };
+
+        assert_eq!(
+            store.verify_chain(None).await.unwrap(),
+            CustodyChain {
+                length: 0,
+                unsealed: EVENTS,
+                head: None,
+                broken: None,
+                anchored: None,
+            }
+        );
+
+        store.seal().await.unwrap();
+
+        assert_eq!(
+            link(&db, 1).await,
+            chain_hash(CHAIN_ROOT, record(&db, 1).await.as_bytes())
+        );
+        for event in 2..=EVENTS {
+            let previous = link(&db, event - 1).await;
+            assert_eq!(
+                link(&db, event).await,
+                chain_hash(&previous, record(&db, event).await.as_bytes())
+            );
+        }
+
+        let head = link(&db, EVENTS).await;
+        let chain = store.verify_chain(None).await.unwrap();
+        assert_eq!(
+            chain,
+            CustodyChain {
+                length: EVENTS,
+                unsealed: 0,
+                head: Some(head.clone()),
+                broken: None,
+                anchored: None,
+            }
+        );
+
+        // Sealing again leaves the links as they are.
+        store.seal().await.unwrap();
+        assert_eq!(store.verify_chain(None).await.unwrap(), chain);
+        assert_eq!(link(&db, EVENTS).await, head);
+
+        db.connection()
+            .await
+            .unwrap()
+            .execute(
+                "UPDATE unit SET fetched_at = '2020-07-01T10:00:00.000Z' WHERE id = 7",
+                NO_PARAMS,
+            )
+            .unwrap();
+
+        let chain = store.verify_chain(None).await.unwrap();
+        assert_eq!((chain.length, chain.unsealed), (EVENTS, 1));
+        assert_eq!(chain.head, Some(head.clone()));
+
+        store.seal().await.unwrap();
+
+        let chain = store.verify_chain(None).await.unwrap();
+        assert_eq!((chain.length, chain.unsealed), (EVENTS + 1, 0));
+        assert_eq!(
+            chain.head,
+            Some(chain_hash(&head, record(&db, EVENTS + 1).await.as_bytes()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custody_links_are_immutable() {
+        let (db, _store) = sealed().await;
+        let conn = db.connection().await.unwrap();
+
+        let err = conn
+            .execute(
+                "UPDATE custody_link SET hash = '' WHERE event = 1",
+                NO_PARAMS,
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("custody links are immutable"));
+
+        let err = conn
+            .execute("DELETE FROM custody_link WHERE event = 1", NO_PARAMS)
+            .unwrap_err();
+        assert!(err.to_string().contains("custody links are immutable"));
+    }
+
+    #[tokio::test]
+    async fn test_verify_chain_finds_altered_events() {
+        let (db, store) = sealed().await;
+        let previous = link(&db, 2).await;
+        let found = link(&db, 3).await;
+
+        tamper(
+            &db,
+            r#"UPDATE custody_event SET data = json_object('source', 'forged') WHERE id = 3;"#,
+        )
+        .await;
+
+        let expected = chain_hash(&previous, record(&db, 3).await.as_bytes());
+        assert_eq!(
+            store.verify_chain(None).await.unwrap(),
+            CustodyChain {
+                length: 2,
+                unsealed: 0,
+                head: Some(previous),
+                broken: Some(BrokenLink {
+                    event: 3,
+                    reason: ChainBreak::Altered,
+                    expected: Some(expected),
+                    found: Some(found),
+                }),
+                anchored: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_chain_finds_deleted_events() {
+        let (db, store) = sealed().await;
+        let previous = link(&db, 2).await;
+        let found = link(&db, 3).await;
+
+        tamper(&db, "DELETE FROM custody_event WHERE id = 3;").await;
+
+        assert_eq!(
+            store.verify_chain(None).await.unwrap(),
+            CustodyChain {
+                length: 2,
+                unsealed: 0,
+                head: Some(previous),
+                broken: Some(BrokenLink {
+                    event: 3,
+                    reason: ChainBreak::Deleted,
+                    expected: None,
+                    found: Some(found),
+                }),
+                anchored: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_chain_finds_inserted_events() {
+        let (db, store) = sealed().await;
+        let previous = link(&db, 2).await;
+
+        // An event without a link followed by linked events was slipped into
+        // the log after it was sealed.
+        tamper(&db, "DELETE FROM custody_link WHERE event = 3;").await;
+
+        assert_eq!(
+            store.verify_chain(None).await.unwrap(),
+            CustodyChain {
+                length: 2,
+                unsealed: 1,
+                head: Some(previous),
+                broken: Some(BrokenLink {
+                    event: 3,
+                    reason: ChainBreak::Inserted,
+                    expected: None,
+                    found: None,
+                }),
+                anchored: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_chain_against_an_anchor() {
+        let (db, store) = sealed().await;
+        let head = link(&db, EVENTS).await;
+        let earlier = link(&db, 5).await;
+
+        let chain = store.verify_chain(Some(&head)).await.unwrap();
+        assert_eq!(chain.anchored, Some(true));
+        assert_eq!(chain.head, Some(head.clone()));
+
+        // An anchor taken earlier is part of the chain as well.
+        let chain = store.verify_chain(Some(&earlier)).await.unwrap();
+        assert_eq!(chain.anchored, Some(true));
+
+        let chain = store
+            .verify_chain(Some(&head.to_uppercase()))
+            .await
+            .unwrap();
+        assert_eq!(chain.anchored, Some(true));
+
+        let chain = store.verify_chain(Some(CHAIN_ROOT)).await.unwrap();
+        assert_eq!(chain.anchored, Some(false));
+        assert_eq!(chain.broken, None);
+
+        // A chain that was recomputed after an event was altered is intact,
+        // but it doesn't reach the anchor anymore.
+        tamper(
+            &db,
+            r#"
+            UPDATE custody_event SET data = json_object('source', 'forged') WHERE id = 3;
+            DELETE FROM custody_link WHERE event >= 3;
+            "#,
+        )
+        .await;
+        store.seal().await.unwrap();
+
+        let chain = store.verify_chain(Some(&head)).await.unwrap();
+        assert_eq!(chain.broken, None);
+        assert_eq!(chain.length, EVENTS);
+        assert_ne!(chain.head, Some(head.clone()));
+        assert_eq!(chain.anchored, Some(false));
+
+        let chain = store.verify_chain(Some(&link(&db, 2).await)).await.unwrap();
+        assert_eq!(chain.anchored, Some(true));
+    }
+}
@@ -0,0 +1,141 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use ncube_data::DuplicateCluster;
+use ncube_db::{errors::DatabaseError, http, sqlite, Database};
+use ncube_dedup::Document;
+use rusqlite::{params, NO_PARAMS};
+use tracing::instrument;
+
+pub fn duplicate_store(wrapped_db: Database) -> Box<dyn DuplicateStore + Send + Sync> {
+    match wrapped_db {
+        Database::Sqlite(db) => Box::new(DuplicateStoreSqlite { db }),
+        Database::Http(client) => Box::new(DuplicateStoreHttp { client }),
+    }
+}
+
+#[async_trait]
+pub trait DuplicateStore {
+    /// Cluster all units of a remote workspace and replace the previous
+    /// clusters. Local workspaces cluster their units in a background task.
+    async fn detect(&self) -> Result<(), DatabaseError>;
+    async fn documents(&self) -> Result<Vec<Document>, DatabaseError>;
+    /// Replace the previous clusters. The first unit of every cluster is the
+    /// cluster id.
+    async fn save_clusters(&self, clusters: &[Vec<i32>]) -> Result<(), DatabaseError>;
+    async fn list(&self) -> Result<Vec<DuplicateCluster>, DatabaseError>;
+}
+
+#[derive(Debug)]
+pub struct DuplicateStoreSqlite {
+    db: Box<sqlite::Database>,
+}
+
+#[async_trait]
+impl DuplicateStore for DuplicateStoreSqlite {
+    async fn detect(&self) -> Result<(), DatabaseError> {
+        unreachable!()
+    }
+
+    #[instrument]
+    async fn documents(&self) -> Result<Vec<Document>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/duplicate/documents.sql"))?;
+
+        let documents = stmt
+            .query_map(NO_PARAMS, |row| {
+                Ok(Document {
+                    id: row.get(0)?,
+                    content_hash: row.get(1)?,
+                    text: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<Document>, _>>()?;
+
+        Ok(documents)
+    }
+
+    #[instrument]
+    async fn save_clusters(&self, clusters: &[Vec<i32>]) -> Result<(), DatabaseError> {
+        let now = Utc::now();
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/duplicate/delete-all.sql"))?;
+        let mut stmt2 = conn.prepare_cached(include_str!("../sql/duplicate/create.sql"))?;
+
+        conn.execute_batch("BEGIN;")?;
+        stmt.execute(NO_PARAMS)?;
+        for units in clusters {
+            for unit in units {
+                stmt2.execute(params![unit, units[0], &now.to_rfc3339()])?;
+            }
+        }
+        conn.execute_batch("COMMIT;")?;
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn list(&self) -> Result<Vec<DuplicateCluster>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/duplicate/list.sql"))?;
+
+        let mut clusters: Vec<DuplicateCluster> = vec![];
+        let rows = stmt.query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        // The rows are ordered by cluster.
+        for row in rows {
+            let (id, unit): (i32, i32) = row?;
+
+            match clusters.last_mut() {
+                Some(cluster) if cluster.id == id => cluster.units.push(unit),
+                _ => clusters.push(DuplicateCluster {
+                    id,
+                    units: vec![unit],
+                }),
+            }
+        }
+
+        Ok(clusters)
+    }
+}
+
+#[derive(Debug)]
+pub struct DuplicateStoreHttp {
+    client: Box<http::Database>,
+}
+
+#[async_trait]
+impl DuplicateStore for DuplicateStoreHttp {
+    #[instrument]
+    async fn detect(&self) -> Result<(), DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/duplicates",
+            self.client.workspace.slug
+        ));
+
+        self.client.post::<(), ()>(url, ()).await?;
+
+        Ok(())
+    }
+
+    async fn documents(&self) -> Result<Vec<Document>, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn save_clusters(&self, _clusters: &[Vec<i32>]) -> Result<(), DatabaseError> {
+        unreachable!()
+    }
+
+    #[instrument]
+    async fn list(&self) -> Result<Vec<DuplicateCluster>, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/duplicates",
+            self.client.workspace.slug
+        ));
+
+        let data: Vec<DuplicateCluster> = self.client.get(url).await?.unwrap_or_default();
+
+        Ok(data)
+    }
+}
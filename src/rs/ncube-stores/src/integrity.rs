@@ -0,0 +1,174 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ncube_data::{DownloadFile, IntegrityCheck, IntegrityReport};
+use ncube_db::{errors::DatabaseError, http, sqlite, Database};
+use rusqlite::{params, NO_PARAMS};
+use serde_rusqlite::{from_row, from_rows};
+use tracing::instrument;
+
+pub fn integrity_store(wrapped_db: Database) -> Box<dyn IntegrityStore + Send + Sync> {
+    match wrapped_db {
+        Database::Sqlite(db) => Box::new(IntegrityStoreSqlite { db }),
+        Database::Http(client) => Box::new(IntegrityStoreHttp { client }),
+    }
+}
+
+#[async_trait]
+pub trait IntegrityStore {
+    /// Verify all downloads of a remote workspace.
+    async fn verify(&self) -> Result<(), DatabaseError>;
+    async fn downloads(&self) -> Result<Vec<DownloadFile>, DatabaseError>;
+    /// Record the checks of a verification run and return the id of the new
+    /// report.
+    async fn create(
+        &self,
+        created_at: &DateTime<Utc>,
+        checks: &[IntegrityCheck],
+    ) -> Result<i32, DatabaseError>;
+    async fn list(&self) -> Result<Vec<IntegrityReport>, DatabaseError>;
+    async fn show(&self, id: i32) -> Result<Option<IntegrityReport>, DatabaseError>;
+}
+
+#[derive(Debug)]
+pub struct IntegrityStoreSqlite {
+    db: Box<sqlite::Database>,
+}
+
+#[async_trait]
+impl IntegrityStore for IntegrityStoreSqlite {
+    async fn verify(&self) -> Result<(), DatabaseError> {
+        unreachable!()
+    }
+
+    #[instrument]
+    async fn downloads(&self) -> Result<Vec<DownloadFile>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/integrity/list-downloads.sql"))?;
+
+        let mut files: Vec<DownloadFile> = vec![];
+        for file in from_rows::<DownloadFile>(stmt.query(NO_PARAMS)?) {
+            files.push(file?);
+        }
+
+        Ok(files)
+    }
+
+    #[instrument(skip(checks))]
+    async fn create(
+        &self,
+        created_at: &DateTime<Utc>,
+        checks: &[IntegrityCheck],
+    ) -> Result<i32, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/integrity/create-report.sql"))?;
+        let mut stmt2 = conn.prepare_cached(include_str!("../sql/integrity/create-check.sql"))?;
+
+        conn.execute_batch("BEGIN;")?;
+        stmt.execute(params![&created_at.to_rfc3339()])?;
+        let report = conn.last_insert_rowid() as i32;
+        for check in checks {
+            stmt2.execute(params![
+                report,
+                check.download,
+                check.status.to_string(),
+                check.md5,
+                check.sha256
+            ])?;
+        }
+        conn.execute_batch("COMMIT;")?;
+
+        Ok(report)
+    }
+
+    #[instrument]
+    async fn list(&self) -> Result<Vec<IntegrityReport>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/integrity/list-reports.sql"))?;
+
+        let mut reports: Vec<IntegrityReport> = vec![];
+        for report in from_rows::<IntegrityReport>(stmt.query(NO_PARAMS)?) {
+            reports.push(report?);
+        }
+
+        Ok(reports)
+    }
+
+    #[instrument]
+    async fn show(&self, id: i32) -> Result<Option<IntegrityReport>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/integrity/show-report.sql"))?;
+        let mut stmt2 =
+            conn.prepare_cached(include_str!("../sql/integrity/list-failed-checks.sql"))?;
+
+        let mut rows = stmt.query(params![id])?;
+        let mut report = match rows.next()? {
+            Some(row) => from_row::<IntegrityReport>(row)?,
+            None => return Ok(None),
+        };
+
+        for check in from_rows::<IntegrityCheck>(stmt2.query(params![id])?) {
+            report.checks.push(check?);
+        }
+
+        Ok(Some(report))
+    }
+}
+
+#[derive(Debug)]
+pub struct IntegrityStoreHttp {
+    client: Box<http::Database>,
+}
+
+#[async_trait]
+impl IntegrityStore for IntegrityStoreHttp {
+    #[instrument]
+    async fn verify(&self) -> Result<(), DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/downloads/integrity",
+            self.client.workspace.slug
+        ));
+
+        self.client.post::<(), ()>(url, ()).await?;
+
+        Ok(())
+    }
+
+    async fn downloads(&self) -> Result<Vec<DownloadFile>, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn create(
+        &self,
+        _created_at: &DateTime<Utc>,
+        _checks: &[IntegrityCheck],
+    ) -> Result<i32, DatabaseError> {
+        unreachable!()
+    }
+
+    #[instrument]
+    async fn list(&self) -> Result<Vec<IntegrityReport>, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/downloads/integrity",
+            self.client.workspace.slug
+        ));
+
+        let data: Vec<IntegrityReport> = self.client.get(url).await?.unwrap_or_default();
+
+        Ok(data)
+    }
+
+    #[instrument]
+    async fn show(&self, id: i32) -> Result<Option<IntegrityReport>, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/downloads/integrity/{}",
+            self.client.workspace.slug, id
+        ));
+
+        let data: Option<IntegrityReport> = self.client.get(url).await?;
+
+        Ok(data)
+    }
+}
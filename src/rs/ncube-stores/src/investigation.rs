@@ -1,9 +1,16 @@
 use async_trait::async_trait;
 use chrono::Utc;
-use ncube_data::{Investigation, InvestigationReq, Segment, SegmentUnit, VerifySegmentReq};
-use ncube_db::{errors::DatabaseError, http, sqlite, Database};
-use ncube_search::parse_query;
-use rusqlite::{params, ToSql, NO_PARAMS};
+use ncube_data::{
+    ExportOptions, Investigation, InvestigationReq, Segment, SegmentUnit, UnitStateEvent,
+    UnitStateReq, Verification, VerificationTransition, VerifySegmentReq,
+};
+use ncube_db::{
+    errors::DatabaseError,
+    http::{self, RemoteFile},
+    sqlite, Database,
+};
+use ncube_search::parse_stored_query;
+use rusqlite::{params, Error as RusqliteError, Row, ToSql, NO_PARAMS};
 use serde_rusqlite::{self, columns_from_statement, from_row, from_row_with_columns, from_rows};
 use tracing::instrument;
 
@@ -42,13 +49,74 @@ pub trait InvestigationStore {
         segment: &str,
         state: &str,
     ) -> Result<Vec<SegmentUnit>, DatabaseError>;
+    /// Update the state of a unit and record the transition in the history of
+    /// its verification. The update only happens if the unit is still in the
+    /// state it was transitioned from, `current`. Returns `false` if another
+    /// transition moved the unit in the meantime.
+    #[allow(clippy::too_many_arguments)]
     async fn update_unit_state(
         &self,
         investigation: &str,
         segment: &str,
         unit: i32,
+        current: &serde_json::Value,
         state: &serde_json::Value,
-    ) -> Result<(), DatabaseError>;
+        author: &Option<String>,
+        note: &Option<String>,
+    ) -> Result<bool, DatabaseError>;
+    /// The verification of a unit of a segment of an investigation.
+    async fn unit_verification(
+        &self,
+        investigation: &str,
+        segment: &str,
+        unit: i32,
+    ) -> Result<Option<Verification>, DatabaseError>;
+    async fn verification(
+        &self,
+        investigation: &str,
+        verification: i32,
+    ) -> Result<Option<Verification>, DatabaseError>;
+    /// Move a unit of a remote workspace to its next state. The remote
+    /// workspace validates the transition and returns the new state.
+    async fn transition_unit(
+        &self,
+        investigation: &str,
+        segment: &str,
+        unit: i32,
+        state_req: &UnitStateReq,
+    ) -> Result<serde_json::Value, DatabaseError>;
+    /// The state transitions of a verification, oldest first.
+    async fn history(
+        &self,
+        investigation: &str,
+        verification: i32,
+    ) -> Result<Vec<VerificationTransition>, DatabaseError>;
+    /// Stream the export archive of the units of a segment of an
+    /// investigation of a remote workspace.
+    async fn export(
+        &self,
+        investigation: &str,
+        segment: &str,
+        state: Option<&str>,
+    ) -> Result<RemoteFile, DatabaseError>;
+    /// Stream the unit export of a segment of an investigation of a remote
+    /// workspace.
+    async fn export_units(
+        &self,
+        investigation: &str,
+        segment: &str,
+        state: Option<&str>,
+        options: &ExportOptions,
+    ) -> Result<RemoteFile, DatabaseError>;
+}
+
+// The state is read as JSON, not as the string it is stored as.
+fn verification_from_row(row: &Row) -> Result<Verification, RusqliteError> {
+    Ok(Verification {
+        id: row.get(0)?,
+        unit: row.get(1)?,
+        state: row.get(2)?,
+    })
 }
 
 #[derive(Debug)]
@@ -164,7 +232,7 @@ impl InvestigationStore for InvestigationStoreSqlite {
 
         let tmpl = include_str!("../sql/search/data_list.sql");
         let params: Vec<Box<dyn ToSql>> = vec![];
-        let query = parse_query(&query);
+        let query = parse_stored_query(&query);
         let sql = SearchQuerySqlite::from(&query);
         let (data_sql, params) = sql.to_sql(tmpl, params);
         let mut stmt3 = conn.prepare_cached(&data_sql)?;
@@ -286,8 +354,12 @@ impl InvestigationStore for InvestigationStoreSqlite {
         investigation: &str,
         segment: &str,
         unit: i32,
+        current: &serde_json::Value,
         state: &serde_json::Value,
-    ) -> Result<(), DatabaseError> {
+        author: &Option<String>,
+        note: &Option<String>,
+    ) -> Result<bool, DatabaseError> {
+        let now = Utc::now();
         let conn = self.db.connection().await?;
 
         let mut stmt = conn.prepare_cached(include_str!(
@@ -298,6 +370,8 @@ impl InvestigationStore for InvestigationStoreSqlite {
         ))?;
 
         let mut stmt3 =
+            conn.prepare_cached(include_str!("../sql/investigation/create_transition.sql"))?;
+        let mut stmt4 =
             conn.prepare_cached(include_str!("../sql/investigation/update_state.sql"))?;
 
         let (investigation_id, _initial_state): (i32, String) = stmt
@@ -307,9 +381,128 @@ impl InvestigationStore for InvestigationStoreSqlite {
         let (segment_id, _query): (i32, String) =
             stmt2.query_row(params![&segment], |row| Ok((row.get(0)?, row.get(1)?)))?;
 
-        stmt3.execute(params![&investigation_id, &segment_id, unit, &state])?;
+        // The write lock is taken right away, the state can't change
+        // between recording the transition and updating the state.
+        conn.execute_batch("BEGIN IMMEDIATE;")?;
+
+        stmt3.execute(params![
+            &investigation_id,
+            &segment_id,
+            unit,
+            &state,
+            &author,
+            &note,
+            &now.to_rfc3339(),
+            &current
+        ])?;
+        let updated = stmt4.execute(params![
+            &investigation_id,
+            &segment_id,
+            unit,
+            &state,
+            &now.to_rfc3339(),
+            &current
+        ])?;
 
-        Ok(())
+        if updated == 0 {
+            conn.execute_batch("ROLLBACK;")?;
+            return Ok(false);
+        }
+
+        conn.execute_batch("COMMIT;")?;
+
+        Ok(true)
+    }
+
+    #[instrument]
+    async fn unit_verification(
+        &self,
+        investigation: &str,
+        segment: &str,
+        unit: i32,
+    ) -> Result<Option<Verification>, DatabaseError> {
+        let conn = self.db.connection().await?;
+
+        let mut stmt = conn.prepare_cached(include_str!(
+            "../sql/investigation/show_unit_verification.sql"
+        ))?;
+
+        match stmt.query_row(
+            params![&investigation, &segment, unit],
+            verification_from_row,
+        ) {
+            Ok(verification) => Ok(Some(verification)),
+            Err(RusqliteError::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e)?,
+        }
+    }
+
+    #[instrument]
+    async fn verification(
+        &self,
+        investigation: &str,
+        verification: i32,
+    ) -> Result<Option<Verification>, DatabaseError> {
+        let conn = self.db.connection().await?;
+
+        let mut stmt =
+            conn.prepare_cached(include_str!("../sql/investigation/show_verification.sql"))?;
+
+        match stmt.query_row(params![&investigation, verification], verification_from_row) {
+            Ok(verification) => Ok(Some(verification)),
+            Err(RusqliteError::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e)?,
+        }
+    }
+
+    async fn transition_unit(
+        &self,
+        _investigation: &str,
+        _segment: &str,
+        _unit: i32,
+        _state_req: &UnitStateReq,
+    ) -> Result<serde_json::Value, DatabaseError> {
+        unreachable!()
+    }
+
+    #[instrument]
+    async fn history(
+        &self,
+        investigation: &str,
+        verification: i32,
+    ) -> Result<Vec<VerificationTransition>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt =
+            conn.prepare_cached(include_str!("../sql/investigation/list_history.sql"))?;
+
+        let mut transitions: Vec<VerificationTransition> = vec![];
+        for row in stmt.query_and_then(
+            params![&investigation, verification],
+            from_row::<VerificationTransition>,
+        )? {
+            transitions.push(row?)
+        }
+
+        Ok(transitions)
+    }
+
+    async fn export(
+        &self,
+        _investigation: &str,
+        _segment: &str,
+        _state: Option<&str>,
+    ) -> Result<RemoteFile, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn export_units(
+        &self,
+        _investigation: &str,
+        _segment: &str,
+        _state: Option<&str>,
+        _options: &ExportOptions,
+    ) -> Result<RemoteFile, DatabaseError> {
+        unreachable!()
     }
 }
 
@@ -462,8 +655,12 @@ impl InvestigationStore for InvestigationStoreHttp {
         investigation: &str,
         segment: &str,
         unit: i32,
+        _current: &serde_json::Value,
         state: &serde_json::Value,
-    ) -> Result<(), DatabaseError> {
+        // The remote workspace records the account of the request as author.
+        _author: &Option<String>,
+        _note: &Option<String>,
+    ) -> Result<bool, DatabaseError> {
         let mut url = self.client.url.clone();
         url.set_path(&format!(
             "/api/workspaces/{}/investigations/{}/segments/{}/{}",
@@ -474,6 +671,301 @@ impl InvestigationStore for InvestigationStoreHttp {
 
         self.client.put::<(), String>(url, payload).await?;
 
-        Ok(())
+        Ok(true)
+    }
+
+    async fn unit_verification(
+        &self,
+        _investigation: &str,
+        _segment: &str,
+        _unit: i32,
+    ) -> Result<Option<Verification>, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn verification(
+        &self,
+        _investigation: &str,
+        _verification: i32,
+    ) -> Result<Option<Verification>, DatabaseError> {
+        unreachable!()
+    }
+
+    #[instrument]
+    async fn transition_unit(
+        &self,
+        investigation: &str,
+        segment: &str,
+        unit: i32,
+        state_req: &UnitStateReq,
+    ) -> Result<serde_json::Value, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/investigations/{}/segments/{}/{}",
+            self.client.workspace.slug, investigation, segment, unit
+        ));
+
+        // Clients may send whole persisted states, the remote workspace only
+        // needs the event.
+        let payload = UnitStateReq {
+            event: UnitStateEvent::Name(state_req.event().into()),
+            note: state_req.note.clone(),
+        };
+
+        let state: Option<serde_json::Value> = self.client.put(url, payload).await?;
+
+        state.ok_or_else(|| {
+            DatabaseError::NotFound(format!("{}/{}/{}", investigation, segment, unit))
+        })
+    }
+
+    #[instrument]
+    async fn history(
+        &self,
+        investigation: &str,
+        verification: i32,
+    ) -> Result<Vec<VerificationTransition>, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/investigations/{}/verifications/{}/history",
+            self.client.workspace.slug, investigation, verification
+        ));
+
+        let transitions: Vec<VerificationTransition> =
+            self.client.get(url).await?.unwrap_or_default();
+
+        Ok(transitions)
+    }
+
+    #[instrument]
+    async fn export(
+        &self,
+        investigation: &str,
+        segment: &str,
+        state: Option<&str>,
+    ) -> Result<RemoteFile, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/investigations/{}/segments/{}/export",
+            self.client.workspace.slug, investigation, segment
+        ));
+
+        if let Some(state) = state {
+            url.query_pairs_mut().clear().append_pair("state", state);
+        }
+
+        self.client.get_file(url, None).await
+    }
+
+    #[instrument]
+    async fn export_units(
+        &self,
+        investigation: &str,
+        segment: &str,
+        state: Option<&str>,
+        options: &ExportOptions,
+    ) -> Result<RemoteFile, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/investigations/{}/segments/{}/units/export",
+            self.client.workspace.slug, investigation, segment
+        ));
+        url.query_pairs_mut()
+            .clear()
+            .append_pair("format", &options.format.to_string())
+            .append_pair("columns", &options.column_list());
+
+        if let Some(state) = state {
+            url.query_pairs_mut().append_pair("state", state);
+        }
+
+        self.client.get_file(url, None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{segment_store, tests::database};
+
+    fn state(value: &str, event: &str) -> serde_json::Value {
+        json!({"value": value, "event": {"type": event}})
+    }
+
+    async fn history_rows(db: &sqlite::Database, verification: i32) -> Vec<(String, String)> {
+        let conn = db.connection().await.unwrap();
+        let mut stmt = conn
+            .prepare("SELECT to_state, event FROM verification_history WHERE verification = ?1 ORDER BY id")
+            .unwrap();
+        let rows = stmt
+            .query_map(params![verification], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<(String, String)>>>()
+            .unwrap();
+        rows
+    }
+
+    #[tokio::test]
+    async fn test_history_records_transitions_in_order() {
+        let db = database().await;
+        let store = InvestigationStoreSqlite { db: db.clone() };
+        let before = Utc::now();
+
+        let transitions = [
+            (
+                "incoming_data",
+                state("desk_research", "TO_DESK_RESEARCH"),
+                Some("reviewer@example.org".to_string()),
+                Some("The location needs a second source.".to_string()),
+            ),
+            (
+                "desk_research",
+                state("sign_off", "TO_SIGN_OFF"),
+                Some("lead@example.org".to_string()),
+                None,
+            ),
+            ("sign_off", state("done", "TO_DONE"), None, None),
+        ];
+        for (current, next, author, note) in &transitions {
+            let updated = store
+                .update_unit_state(
+                    "aleppo",
+                    "aleppo",
+                    1,
+                    &json!({ "value": current }),
+                    next,
+                    author,
+                    note,
+                )
+                .await
+                .unwrap();
+            assert!(updated);
+        }
+
+        // A transition from a state the unit already left isn't recorded.
+        let updated = store
+            .update_unit_state(
+                "aleppo",
+                "aleppo",
+                1,
+                &json!({"value": "incoming_data"}),
+                &state("discarded", "TO_DISCARDED"),
+                &None,
+                &None,
+            )
+            .await
+            .unwrap();
+        assert!(!updated);
+
+        let history = store.history("aleppo", 1).await.unwrap();
+        let after = Utc::now();
+
+        let steps: Vec<_> = history
+            .iter()
+            .map(|t| {
+                (
+                    t.verification,
+                    t.from_state.as_deref(),
+                    t.to_state.as_str(),
+                    t.event.as_str(),
+                    t.author.as_deref(),
+                    t.note.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                (1, None, "incoming_data", "xstate.init", None, None),
+                (
+                    1,
+                    Some("incoming_data"),
+                    "desk_research",
+                    "TO_DESK_RESEARCH",
+                    Some("reviewer@example.org"),
+                    Some("The location needs a second source."),
+                ),
+                (
+                    1,
+                    Some("desk_research"),
+                    "sign_off",
+                    "TO_SIGN_OFF",
+                    Some("lead@example.org"),
+                    None,
+                ),
+                (1, Some("sign_off"), "done", "TO_DONE", None, None),
+            ]
+        );
+
+        assert!(history.windows(2).all(|w| w[0].id < w[1].id));
+        assert!(history
+            .windows(2)
+            .all(|w| w[0].created_at <= w[1].created_at));
+        for transition in &history[1..] {
+            assert!(transition.created_at >= before && transition.created_at <= after);
+        }
+
+        // The history of other verifications is separate.
+        let history = store.history("aleppo", 2).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].to_state, "desk_research");
+    }
+
+    #[tokio::test]
+    async fn test_history_is_kept_when_the_segment_is_removed() {
+        let db = database().await;
+        let store = InvestigationStoreSqlite { db: db.clone() };
+
+        store
+            .update_unit_state(
+                "aleppo",
+                "aleppo",
+                2,
+                &json!({"value": "desk_research"}),
+                &state("sign_off", "TO_SIGN_OFF"),
+                &Some("lead@example.org".into()),
+                &None,
+            )
+            .await
+            .unwrap();
+
+        segment_store(Database::Sqlite(db.clone()))
+            .delete("aleppo")
+            .await
+            .unwrap();
+
+        {
+            let conn = db.connection().await.unwrap();
+            let verifications: i32 = conn
+                .query_row("SELECT count(*) FROM verification", NO_PARAMS, |row| {
+                    row.get(0)
+                })
+                .unwrap();
+            assert_eq!(verifications, 0);
+        }
+
+        assert_eq!(
+            history_rows(&db, 2).await,
+            vec![
+                ("desk_research".to_string(), "xstate.init".to_string()),
+                ("sign_off".to_string(), "TO_SIGN_OFF".to_string()),
+            ]
+        );
+        assert_eq!(history_rows(&db, 1).await.len(), 1);
+        assert_eq!(history_rows(&db, 3).await.len(), 1);
+
+        // The history is append only.
+        let conn = db.connection().await.unwrap();
+        assert!(conn
+            .execute("DELETE FROM verification_history", NO_PARAMS)
+            .is_err());
+        assert!(conn
+            .execute(
+                "UPDATE verification_history SET author = 'someone@example.org'",
+                NO_PARAMS
+            )
+            .is_err());
     }
 }
@@ -1,10 +1,20 @@
-use ncube_search::{Limit, SearchQuery};
-use rusqlite::ToSql;
+// Stores leave the methods that only one database implements as
+// `unreachable!()`. `async_trait` binds the body of every method to a
+// variable, which clippy reports as a diverging sub-expression.
+#![allow(clippy::diverging_sub_expression)]
+
+use ncube_data::{Cursor, CursorKey, PageRequest, SortOrder};
+use ncube_search::{DateRange, Duplicates, Limit, SearchQuery};
+use rusqlite::{types::Value, Row, ToSql};
 
 mod account;
 mod annotation;
 mod config;
+mod custody;
+mod duplicate;
+mod integrity;
 mod investigation;
+mod media;
 mod methodology;
 mod process;
 mod search;
@@ -17,7 +27,11 @@ mod workspace;
 pub use self::account::*;
 pub use self::annotation::*;
 pub use self::config::*;
+pub use self::custody::*;
+pub use self::duplicate::*;
+pub use self::integrity::*;
 pub use self::investigation::*;
+pub use self::media::*;
 pub use self::methodology::*;
 pub use self::process::*;
 pub use self::search::*;
@@ -27,6 +41,11 @@ pub use self::stat::*;
 pub use self::unit::*;
 pub use self::workspace::*;
 
+/// Translate a search query into SQL conditions for the unit search
+/// templates. Every template is expected to select `FROM unit_fts` and to join
+/// the `unit` table as `u`. Text conjuncts on the top level of the query are
+/// combined into a single `unit_fts MATCH` to allow ranking by `bm25`, all
+/// other parts of the query are translated into sub-selects.
 pub(crate) struct SearchQuerySqlite {
     query: SearchQuery,
 }
@@ -41,32 +60,152 @@ impl SearchQuerySqlite {
     fn populate(&self, params: Vec<Box<dyn ToSql>>) -> (Vec<String>, Vec<Box<dyn ToSql>>) {
         let mut conditionals: Vec<String> = vec![];
         let mut params = params;
-        let mut param_index = params.len() + 1;
 
-        if let Some(terms) = &self.query.terms {
-            conditionals.push(format!("unit_fts MATCH ?{}", param_index));
-            params.push(Box::new(terms.clone()));
-            param_index += 1;
-        }
+        let conjuncts = match &self.query {
+            SearchQuery::And(exprs) => exprs.clone(),
+            expr => vec![expr.clone()],
+        };
 
-        for limit in &self.query.limits {
-            match limit {
-                Limit::Tag(term) => {
-                    conditionals.push(format!("qt.label = ?{}", param_index));
-                    params.push(Box::new(term.replacen("\"", "", 2).clone()));
-                }
-                Limit::Source(term) => {
-                    conditionals.push(format!("u.source = ?{}", param_index));
-                    params.push(Box::new(term.replacen("\"", "", 2).clone()));
-                }
-            };
+        let (text, rest): (Vec<SearchQuery>, Vec<SearchQuery>) =
+            conjuncts.into_iter().partition(SearchQuery::is_text);
+
+        if !text.is_empty() {
+            let fts = SearchQuery::And(text).to_fts().unwrap();
+            params.push(Box::new(fts));
+            conditionals.push(format!("unit_fts MATCH ?{}", params.len()));
+        }
 
-            param_index += 1;
+        for expr in &rest {
+            conditionals.push(Self::condition(expr, &mut params));
         }
 
         (conditionals, params)
     }
 
+    fn condition(expr: &SearchQuery, params: &mut Vec<Box<dyn ToSql>>) -> String {
+        if let Some(fts) = expr.to_fts() {
+            params.push(Box::new(fts));
+            return format!(
+                "u.id IN (SELECT rowid FROM unit_fts WHERE unit_fts MATCH ?{})",
+                params.len()
+            );
+        }
+
+        match expr {
+            SearchQuery::Limit(limit) => Self::limit(limit, params),
+            SearchQuery::Not(expr) => format!("NOT ({})", Self::condition(expr, params)),
+            SearchQuery::And(exprs) => Self::join(exprs, " AND ", params),
+            SearchQuery::Or(exprs) => Self::join(exprs, " OR ", params),
+            SearchQuery::Term(_) => unreachable!(),
+        }
+    }
+
+    fn limit(limit: &Limit, params: &mut Vec<Box<dyn ToSql>>) -> String {
+        match limit {
+            Limit::Tag(_) => {
+                params.push(Box::new(limit.value()));
+                format!(
+                    "u.id IN (SELECT tu.unit FROM tagged_unit tu \
+                              JOIN query_tag qt ON qt.id = tu.query_tag \
+                              WHERE qt.label = ?{})",
+                    params.len()
+                )
+            }
+            Limit::Source(_) => {
+                params.push(Box::new(limit.value()));
+                format!("u.source = ?{}", params.len())
+            }
+            Limit::Language(_) => {
+                params.push(Box::new(limit.value()));
+                format!("lower(u.language) = ?{}", params.len())
+            }
+            Limit::State(_) => {
+                params.push(Box::new(limit.value()));
+                format!(
+                    "u.id IN (SELECT v.unit FROM verification v \
+                              WHERE json_extract(v.state, '$.value') = ?{})",
+                    params.len()
+                )
+            }
+            Limit::Investigation(_) => {
+                params.push(Box::new(limit.value()));
+                format!(
+                    "u.id IN (SELECT v.unit FROM verification v \
+                              JOIN investigation i ON i.id = v.investigation \
+                              WHERE i.slug = ?{})",
+                    params.len()
+                )
+            }
+            // Annotation values are stored as JSON. Strings are compared
+            // without their quotes, all other values as they are written,
+            // e.g. `true` or `42`.
+            Limit::Annotation(key, _) => {
+                params.push(Box::new(key.clone()));
+                params.push(Box::new(limit.value()));
+                format!(
+                    "u.id IN (SELECT v.unit FROM annotation a \
+                              JOIN verification v ON v.id = a.verification \
+                              WHERE a.key = ?{} \
+                                AND (a.value = ?{} \
+                                     OR (json_type(a.value) = 'text' \
+                                         AND json_extract(a.value, '$') = ?{} COLLATE NOCASE)))",
+                    params.len() - 1,
+                    params.len(),
+                    params.len()
+                )
+            }
+            Limit::Duplicate(Duplicates::Any) => "u.id IN (SELECT unit FROM duplicate)".into(),
+            Limit::Duplicate(Duplicates::Copy) => {
+                "u.id IN (SELECT unit FROM duplicate WHERE unit <> cluster)".into()
+            }
+            Limit::Duplicate(Duplicates::Of(id)) => {
+                params.push(Box::new(*id));
+                format!(
+                    "u.id IN (SELECT d.unit FROM duplicate d \
+                              JOIN duplicate o ON o.cluster = d.cluster \
+                              WHERE o.unit = ?{})",
+                    params.len()
+                )
+            }
+            Limit::Created(range) => Self::date_range("u.created_at", range, params),
+            Limit::Fetched(range) => Self::date_range("u.fetched_at", range, params),
+            // Title and author limits are text queries.
+            Limit::Title(_) | Limit::Author(_) => unreachable!(),
+        }
+    }
+
+    fn date_range(column: &str, range: &DateRange, params: &mut Vec<Box<dyn ToSql>>) -> String {
+        let (op, date) = match range {
+            DateRange::Between(from, to) => {
+                params.push(Box::new(*from));
+                params.push(Box::new(*to));
+                return format!(
+                    "date({}) BETWEEN ?{} AND ?{}",
+                    column,
+                    params.len() - 1,
+                    params.len()
+                );
+            }
+            DateRange::On(date) => ("=", date),
+            DateRange::After(date) => (">", date),
+            DateRange::From(date) => (">=", date),
+            DateRange::Before(date) => ("<", date),
+            DateRange::Until(date) => ("<=", date),
+        };
+
+        params.push(Box::new(*date));
+        format!("date({}) {} ?{}", column, op, params.len())
+    }
+
+    fn join(exprs: &[SearchQuery], op: &str, params: &mut Vec<Box<dyn ToSql>>) -> String {
+        let conditionals: Vec<String> = exprs
+            .iter()
+            .map(|expr| Self::condition(expr, params))
+            .collect();
+
+        format!("({})", conditionals.join(op))
+    }
+
     pub(crate) fn to_sql(
         &self,
         tmpl: &str,
@@ -77,3 +216,421 @@ impl SearchQuerySqlite {
         (tmpl.replacen("{}", &conditionals.join(" AND "), 2), params)
     }
 }
+
+/// Order units in the unit templates. Templates select the sort key as `{key}`
+/// and place the `{after}` condition and the `{order}` clause. The first two
+/// parameters of the template are the sort key and the unit id of the cursor.
+/// Ties are broken by the unit id, which makes the order stable enough to
+/// paginate by cursor.
+#[derive(Debug)]
+pub(crate) struct UnitOrderSqlite {
+    sort: SortOrder,
+    key: &'static str,
+    direction: &'static str,
+}
+
+impl UnitOrderSqlite {
+    /// `rank` is the expression used to order by relevance.
+    pub(crate) fn new(sort: &SortOrder, rank: &'static str) -> Self {
+        let (key, direction) = match sort {
+            SortOrder::Relevance => (rank, "ASC"),
+            SortOrder::CreatedAtAsc => ("coalesce(u.created_at, '')", "ASC"),
+            SortOrder::CreatedAtDesc => ("coalesce(u.created_at, '')", "DESC"),
+            SortOrder::FetchedAtAsc => ("u.fetched_at", "ASC"),
+            SortOrder::FetchedAtDesc => ("u.fetched_at", "DESC"),
+            SortOrder::Author => ("coalesce(u.author, '')", "ASC"),
+            SortOrder::Source => ("u.source", "ASC"),
+        };
+
+        Self {
+            sort: *sort,
+            key,
+            direction,
+        }
+    }
+
+    pub(crate) fn params(&self, page: &PageRequest) -> Vec<Box<dyn ToSql>> {
+        let key = match page.after.as_ref().map(|cursor| &cursor.key) {
+            Some(CursorKey::Rank(rank)) => Value::Real(*rank),
+            Some(CursorKey::Text(text)) => Value::Text(text.clone()),
+            None => Value::Null,
+        };
+
+        vec![
+            Box::new(key),
+            Box::new(page.after.as_ref().map(|cursor| cursor.id)),
+        ]
+    }
+
+    /// Replace `{key}`, `{after}` and `{order}` in `tmpl`. Only a page with
+    /// a cursor is restricted to the units after it. The row value compares
+    /// the sort key and the unit id at once, the redundant bound on the key
+    /// lets SQLite seek the sort index to the cursor instead of scanning it.
+    pub(crate) fn to_sql(&self, tmpl: &str, page: &PageRequest) -> String {
+        let op = if self.direction == "ASC" { ">" } else { "<" };
+        let after = match page.after {
+            Some(_) => format!(
+                "{key} {op}= ?1 AND ({key}, u.id) {op} (?1, ?2)",
+                key = self.key,
+                op = op
+            ),
+            None => "1".to_string(),
+        };
+        let order = format!(
+            "{key} {direction}, u.id {direction}",
+            key = self.key,
+            direction = self.direction
+        );
+
+        tmpl.replace("{key}", self.key)
+            .replace("{after}", &after)
+            .replace("{order}", &order)
+    }
+
+    /// The cursor to the unit of `row`, the sort key is selected as
+    /// `sort_key`.
+    pub(crate) fn cursor(&self, row: &Row, id: i32) -> rusqlite::Result<Cursor> {
+        match self.sort {
+            SortOrder::Relevance => Ok(Cursor::rank(row.get("sort_key")?, id)),
+            _ => Ok(Cursor::text(&row.get::<_, String>("sort_key")?, id)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use ncube_data::{Cursor, PageRequest, SortOrder};
+    use ncube_db::sqlite;
+    use ncube_search::parse_query;
+    use rusqlite::Connection;
+
+    use super::*;
+
+    fn populate(conn: &mut Connection) {
+        conn.execute_batch(include_str!("../sql/test/sugarcube.sql"))
+            .unwrap();
+        ncube_db::migrations::migrate_workspace(conn).unwrap();
+        conn.execute_batch(include_str!("../sql/test/units.sql"))
+            .unwrap();
+    }
+
+    /// A migrated workspace database with the units of `units.sql`. Other
+    /// store tests use it as their fixture too.
+    pub(crate) fn workspace() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        populate(&mut conn);
+        conn
+    }
+
+    /// The same fixture as a database for the stores. An in-memory database
+    /// isn't shared between connections, the pool holds a single one. Tests
+    /// have to drop their connection before they call a store.
+    pub(crate) async fn database() -> Box<sqlite::Database> {
+        let db = sqlite::Database::from_str("sqlite://:memory:", 1).unwrap();
+        populate(&mut db.connection().await.unwrap());
+        Box::new(db)
+    }
+
+    fn search(conn: &Connection, query: &str) -> Vec<i32> {
+        let query = parse_query(query).unwrap();
+        let sql = SearchQuerySqlite::from(&query);
+        let (data_sql, params) = sql.to_sql(include_str!("../sql/search/data_list.sql"), vec![]);
+
+        let mut stmt = conn.prepare(&data_sql).unwrap();
+        let mut units = stmt
+            .query_map(params, |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<i32>>>()
+            .unwrap();
+        units.sort_unstable();
+        units
+    }
+
+    /// Walk a list page by page and return the units of every page. Cursors
+    /// are passed through their string form, like a client would.
+    fn pages(
+        conn: &Connection,
+        tmpl: &str,
+        rank: &'static str,
+        query: Option<&str>,
+        sort: &SortOrder,
+        size: i32,
+    ) -> Vec<Vec<i32>> {
+        let order = UnitOrderSqlite::new(sort, rank);
+        let query = query.map(|query| parse_query(query).unwrap());
+        let mut after: Option<Cursor> = None;
+        let mut pages = vec![];
+
+        loop {
+            let page = PageRequest {
+                after: after.take(),
+                page: 0,
+                size,
+            };
+            let tmpl = order.to_sql(tmpl, &page);
+            let mut params = order.params(&page);
+            params.push(Box::new(page.size));
+            params.push(Box::new(page.offset()));
+
+            let (data_sql, params) = match &query {
+                Some(query) => SearchQuerySqlite::from(query).to_sql(&tmpl, params),
+                None => (tmpl, params),
+            };
+
+            let mut stmt = conn.prepare(&data_sql).unwrap();
+            let mut rows = stmt.query(params).unwrap();
+            let mut units = vec![];
+            let mut last = None;
+
+            while let Some(row) = rows.next().unwrap() {
+                let id: i32 = row.get("id").unwrap();
+                last = Some(order.cursor(row, id).unwrap());
+                units.push(id);
+            }
+
+            let next = page.next(units.len(), last);
+            if !units.is_empty() {
+                pages.push(units);
+            }
+
+            match next {
+                Some(cursor) => {
+                    let encoded = cursor.to_string();
+                    let decoded = encoded.parse::<Cursor>().unwrap();
+                    assert_eq!(decoded, cursor);
+                    assert!(decoded.matches(sort));
+                    after = Some(decoded);
+                }
+                None => return pages,
+            }
+        }
+    }
+
+    fn list(conn: &Connection, sort: &SortOrder, size: i32) -> Vec<Vec<i32>> {
+        pages(
+            conn,
+            include_str!("../sql/unit/paginate.sql"),
+            "0.0",
+            None,
+            sort,
+            size,
+        )
+    }
+
+    fn search_pages(conn: &Connection, query: &str, sort: &SortOrder, size: i32) -> Vec<Vec<i32>> {
+        pages(
+            conn,
+            include_str!("../sql/search/data.sql"),
+            "bm25(unit_fts)",
+            Some(query),
+            sort,
+            size,
+        )
+    }
+
+    #[test]
+    fn test_search_terms_and_limits() {
+        let conn = workspace();
+
+        assert_eq!(search(&conn, "aleppo"), vec![1, 2, 5]);
+        assert_eq!(search(&conn, "airstrike OR weather"), vec![2, 4, 7]);
+        assert_eq!(search(&conn, "aleppo source:youtube_video"), vec![1, 5]);
+        assert_eq!(search(&conn, "author:alice"), vec![1, 3]);
+        assert_eq!(search(&conn, "tag:civilian"), vec![2, 4]);
+        assert_eq!(search(&conn, "lang:ar"), vec![2, 4]);
+    }
+
+    #[test]
+    fn test_search_nested_boolean_expressions() {
+        let conn = workspace();
+
+        assert_eq!(search(&conn, "protest -source:youtube_video"), vec![3, 6]);
+        assert_eq!(search(&conn, "NOT (aleppo OR idlib)"), vec![7]);
+        assert_eq!(search(&conn, "-(-tag:civilian)"), vec![2, 4]);
+        assert_eq!(
+            search(&conn, "(tag:civilian OR dup:copy) source:youtube_video"),
+            vec![4, 5]
+        );
+        assert_eq!(
+            search(&conn, "protest NOT (dup:copy OR lang:ar)"),
+            vec![1, 3]
+        );
+        assert_eq!(
+            search(&conn, "(aleppo -author:alice) OR (idlib tag:civilian)"),
+            vec![2, 4, 5]
+        );
+    }
+
+    #[test]
+    fn test_search_date_ranges() {
+        let conn = workspace();
+
+        assert_eq!(search(&conn, "created:2020-01-15"), vec![1, 5]);
+        assert_eq!(search(&conn, "created:2020-03-01..2020-03-31"), vec![2, 3]);
+        assert_eq!(search(&conn, "created:..2020-03-01"), vec![1, 2, 5]);
+        assert_eq!(search(&conn, "created:2020-04-01.."), vec![4, 6, 7]);
+        assert_eq!(search(&conn, "created:>2020-04-01"), vec![4, 7]);
+        assert_eq!(search(&conn, "created:>=2020-04-01"), vec![4, 6, 7]);
+        assert_eq!(search(&conn, "created:<2020-03-01"), vec![1, 5]);
+        assert_eq!(search(&conn, "created:<=2020-03-01"), vec![1, 2, 5]);
+        assert_eq!(search(&conn, "fetched:2020-01-16"), vec![1, 5]);
+        assert_eq!(
+            search(&conn, "protest NOT fetched:2020-04-01..2020-04-02"),
+            vec![1, 5]
+        );
+    }
+
+    #[test]
+    fn test_search_verifications_and_annotations() {
+        let conn = workspace();
+
+        assert_eq!(search(&conn, "investigation:aleppo"), vec![1, 2, 5]);
+        assert_eq!(search(&conn, "state:desk_research"), vec![2, 5]);
+        assert_eq!(
+            search(&conn, "investigation:aleppo -state:desk_research"),
+            vec![1]
+        );
+        assert_eq!(search(&conn, "annotation:location=aleppo"), vec![2]);
+        assert_eq!(
+            search(&conn, "annotation:location=\"aleppo city\""),
+            vec![5]
+        );
+        assert_eq!(search(&conn, "annotation:has-teargas=true"), vec![2]);
+        assert_eq!(search(&conn, "annotation:has-teargas=false"), vec![5]);
+        assert_eq!(search(&conn, "annotation:has-teargas=\"true\""), vec![2]);
+        assert_eq!(search(&conn, "annotation:weather=true"), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_search_duplicates() {
+        let conn = workspace();
+
+        assert_eq!(search(&conn, "dup:any"), vec![1, 3, 5, 6]);
+        assert_eq!(search(&conn, "dup:copy"), vec![5, 6]);
+        assert_eq!(search(&conn, "dup:5"), vec![1, 5]);
+        assert_eq!(search(&conn, "dup:3"), vec![3, 6]);
+        assert_eq!(search(&conn, "dup:7"), Vec::<i32>::new());
+        assert_eq!(search(&conn, "aleppo -dup:copy"), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_paginate_ties_by_unit_id() {
+        let conn = workspace();
+
+        // Units without an author sort first. The authors of units 1 and 3,
+        // and of units 2, 6 and 7 are the same.
+        assert_eq!(
+            list(&conn, &SortOrder::Author, 2),
+            vec![vec![4, 1], vec![3, 2], vec![6, 7], vec![5]]
+        );
+        assert_eq!(
+            list(&conn, &SortOrder::Source, 2),
+            vec![vec![6, 7], vec![2, 3], vec![1, 4], vec![5]]
+        );
+        // Descending lists break ties in descending order as well.
+        assert_eq!(
+            list(&conn, &SortOrder::CreatedAtDesc, 3),
+            vec![vec![7, 4, 6], vec![3, 2, 5], vec![1]]
+        );
+        assert_eq!(
+            list(&conn, &SortOrder::CreatedAtAsc, 3),
+            vec![vec![1, 5, 2], vec![3, 6, 4], vec![7]]
+        );
+        // Units that are not part of a search all have the same rank.
+        assert_eq!(
+            list(&conn, &SortOrder::Relevance, 3),
+            vec![vec![1, 2, 3], vec![4, 5, 6], vec![7]]
+        );
+    }
+
+    #[test]
+    fn test_paginate_search_results() {
+        let conn = workspace();
+
+        // All four units match equally well.
+        assert_eq!(
+            search_pages(&conn, "protest", &SortOrder::Relevance, 3),
+            vec![vec![1, 3, 5], vec![6]]
+        );
+        assert_eq!(
+            search_pages(&conn, "protest", &SortOrder::FetchedAtDesc, 2),
+            vec![vec![6, 3], vec![5, 1]]
+        );
+        assert_eq!(
+            search_pages(&conn, "aleppo OR idlib", &SortOrder::Author, 2),
+            vec![vec![4, 1], vec![3, 2], vec![6, 5]]
+        );
+    }
+
+    #[test]
+    fn test_paginate_visits_every_unit_once() {
+        let conn = workspace();
+        let sorts = [
+            SortOrder::Relevance,
+            SortOrder::CreatedAtAsc,
+            SortOrder::CreatedAtDesc,
+            SortOrder::FetchedAtAsc,
+            SortOrder::FetchedAtDesc,
+            SortOrder::Author,
+            SortOrder::Source,
+        ];
+
+        for sort in sorts.iter() {
+            for size in 1..=8 {
+                let mut units: Vec<i32> = list(&conn, sort, size).into_iter().flatten().collect();
+                assert_eq!(units.len(), 7, "{:?} in pages of {}", sort, size);
+                units.sort_unstable();
+                assert_eq!(units, vec![1, 2, 3, 4, 5, 6, 7]);
+
+                let mut units: Vec<i32> = search_pages(&conn, "protest OR airstrike", sort, size)
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                assert_eq!(units.len(), 6, "{:?} in pages of {}", sort, size);
+                units.sort_unstable();
+                assert_eq!(units, vec![1, 2, 3, 4, 5, 6]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_paginate_seeks_the_sort_index() {
+        let conn = workspace();
+        let sorts = [
+            (SortOrder::CreatedAtAsc, "unit_created_at_idx"),
+            (SortOrder::CreatedAtDesc, "unit_created_at_idx"),
+            (SortOrder::FetchedAtAsc, "unit_fetched_at_idx"),
+            (SortOrder::FetchedAtDesc, "unit_fetched_at_idx"),
+            (SortOrder::Author, "unit_author_idx"),
+            (SortOrder::Source, "unit_source_idx"),
+        ];
+
+        for (sort, index) in sorts.iter() {
+            let order = UnitOrderSqlite::new(sort, "0.0");
+            let page = PageRequest {
+                after: Some(Cursor::text("2020-03-01T10:00:00.000Z", 2)),
+                page: 0,
+                size: 2,
+            };
+            let sql = order.to_sql(include_str!("../sql/unit/paginate.sql"), &page);
+            let mut params = order.params(&page);
+            params.push(Box::new(page.size));
+            params.push(Box::new(page.offset()));
+
+            let mut stmt = conn
+                .prepare(&format!("EXPLAIN QUERY PLAN {}", sql))
+                .unwrap();
+            let plan = stmt
+                .query_map(params, |row| row.get::<_, String>(3))
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<String>>>()
+                .unwrap();
+
+            // The plan seeks the index to the cursor and reads the units in
+            // its order, without sorting them first.
+            assert_eq!(plan.len(), 1, "{:?}: {:?}", sort, plan);
+            assert!(plan[0].starts_with("SEARCH"), "{:?}: {:?}", sort, plan);
+            assert!(plan[0].contains(index), "{:?}: {:?}", sort, plan);
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use ncube_data::{Download, FileMetadata, MediaFile, MediaMetadata, SimilarMedia};
+use ncube_db::{
+    errors::DatabaseError,
+    http::{self, RemoteFile},
+    sqlite, Database,
+};
+use ncube_media::distance;
+use rusqlite::{params, NO_PARAMS};
+use serde_rusqlite::{from_row, from_rows};
+use std::collections::HashMap;
+use tracing::instrument;
+
+pub fn media_store(wrapped_db: Database) -> Box<dyn MediaStore + Send + Sync> {
+    match wrapped_db {
+        Database::Sqlite(db) => Box::new(MediaStoreSqlite { db }),
+        Database::Http(client) => Box::new(MediaStoreHttp { client }),
+    }
+}
+
+#[async_trait]
+pub trait MediaStore {
+    /// Hash all downloads of a remote workspace.
+    async fn hash(&self) -> Result<(), DatabaseError>;
+    async fn unhashed(&self) -> Result<Vec<MediaFile>, DatabaseError>;
+    async fn save_hashes(&self, download: i32, hashes: &[u64]) -> Result<(), DatabaseError>;
+    /// Find downloads of other units whose perceptual hashes differ in at most
+    /// `max_distance` bits from a hash of a download of `unit`. The closest
+    /// matches come first.
+    async fn similar(
+        &self,
+        unit: i32,
+        max_distance: u32,
+    ) -> Result<Vec<SimilarMedia>, DatabaseError>;
+    /// Generate the thumbnails of all downloads of a remote workspace.
+    async fn thumbnails(&self) -> Result<(), DatabaseError>;
+    async fn files(&self) -> Result<Vec<MediaFile>, DatabaseError>;
+    /// Find the downloaded image or video at `location`.
+    async fn file(&self, location: &str) -> Result<Option<MediaFile>, DatabaseError>;
+    /// The SHA256 digest of the download at `location`.
+    async fn digest(&self, location: &str) -> Result<Option<String>, DatabaseError>;
+    async fn metadata(&self, download: i32) -> Result<Option<MediaMetadata>, DatabaseError>;
+    async fn save_metadata(
+        &self,
+        download: i32,
+        metadata: &MediaMetadata,
+    ) -> Result<(), DatabaseError>;
+    /// Stream a download of a remote workspace. `range` is the value of a HTTP
+    /// `Range` header.
+    async fn download(
+        &self,
+        file_path: &str,
+        range: Option<String>,
+    ) -> Result<RemoteFile, DatabaseError>;
+    async fn download_metadata(&self, file_path: &str) -> Result<FileMetadata, DatabaseError>;
+    /// Stream the thumbnail or contact sheet of a download of a remote
+    /// workspace.
+    async fn thumbnail(
+        &self,
+        file_path: &str,
+        sheet: bool,
+        range: Option<String>,
+    ) -> Result<RemoteFile, DatabaseError>;
+}
+
+#[derive(Debug)]
+pub struct MediaStoreSqlite {
+    db: Box<sqlite::Database>,
+}
+
+#[async_trait]
+impl MediaStore for MediaStoreSqlite {
+    async fn hash(&self) -> Result<(), DatabaseError> {
+        unreachable!()
+    }
+
+    #[instrument]
+    async fn unhashed(&self) -> Result<Vec<MediaFile>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/media/list-unhashed.sql"))?;
+
+        let mut files: Vec<MediaFile> = vec![];
+        for file in from_rows::<MediaFile>(stmt.query(NO_PARAMS)?) {
+            files.push(file?);
+        }
+
+        Ok(files)
+    }
+
+    #[instrument]
+    async fn save_hashes(&self, download: i32, hashes: &[u64]) -> Result<(), DatabaseError> {
+        let now = Utc::now();
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/media/create-hash.sql"))?;
+
+        conn.execute_batch("BEGIN;")?;
+        for (frame, hash) in hashes.iter().enumerate() {
+            stmt.execute(params![
+                download,
+                frame as i32,
+                // SQLite has no unsigned integers, the hash is stored bit for bit.
+                *hash as i64,
+                &now.to_rfc3339()
+            ])?;
+        }
+        conn.execute_batch("COMMIT;")?;
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn similar(
+        &self,
+        unit: i32,
+        max_distance: u32,
+    ) -> Result<Vec<SimilarMedia>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/media/list-unit-hashes.sql"))?;
+        let mut stmt2 = conn.prepare_cached(include_str!("../sql/media/list-other-hashes.sql"))?;
+
+        let mut unit_hashes: Vec<(String, u64)> = vec![];
+        let mut rows = stmt.query(params![unit])?;
+        while let Some(row) = rows.next()? {
+            let hash: i64 = row.get(1)?;
+            unit_hashes.push((row.get(0)?, hash as u64));
+        }
+
+        if unit_hashes.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // Every download can have many hashes, e.g. one for every sampled
+        // video frame. Only the closest match of a download is kept.
+        let mut matches: HashMap<i32, SimilarMedia> = HashMap::new();
+        let mut rows = stmt2.query(params![unit])?;
+        while let Some(row) = rows.next()? {
+            let hash: i64 = row.get(3)?;
+            let hash = hash as u64;
+
+            let closest = unit_hashes
+                .iter()
+                .map(|(id_hash, unit_hash)| (id_hash, distance(hash, *unit_hash)))
+                .min_by_key(|(_, distance)| *distance);
+
+            let (similar_to, distance) = match closest {
+                Some((id_hash, distance)) if distance <= max_distance => (id_hash, distance),
+                _ => continue,
+            };
+
+            let id: i32 = row.get(0)?;
+            if let Some(similar) = matches.get(&id) {
+                if similar.distance <= distance {
+                    continue;
+                }
+            }
+
+            matches.insert(
+                id,
+                SimilarMedia {
+                    unit: row.get(1)?,
+                    title: row.get(2)?,
+                    similar_to: similar_to.to_string(),
+                    distance,
+                    download: from_row::<Download>(row)?,
+                },
+            );
+        }
+
+        let mut similar: Vec<SimilarMedia> = matches.into_values().collect();
+        similar.sort_by(|a, b| {
+            a.distance
+                .cmp(&b.distance)
+                .then(a.unit.cmp(&b.unit))
+                .then(a.download.id_hash.cmp(&b.download.id_hash))
+        });
+
+        Ok(similar)
+    }
+
+    async fn thumbnails(&self) -> Result<(), DatabaseError> {
+        unreachable!()
+    }
+
+    #[instrument]
+    async fn files(&self) -> Result<Vec<MediaFile>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/media/list-files.sql"))?;
+
+        let mut files: Vec<MediaFile> = vec![];
+        for file in from_rows::<MediaFile>(stmt.query(NO_PARAMS)?) {
+            files.push(file?);
+        }
+
+        Ok(files)
+    }
+
+    #[instrument]
+    async fn file(&self, location: &str) -> Result<Option<MediaFile>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/media/show-download.sql"))?;
+
+        let mut rows = stmt.query(params![&location])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(from_row::<MediaFile>(row)?)),
+            None => Ok(None),
+        }
+    }
+
+    #[instrument]
+    async fn digest(&self, location: &str) -> Result<Option<String>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/media/show-digest.sql"))?;
+
+        let mut rows = stmt.query(params![&location])?;
+        match rows.next()? {
+            Some(row) => Ok(row.get(0)?),
+            None => Ok(None),
+        }
+    }
+
+    #[instrument]
+    async fn metadata(&self, download: i32) -> Result<Option<MediaMetadata>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/media/show-metadata.sql"))?;
+
+        let mut rows = stmt.query(params![download])?;
+        match rows.next()? {
+            Some(row) => {
+                let metadata: serde_json::Value = row.get(0)?;
+                Ok(Some(serde_json::from_value(metadata)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    #[instrument]
+    async fn save_metadata(
+        &self,
+        download: i32,
+        metadata: &MediaMetadata,
+    ) -> Result<(), DatabaseError> {
+        let now = Utc::now();
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/media/create-metadata.sql"))?;
+
+        stmt.execute(params![
+            download,
+            &serde_json::to_value(metadata)?,
+            &now.to_rfc3339()
+        ])?;
+
+        Ok(())
+    }
+
+    async fn download(
+        &self,
+        _file_path: &str,
+        _range: Option<String>,
+    ) -> Result<RemoteFile, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn download_metadata(&self, _file_path: &str) -> Result<FileMetadata, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn thumbnail(
+        &self,
+        _file_path: &str,
+        _sheet: bool,
+        _range: Option<String>,
+    ) -> Result<RemoteFile, DatabaseError> {
+        unreachable!()
+    }
+}
+
+#[derive(Debug)]
+pub struct MediaStoreHttp {
+    client: Box<http::Database>,
+}
+
+#[async_trait]
+impl MediaStore for MediaStoreHttp {
+    #[instrument]
+    async fn hash(&self) -> Result<(), DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/media/hashes",
+            self.client.workspace.slug
+        ));
+
+        self.client.post::<(), ()>(url, ()).await?;
+
+        Ok(())
+    }
+
+    async fn unhashed(&self) -> Result<Vec<MediaFile>, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn save_hashes(&self, _download: i32, _hashes: &[u64]) -> Result<(), DatabaseError> {
+        unreachable!()
+    }
+
+    #[instrument]
+    async fn similar(
+        &self,
+        unit: i32,
+        max_distance: u32,
+    ) -> Result<Vec<SimilarMedia>, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/data/units/{}/similar",
+            self.client.workspace.slug, unit
+        ));
+        url.query_pairs_mut()
+            .clear()
+            .append_pair("distance", &max_distance.to_string());
+
+        let data: Vec<SimilarMedia> = self.client.get(url).await?.unwrap_or_default();
+
+        Ok(data)
+    }
+
+    #[instrument]
+    async fn thumbnails(&self) -> Result<(), DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/media/thumbnails",
+            self.client.workspace.slug
+        ));
+
+        self.client.post::<(), ()>(url, ()).await?;
+
+        Ok(())
+    }
+
+    async fn files(&self) -> Result<Vec<MediaFile>, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn file(&self, _location: &str) -> Result<Option<MediaFile>, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn digest(&self, _location: &str) -> Result<Option<String>, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn metadata(&self, _download: i32) -> Result<Option<MediaMetadata>, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn save_metadata(
+        &self,
+        _download: i32,
+        _metadata: &MediaMetadata,
+    ) -> Result<(), DatabaseError> {
+        unreachable!()
+    }
+
+    #[instrument]
+    async fn download(
+        &self,
+        file_path: &str,
+        range: Option<String>,
+    ) -> Result<RemoteFile, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/data/{}",
+            self.client.workspace.slug, file_path
+        ));
+
+        self.client.get_file(url, range).await
+    }
+
+    #[instrument]
+    async fn download_metadata(&self, file_path: &str) -> Result<FileMetadata, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/data/{}/meta",
+            self.client.workspace.slug, file_path
+        ));
+
+        self.client
+            .get(url)
+            .await?
+            .ok_or_else(|| DatabaseError::NotFound(file_path.to_string()))
+    }
+
+    #[instrument]
+    async fn thumbnail(
+        &self,
+        file_path: &str,
+        sheet: bool,
+        range: Option<String>,
+    ) -> Result<RemoteFile, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/data/{}/thumbnail",
+            self.client.workspace.slug, file_path
+        ));
+        url.query_pairs_mut()
+            .clear()
+            .append_pair("sheet", &sheet.to_string());
+
+        self.client.get_file(url, range).await
+    }
+}
@@ -21,10 +21,33 @@ pub trait MethodologyStore {
         title: &str,
         description: &Option<String>,
         process: &serde_json::Value,
+        initial_state: &serde_json::Value,
         slug: &str,
     ) -> Result<(), DatabaseError>;
     async fn show(&self, slug: &str) -> Result<Option<Methodology>, DatabaseError>;
+    /// Show a methodology with the process of an earlier `version`.
+    async fn show_version(
+        &self,
+        slug: &str,
+        version: i32,
+    ) -> Result<Option<Methodology>, DatabaseError>;
     async fn list(&self) -> Result<Vec<Methodology>, DatabaseError>;
+    /// Update a methodology. A changed process creates a new version of the
+    /// methodology, earlier versions stay as they are. Processes are compared
+    /// as parsed JSON, whitespace and the order of keys don't matter. Any
+    /// other change creates a new version, even if it doesn't change how the
+    /// process behaves, e.g. `1.0` instead of `1` or an added `meta` field.
+    async fn update(
+        &self,
+        slug: &str,
+        title: &str,
+        description: &Option<String>,
+        process: &serde_json::Value,
+        initial_state: &serde_json::Value,
+    ) -> Result<(), DatabaseError>;
+    async fn delete(&self, slug: &str) -> Result<(), DatabaseError>;
+    /// Check whether any investigation uses the methodology.
+    async fn in_use(&self, slug: &str) -> Result<bool, DatabaseError>;
 }
 
 #[derive(Debug)]
@@ -53,21 +76,30 @@ impl MethodologyStore for MethodologyStoreSqlite {
         title: &str,
         description: &Option<String>,
         process: &serde_json::Value,
+        initial_state: &serde_json::Value,
         slug: &str,
     ) -> Result<(), DatabaseError> {
         let now = Utc::now();
         let conn = self.db.connection().await?;
 
         let mut stmt = conn.prepare_cached(include_str!("../sql/methodology/create.sql"))?;
+        let mut stmt2 =
+            conn.prepare_cached(include_str!("../sql/methodology/create_version.sql"))?;
+
+        conn.execute_batch("BEGIN;")?;
 
         stmt.execute(params![
             &title,
             &slug,
             &description,
             &process,
+            &initial_state,
             &now.to_rfc3339(),
             &now.to_rfc3339()
         ])?;
+        stmt2.execute(params![&slug, &now.to_rfc3339()])?;
+
+        conn.execute_batch("COMMIT;")?;
 
         Ok(())
     }
@@ -104,6 +136,109 @@ impl MethodologyStore for MethodologyStoreSqlite {
 
         Ok(methodologies)
     }
+
+    #[instrument]
+    async fn show_version(
+        &self,
+        slug: &str,
+        version: i32,
+    ) -> Result<Option<Methodology>, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/methodology/show_version.sql"))?;
+        let columns = columns_from_statement(&stmt);
+        let rows = stmt.query_and_then(params![&slug, version], |row| {
+            from_row_with_columns::<Methodology>(row, &columns)
+        })?;
+
+        let mut methodologies: Vec<Methodology> = vec![];
+        for row in rows {
+            methodologies.push(row?)
+        }
+
+        match methodologies.first() {
+            Some(methodology) => Ok(Some(methodology.to_owned())),
+            _ => Ok(None),
+        }
+    }
+
+    #[instrument]
+    async fn update(
+        &self,
+        slug: &str,
+        title: &str,
+        description: &Option<String>,
+        process: &serde_json::Value,
+        initial_state: &serde_json::Value,
+    ) -> Result<(), DatabaseError> {
+        let now = Utc::now();
+        let conn = self.db.connection().await?;
+
+        let mut stmt = conn.prepare_cached(include_str!("../sql/methodology/show_process.sql"))?;
+        let mut stmt2 = conn.prepare_cached(include_str!("../sql/methodology/update.sql"))?;
+        let mut stmt3 =
+            conn.prepare_cached(include_str!("../sql/methodology/create_version.sql"))?;
+
+        let methodology_req = MethodologyReq {
+            title: title.to_string(),
+            description: description.clone(),
+            process: process.clone(),
+        };
+
+        let current: serde_json::Value = stmt.query_row(params![&slug], |row| row.get(0))?;
+        let changed = current != *process;
+
+        conn.execute_batch("BEGIN;")?;
+
+        stmt2.execute(params![
+            &methodology_req.title,
+            &methodology_req.slug(),
+            &methodology_req.description,
+            &methodology_req.process,
+            &initial_state,
+            changed as i32,
+            &now.to_rfc3339(),
+            &slug
+        ])?;
+
+        if changed {
+            stmt3.execute(params![&methodology_req.slug(), &now.to_rfc3339()])?;
+        }
+
+        conn.execute_batch("COMMIT;")?;
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete(&self, slug: &str) -> Result<(), DatabaseError> {
+        let conn = self.db.connection().await?;
+
+        let mut stmt =
+            conn.prepare_cached(include_str!("../sql/methodology/delete_versions.sql"))?;
+        let mut stmt2 = conn.prepare_cached(include_str!("../sql/methodology/delete.sql"))?;
+
+        conn.execute_batch("BEGIN;")?;
+
+        stmt.execute(params![&slug])?;
+        stmt2.execute(params![&slug])?;
+
+        conn.execute_batch("COMMIT;")?;
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn in_use(&self, slug: &str) -> Result<bool, DatabaseError> {
+        let conn = self.db.connection().await?;
+        let mut stmt = conn.prepare_cached(include_str!("../sql/methodology/in_use.sql"))?;
+
+        let result: i32 = stmt.query_row(params![&slug], |row| row.get(0))?;
+
+        match result {
+            0 => Ok(false),
+            _ => Ok(true),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -132,6 +267,8 @@ impl MethodologyStore for MethodologyStoreHttp {
         title: &str,
         description: &Option<String>,
         process: &serde_json::Value,
+        // The remote workspace computes the initial state itself.
+        _initial_state: &serde_json::Value,
         _slug: &str,
     ) -> Result<(), DatabaseError> {
         let mut url = self.client.url.clone();
@@ -176,4 +313,64 @@ impl MethodologyStore for MethodologyStoreHttp {
 
         Ok(data)
     }
+
+    #[instrument]
+    async fn show_version(
+        &self,
+        slug: &str,
+        version: i32,
+    ) -> Result<Option<Methodology>, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/methodologies/{}/versions/{}",
+            self.client.workspace.slug, slug, version
+        ));
+
+        let data: Option<Methodology> = self.client.get(url).await?;
+
+        Ok(data)
+    }
+
+    #[instrument]
+    async fn update(
+        &self,
+        slug: &str,
+        title: &str,
+        description: &Option<String>,
+        process: &serde_json::Value,
+        _initial_state: &serde_json::Value,
+    ) -> Result<(), DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/methodologies/{}",
+            self.client.workspace.slug, slug
+        ));
+
+        let payload = MethodologyReq {
+            title: title.to_string(),
+            process: process.clone(),
+            description: description.clone(),
+        };
+
+        self.client.put::<(), MethodologyReq>(url, payload).await?;
+
+        Ok(())
+    }
+
+    #[instrument]
+    async fn delete(&self, slug: &str) -> Result<(), DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/methodologies/{}",
+            self.client.workspace.slug, slug
+        ));
+
+        self.client.delete(url).await?;
+
+        Ok(())
+    }
+
+    async fn in_use(&self, _slug: &str) -> Result<bool, DatabaseError> {
+        unreachable!()
+    }
 }
@@ -1,12 +1,19 @@
 use async_trait::async_trait;
-use ncube_data::{Download, Media, QueryTag, SearchResponse, Source, Unit};
-use ncube_db::{errors::DatabaseError, http, sqlite, Database};
+use ncube_data::{
+    Cursor, Download, ExportOptions, FacetCount, Facets, Highlights, Media, Page, PageRequest,
+    QueryTag, SearchResponse, SortOrder, Source, Unit,
+};
+use ncube_db::{
+    errors::DatabaseError,
+    http::{self, RemoteFile},
+    sqlite, Database,
+};
 use ncube_search::SearchQuery;
-use rusqlite::{params, ToSql};
-use serde_rusqlite::from_rows;
+use rusqlite::{params, Connection, Row, ToSql};
+use serde_rusqlite::{from_row, from_rows};
 use tracing::instrument;
 
-use crate::SearchQuerySqlite;
+use crate::{SearchQuerySqlite, UnitOrderSqlite};
 
 pub fn search_store(wrapped_db: Database) -> Box<dyn SearchStore + Send + Sync> {
     match wrapped_db {
@@ -20,15 +27,86 @@ pub trait SearchStore {
     async fn data(
         &self,
         query: &SearchQuery,
-        page: i32,
-        page_size: i32,
-    ) -> Result<Vec<Unit>, DatabaseError>;
+        sort: &SortOrder,
+        page: &PageRequest,
+    ) -> Result<Page<Unit>, DatabaseError>;
     async fn sources(
         &self,
         query: &str,
         page: i32,
         page_size: i32,
     ) -> Result<Vec<Source>, DatabaseError>;
+    async fn facets(&self, query: &SearchQuery) -> Result<Facets, DatabaseError>;
+    /// Stream the unit export of a search of a remote workspace.
+    async fn export(
+        &self,
+        query: &SearchQuery,
+        sort: &SortOrder,
+        options: &ExportOptions,
+    ) -> Result<RemoteFile, DatabaseError>;
+}
+
+// The snippets delimit matches with control characters that are replaced by
+// `<mark>` tags once the text of the unit is HTML escaped. The snippets are
+// always selected, but only those that mark a match are kept. A unit can
+// match on a limit or on a column that is not highlighted.
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            MATCH_START => escaped.push_str("<mark>"),
+            MATCH_END => escaped.push_str("</mark>"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn highlights(row: &Row) -> rusqlite::Result<Option<Highlights>> {
+    let matched = |column: &str| -> rusqlite::Result<Option<String>> {
+        let snippet: Option<String> = row.get(column)?;
+        Ok(snippet
+            .filter(|snippet| snippet.contains(MATCH_START))
+            .map(|snippet| escape_html(&snippet)))
+    };
+
+    let highlights = Highlights {
+        body: matched("highlight_body")?,
+        title: matched("highlight_title")?,
+        description: matched("highlight_description")?,
+    };
+
+    if highlights == Highlights::default() {
+        Ok(None)
+    } else {
+        Ok(Some(highlights))
+    }
+}
+
+fn facet(conn: &Connection, tmpl: &str, query: &SearchQuery) -> rusqlite::Result<Vec<FacetCount>> {
+    let params: Vec<Box<dyn ToSql>> = vec![];
+    let sql = SearchQuerySqlite::from(query);
+    let (facet_sql, params) = sql.to_sql(tmpl, params);
+
+    let mut stmt = conn.prepare_cached(&facet_sql)?;
+    let counts = stmt.query_map(params, |row| {
+        Ok(FacetCount {
+            value: row.get(0)?,
+            count: row.get(1)?,
+        })
+    })?;
+
+    counts.collect()
 }
 
 #[derive(Debug)]
@@ -42,17 +120,19 @@ impl SearchStore for SearchStoreSqlite {
     async fn data(
         &self,
         query: &SearchQuery,
-        page: i32,
-        page_size: i32,
-    ) -> Result<Vec<Unit>, DatabaseError> {
+        sort: &SortOrder,
+        page: &PageRequest,
+    ) -> Result<Page<Unit>, DatabaseError> {
         let conn = self.db.connection().await?;
 
-        let tmpl = include_str!("../sql/search/data.sql");
-        let offset = page * page_size;
-        let params: Vec<Box<dyn ToSql>> = vec![Box::new(offset), Box::new(page_size)];
+        let order = UnitOrderSqlite::new(sort, "bm25(unit_fts)");
+        let tmpl = order.to_sql(include_str!("../sql/search/data.sql"), page);
+        let mut params = order.params(page);
+        params.push(Box::new(page.size));
+        params.push(Box::new(page.offset()));
 
         let sql = SearchQuerySqlite::from(query);
-        let (data_sql, params) = sql.to_sql(tmpl, params);
+        let (data_sql, params) = sql.to_sql(&tmpl, params);
 
         let mut stmt = conn.prepare_cached(&data_sql)?;
         let mut stmt2 = conn.prepare_cached(include_str!("../sql/unit/list-media.sql"))?;
@@ -61,9 +141,13 @@ impl SearchStore for SearchStoreSqlite {
         let mut stmt5 = conn.prepare_cached(include_str!("../sql/unit/list-tags.sql"))?;
 
         let mut units: Vec<Unit> = vec![];
+        let mut last: Option<Cursor> = None;
 
-        for unit in from_rows::<Unit>(stmt.query(params)?) {
-            let mut unit = unit?;
+        let mut rows = stmt.query(params)?;
+
+        while let Some(row) = rows.next()? {
+            let mut unit = from_row::<Unit>(row)?;
+            last = Some(order.cursor(row, unit.id)?);
             let mut medias: Vec<Media> = vec![];
             let mut downloads: Vec<Download> = vec![];
             let mut sources: Vec<Source> = vec![];
@@ -89,11 +173,17 @@ impl SearchStore for SearchStoreSqlite {
             unit.downloads = downloads;
             unit.sources = sources;
             unit.tags = tags;
+            unit.highlights = highlights(row)?;
+            if let SortOrder::Relevance = sort {
+                unit.rank = Some(row.get("sort_key")?);
+            }
 
             units.push(unit);
         }
 
-        Ok(units)
+        let next = page.next(units.len(), last);
+
+        Ok(Page { data: units, next })
     }
 
     #[instrument]
@@ -127,6 +217,41 @@ impl SearchStore for SearchStoreSqlite {
 
         Ok(sources)
     }
+
+    #[instrument]
+    async fn facets(&self, query: &SearchQuery) -> Result<Facets, DatabaseError> {
+        let conn = self.db.connection().await?;
+
+        Ok(Facets {
+            tags: facet(&conn, include_str!("../sql/search/facets_tags.sql"), query)?,
+            sources: facet(
+                &conn,
+                include_str!("../sql/search/facets_sources.sql"),
+                query,
+            )?,
+            languages: facet(
+                &conn,
+                include_str!("../sql/search/facets_languages.sql"),
+                query,
+            )?,
+            years: facet(&conn, include_str!("../sql/search/facets_years.sql"), query)?,
+            months: facet(
+                &conn,
+                include_str!("../sql/search/facets_months.sql"),
+                query,
+            )?,
+            media: facet(&conn, include_str!("../sql/search/facets_media.sql"), query)?,
+        })
+    }
+
+    async fn export(
+        &self,
+        _query: &SearchQuery,
+        _sort: &SortOrder,
+        _options: &ExportOptions,
+    ) -> Result<RemoteFile, DatabaseError> {
+        unreachable!()
+    }
 }
 
 #[derive(Debug)]
@@ -140,9 +265,9 @@ impl SearchStore for SearchStoreHttp {
     async fn data(
         &self,
         query: &SearchQuery,
-        page: i32,
-        page_size: i32,
-    ) -> Result<Vec<Unit>, DatabaseError> {
+        sort: &SortOrder,
+        page: &PageRequest,
+    ) -> Result<Page<Unit>, DatabaseError> {
         let mut url = self.client.url.clone();
         url.set_path(&format!(
             "/api/workspaces/{}/data/search",
@@ -151,8 +276,14 @@ impl SearchStore for SearchStoreHttp {
         url.query_pairs_mut()
             .clear()
             .append_pair("q", &query.to_string())
-            .append_pair("page", &page.to_string())
-            .append_pair("size", &page_size.to_string());
+            .append_pair("sort", &sort.to_string())
+            .append_pair("page", &page.page.to_string())
+            .append_pair("size", &page.size.to_string());
+
+        if let Some(cursor) = &page.after {
+            url.query_pairs_mut()
+                .append_pair("cursor", &cursor.to_string());
+        }
 
         let data: SearchResponse<Unit> =
             self.client
@@ -161,9 +292,13 @@ impl SearchStore for SearchStoreHttp {
                 .unwrap_or_else(|| SearchResponse {
                     data: vec![],
                     total: 0,
+                    next: None,
                 });
 
-        Ok(data.data)
+        Ok(Page {
+            data: data.data,
+            next: data.next,
+        })
     }
 
     #[instrument]
@@ -191,8 +326,231 @@ impl SearchStore for SearchStoreHttp {
                 .unwrap_or_else(|| SearchResponse {
                     data: vec![],
                     total: 0,
+                    next: None,
                 });
 
         Ok(data.data)
     }
+
+    #[instrument]
+    async fn facets(&self, query: &SearchQuery) -> Result<Facets, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/data/search/facets",
+            self.client.workspace.slug
+        ));
+        url.query_pairs_mut()
+            .clear()
+            .append_pair("q", &query.to_string());
+
+        let data: Facets = self.client.get(url).await?.unwrap_or_default();
+
+        Ok(data)
+    }
+
+    #[instrument]
+    async fn export(
+        &self,
+        query: &SearchQuery,
+        sort: &SortOrder,
+        options: &ExportOptions,
+    ) -> Result<RemoteFile, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/data/export",
+            self.client.workspace.slug
+        ));
+        url.query_pairs_mut()
+            .clear()
+            .append_pair("q", &query.to_string())
+            .append_pair("sort", &sort.to_string())
+            .append_pair("format", &options.format.to_string())
+            .append_pair("columns", &options.column_list());
+
+        self.client.get_file(url, None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ncube_data::{PageRequest, SortOrder};
+    use ncube_search::parse_query;
+    use rusqlite::Connection;
+
+    use super::*;
+    use crate::tests::workspace;
+
+    fn search_highlights(conn: &Connection, query: &str) -> Vec<(i32, Option<Highlights>)> {
+        let order = UnitOrderSqlite::new(&SortOrder::Relevance, "bm25(unit_fts)");
+        let page = PageRequest {
+            after: None,
+            page: 0,
+            size: 20,
+        };
+        let tmpl = order.to_sql(include_str!("../sql/search/data.sql"), &page);
+        let mut params = order.params(&page);
+        params.push(Box::new(page.size));
+        params.push(Box::new(page.offset()));
+
+        let query = parse_query(query).unwrap();
+        let (data_sql, params) = SearchQuerySqlite::from(&query).to_sql(&tmpl, params);
+
+        let mut stmt = conn.prepare(&data_sql).unwrap();
+        let mut rows = stmt.query(params).unwrap();
+        let mut results = vec![];
+
+        while let Some(row) = rows.next().unwrap() {
+            results.push((row.get("id").unwrap(), highlights(row).unwrap()));
+        }
+
+        results
+    }
+
+    fn facet_counts(conn: &Connection, tmpl: &str, query: &str) -> Vec<(String, i32)> {
+        facet(conn, tmpl, &parse_query(query).unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|facet| (facet.value, facet.count))
+            .collect()
+    }
+
+    fn counts(counts: &[(&str, i32)]) -> Vec<(String, i32)> {
+        counts
+            .iter()
+            .map(|(value, count)| (value.to_string(), *count))
+            .collect()
+    }
+
+    #[test]
+    fn test_facets_count_the_units_of_a_query() {
+        let conn = workspace();
+        // Units 1, 2, 4 and 5.
+        let query = "aleppo OR airstrike";
+
+        assert_eq!(
+            facet_counts(
+                &conn,
+                include_str!("../sql/search/facets_sources.sql"),
+                query
+            ),
+            counts(&[("youtube_video", 3), ("twitter_tweet", 1)])
+        );
+        assert_eq!(
+            facet_counts(&conn, include_str!("../sql/search/facets_tags.sql"), query),
+            counts(&[("civilian", 2), ("verified", 1)])
+        );
+        assert_eq!(
+            facet_counts(
+                &conn,
+                include_str!("../sql/search/facets_languages.sql"),
+                query
+            ),
+            counts(&[("ar", 2), ("en", 2)])
+        );
+        assert_eq!(
+            facet_counts(&conn, include_str!("../sql/search/facets_media.sql"), query),
+            counts(&[("video", 2), ("image", 1)])
+        );
+        assert_eq!(
+            facet_counts(&conn, include_str!("../sql/search/facets_years.sql"), query),
+            counts(&[("2020", 4)])
+        );
+        assert_eq!(
+            facet_counts(
+                &conn,
+                include_str!("../sql/search/facets_months.sql"),
+                query
+            ),
+            counts(&[("2020-01", 2), ("2020-03", 1), ("2020-05", 1)])
+        );
+    }
+
+    #[test]
+    fn test_facets_of_a_query_with_limits() {
+        let conn = workspace();
+        // Units 1, 3 and 5, unit 6 is an http_url.
+        let query = "protest -source:http_url";
+
+        assert_eq!(
+            facet_counts(
+                &conn,
+                include_str!("../sql/search/facets_sources.sql"),
+                query
+            ),
+            counts(&[("youtube_video", 2), ("twitter_tweet", 1)])
+        );
+        assert_eq!(
+            facet_counts(&conn, include_str!("../sql/search/facets_tags.sql"), query),
+            counts(&[])
+        );
+        assert_eq!(
+            facet_counts(&conn, include_str!("../sql/search/facets_media.sql"), query),
+            counts(&[("image", 1), ("video", 1)])
+        );
+        assert_eq!(
+            facet_counts(
+                &conn,
+                include_str!("../sql/search/facets_months.sql"),
+                query
+            ),
+            counts(&[("2020-01", 2), ("2020-03", 1)])
+        );
+    }
+
+    #[test]
+    fn test_highlights_escape_the_unit_text() {
+        let conn = workspace();
+        conn.execute_batch(
+            r#"
+            INSERT INTO unit (id, id_hash, content_hash, source, body, title, fetched_at)
+            VALUES (8, 'h8', 'c8', 'http_url', '<script>alert("x")</script> & more', NULL,
+                    '2020-07-01T10:00:00.000Z'),
+                   (9, 'h9', 'c9', 'http_url', '<mark>forecast</mark>', 'storm',
+                    '2020-07-01T10:00:00.000Z');
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            search_highlights(&conn, "alert"),
+            vec![(
+                8,
+                Some(Highlights {
+                    body: Some(
+                        "&lt;script&gt;<mark>alert</mark>(&quot;x&quot;)&lt;/script&gt; &amp; more"
+                            .into()
+                    ),
+                    title: None,
+                    description: None,
+                })
+            )]
+        );
+
+        // The body contains a literal mark tag but only the title matched.
+        assert_eq!(
+            search_highlights(&conn, "storm"),
+            vec![(
+                9,
+                Some(Highlights {
+                    body: None,
+                    title: Some("<mark>storm</mark>".into()),
+                    description: None,
+                })
+            )]
+        );
+
+        // A unit that only matches on a limit has no highlights.
+        assert_eq!(search_highlights(&conn, "forecast tag:civilian"), vec![]);
+        assert_eq!(
+            search_highlights(&conn, "source:http_url forecast"),
+            vec![(
+                9,
+                Some(Highlights {
+                    body: Some("&lt;mark&gt;<mark>forecast</mark>&lt;/mark&gt;".into()),
+                    title: None,
+                    description: None,
+                })
+            )]
+        );
+    }
 }
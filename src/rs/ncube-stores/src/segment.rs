@@ -1,7 +1,11 @@
 use async_trait::async_trait;
 use chrono::Utc;
-use ncube_data::{Segment, SegmentRequest};
-use ncube_db::{errors::DatabaseError, http, sqlite, Database};
+use ncube_data::{ExportOptions, Segment, SegmentRequest, SortOrder};
+use ncube_db::{
+    errors::DatabaseError,
+    http::{self, RemoteFile},
+    sqlite, Database,
+};
 use rusqlite::{params, NO_PARAMS};
 use serde_rusqlite::{self, columns_from_statement, from_row_with_columns, from_rows};
 use tracing::instrument;
@@ -16,11 +20,31 @@ pub fn segment_store(wrapped_db: Database) -> Box<dyn SegmentStore + Send + Sync
 #[async_trait]
 pub trait SegmentStore {
     async fn exists(&self, slug: &str) -> Result<bool, DatabaseError>;
-    async fn create(&self, query: &str, title: &str, slug: &str) -> Result<(), DatabaseError>;
+    async fn create(
+        &self,
+        query: &str,
+        title: &str,
+        slug: &str,
+        sort: Option<SortOrder>,
+    ) -> Result<(), DatabaseError>;
     async fn show(&self, slug: &str) -> Result<Option<Segment>, DatabaseError>;
     async fn list(&self) -> Result<Vec<Segment>, DatabaseError>;
     async fn delete(&self, slug: &str) -> Result<(), DatabaseError>;
-    async fn update(&self, slug: &str, query: &str, title: &str) -> Result<(), DatabaseError>;
+    async fn update(
+        &self,
+        slug: &str,
+        query: &str,
+        title: &str,
+        sort: Option<SortOrder>,
+    ) -> Result<(), DatabaseError>;
+    /// Stream the export archive of a segment of a remote workspace.
+    async fn export(&self, slug: &str) -> Result<RemoteFile, DatabaseError>;
+    /// Stream the unit export of a segment of a remote workspace.
+    async fn export_units(
+        &self,
+        slug: &str,
+        options: &ExportOptions,
+    ) -> Result<RemoteFile, DatabaseError>;
 }
 
 #[derive(Debug)]
@@ -44,7 +68,13 @@ impl SegmentStore for SegmentStoreSqlite {
     }
 
     #[instrument]
-    async fn create(&self, query: &str, title: &str, slug: &str) -> Result<(), DatabaseError> {
+    async fn create(
+        &self,
+        query: &str,
+        title: &str,
+        slug: &str,
+        sort: Option<SortOrder>,
+    ) -> Result<(), DatabaseError> {
         let now = Utc::now();
         let conn = self.db.connection().await?;
 
@@ -54,6 +84,7 @@ impl SegmentStore for SegmentStoreSqlite {
             &query,
             &title,
             &slug,
+            &sort.map(|sort| sort.to_string()),
             &now.to_rfc3339(),
             &now.to_rfc3339()
         ])?;
@@ -105,6 +136,7 @@ impl SegmentStore for SegmentStoreSqlite {
             ))?;
             let mut stmt3 = conn.prepare_cached(include_str!("../sql/segment/delete.sql"))?;
 
+            // The history of the removed verifications is kept.
             conn.execute_batch("BEGIN;")?;
 
             stmt.execute(params![&segment.id])?;
@@ -118,7 +150,13 @@ impl SegmentStore for SegmentStoreSqlite {
     }
 
     #[instrument]
-    async fn update(&self, slug: &str, query: &str, title: &str) -> Result<(), DatabaseError> {
+    async fn update(
+        &self,
+        slug: &str,
+        query: &str,
+        title: &str,
+        sort: Option<SortOrder>,
+    ) -> Result<(), DatabaseError> {
         let now = Utc::now();
         let conn = self.db.connection().await?;
         let mut stmt = conn.prepare_cached(include_str!("../sql/segment/update.sql"))?;
@@ -126,18 +164,32 @@ impl SegmentStore for SegmentStoreSqlite {
         let segment_req = SegmentRequest {
             title: title.to_string(),
             query: query.to_string(),
+            sort,
         };
 
         stmt.execute(params![
             &segment_req.query,
             &segment_req.title,
             &segment_req.slug(),
+            &segment_req.sort.map(|sort| sort.to_string()),
             &now.to_rfc3339(),
             &slug
         ])?;
 
         Ok(())
     }
+
+    async fn export(&self, _slug: &str) -> Result<RemoteFile, DatabaseError> {
+        unreachable!()
+    }
+
+    async fn export_units(
+        &self,
+        _slug: &str,
+        _options: &ExportOptions,
+    ) -> Result<RemoteFile, DatabaseError> {
+        unreachable!()
+    }
 }
 
 #[derive(Debug)]
@@ -148,7 +200,13 @@ pub struct SegmentStoreHttp {
 #[async_trait]
 impl SegmentStore for SegmentStoreHttp {
     #[instrument]
-    async fn create(&self, query: &str, title: &str, _slug: &str) -> Result<(), DatabaseError> {
+    async fn create(
+        &self,
+        query: &str,
+        title: &str,
+        _slug: &str,
+        sort: Option<SortOrder>,
+    ) -> Result<(), DatabaseError> {
         let mut url = self.client.url.clone();
         url.set_path(&format!(
             "/api/workspaces/{}/segments",
@@ -158,6 +216,7 @@ impl SegmentStore for SegmentStoreHttp {
         let payload = SegmentRequest {
             query: query.to_string(),
             title: title.to_string(),
+            sort,
         };
 
         self.client.post::<(), SegmentRequest>(url, payload).await?;
@@ -219,7 +278,13 @@ impl SegmentStore for SegmentStoreHttp {
     }
 
     #[instrument]
-    async fn update(&self, slug: &str, query: &str, title: &str) -> Result<(), DatabaseError> {
+    async fn update(
+        &self,
+        slug: &str,
+        query: &str,
+        title: &str,
+        sort: Option<SortOrder>,
+    ) -> Result<(), DatabaseError> {
         let mut url = self.client.url.clone();
         url.set_path(&format!(
             "/api/workspaces/{}/segments/{}",
@@ -229,10 +294,41 @@ impl SegmentStore for SegmentStoreHttp {
         let payload = SegmentRequest {
             title: title.to_string(),
             query: query.to_string(),
+            sort,
         };
 
         self.client.put::<(), SegmentRequest>(url, payload).await?;
 
         Ok(())
     }
+
+    #[instrument]
+    async fn export(&self, slug: &str) -> Result<RemoteFile, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/segments/{}/export",
+            self.client.workspace.slug, slug
+        ));
+
+        self.client.get_file(url, None).await
+    }
+
+    #[instrument]
+    async fn export_units(
+        &self,
+        slug: &str,
+        options: &ExportOptions,
+    ) -> Result<RemoteFile, DatabaseError> {
+        let mut url = self.client.url.clone();
+        url.set_path(&format!(
+            "/api/workspaces/{}/segments/{}/units/export",
+            self.client.workspace.slug, slug
+        ));
+        url.query_pairs_mut()
+            .clear()
+            .append_pair("format", &options.format.to_string())
+            .append_pair("columns", &options.column_list());
+
+        self.client.get_file(url, None).await
+    }
 }
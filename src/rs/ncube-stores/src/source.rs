@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 use chrono::Utc;
-use ncube_data::{QueryTag, Source, SourceRequest};
+use ncube_data::{Cursor, Page, PageRequest, QueryTag, Source, SourceRequest};
 use rusqlite::{params, Error as RusqliteError, NO_PARAMS};
 use serde_rusqlite::from_rows;
 use tracing::instrument;
@@ -24,7 +24,7 @@ pub trait SourceStore {
         term: &str,
         tags: Vec<QueryTag>,
     ) -> Result<(), DatabaseError>;
-    async fn list(&self, page: i32, page_size: i32) -> Result<Vec<Source>, DatabaseError>;
+    async fn list(&self, page: &PageRequest) -> Result<Page<Source>, DatabaseError>;
     async fn delete(&self, id: i32) -> Result<(), DatabaseError>;
     async fn update(&self, id: i32, kind: &str, term: &str) -> Result<(), DatabaseError>;
     async fn list_source_tags(&self) -> Result<Vec<QueryTag>, DatabaseError>;
@@ -118,16 +118,19 @@ impl SourceStore for SourceStoreSqlite {
     }
 
     #[instrument]
-    async fn list(&self, page: i32, page_size: i32) -> Result<Vec<Source>, DatabaseError> {
+    async fn list(&self, page: &PageRequest) -> Result<Page<Source>, DatabaseError> {
         let conn = self.db.connection().await?;
         let mut stmt = conn.prepare_cached(include_str!("../sql/source/paginate.sql"))?;
         let mut stmt2 =
             conn.prepare_cached(include_str!("../sql/source/list-query-tags-for-query.sql"))?;
 
-        let offset = page * page_size;
         let mut sources: Vec<Source> = vec![];
 
-        for source in from_rows::<Source>(stmt.query(params![offset, page_size])?) {
+        for source in from_rows::<Source>(stmt.query(params![
+            page.after.as_ref().map(|cursor| cursor.id),
+            page.size,
+            page.offset()
+        ])?) {
             let mut source = source?;
 
             let mut tags: Vec<QueryTag> = vec![];
@@ -141,7 +144,13 @@ impl SourceStore for SourceStoreSqlite {
             sources.push(source);
         }
 
-        Ok(sources)
+        let last = sources.last().map(|source| Cursor::rank(0.0, source.id));
+        let next = page.next(sources.len(), last);
+
+        Ok(Page {
+            data: sources,
+            next,
+        })
     }
 
     #[instrument]
@@ -262,18 +271,31 @@ impl SourceStore for SourceStoreHttp {
     }
 
     #[instrument]
-    async fn list(&self, page: i32, page_size: i32) -> Result<Vec<Source>, DatabaseError> {
+    async fn list(&self, page: &PageRequest) -> Result<Page<Source>, DatabaseError> {
         let mut url = self.client.url.clone();
         url.set_path(&format!(
             "/api/workspaces/{}/sources",
             self.client.workspace.slug
         ));
+        // The cursor parameter is always sent, even if it is empty, to receive
+        // the next cursor as part of the response.
         url.query_pairs_mut()
             .clear()
-            .append_pair("page", &page.to_string())
-            .append_pair("size", &page_size.to_string());
-
-        let data: Vec<Source> = self.client.get(url).await?.unwrap_or_else(|| vec![]);
+            .append_pair("page", &page.page.to_string())
+            .append_pair("size", &page.size.to_string())
+            .append_pair(
+                "cursor",
+                &page
+                    .after
+                    .as_ref()
+                    .map(|cursor| cursor.to_string())
+                    .unwrap_or_default(),
+            );
+
+        let data: Page<Source> = self.client.get(url).await?.unwrap_or_else(|| Page {
+            data: vec![],
+            next: None,
+        });
 
         Ok(data)
     }
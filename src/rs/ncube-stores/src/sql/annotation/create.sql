@@ -4,12 +4,14 @@ INSERT INTO annotation (
   value,
   name,
   note,
+  author,
   created_at,
   updated_at
 ) VALUES
-(?1, ?2, ?3, ?4, ?5, ?6, ?7)
+(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
   ON CONFLICT (verification, key) DO UPDATE
  SET value = excluded.value,
      note = excluded.note,
      name = excluded.name,
+     author = excluded.author,
      updated_at = excluded.updated_at;
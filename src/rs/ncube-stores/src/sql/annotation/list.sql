@@ -2,6 +2,7 @@ SELECT key,
        value,
        note,
        name,
+       author,
        created_at,
        updated_at
   FROM annotation
@@ -0,0 +1,20 @@
+SELECT l.event,
+       e.unit,
+       e.kind,
+       e.data,
+       e.created_at,
+       e.id IS NOT NULL,
+       l.hash
+  FROM custody_link l
+  LEFT JOIN custody_event e ON e.id = l.event
+ UNION ALL
+SELECT e.id,
+       e.unit,
+       e.kind,
+       e.data,
+       e.created_at,
+       1,
+       NULL
+  FROM custody_event e
+ WHERE NOT EXISTS (SELECT 1 FROM custody_link l WHERE l.event = e.id)
+ ORDER BY 1;
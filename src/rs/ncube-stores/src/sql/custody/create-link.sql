@@ -0,0 +1,9 @@
+INSERT OR IGNORE INTO custody_link (
+  event,
+  hash,
+  created_at
+) VALUES (
+  ?1,
+  ?2,
+  ?3
+);
@@ -0,0 +1,9 @@
+INSERT INTO process_run (
+  process,
+  task,
+  started_at
+) VALUES (
+  ?1,
+  ?2,
+  ?3
+);
@@ -0,0 +1,3 @@
+UPDATE process_run
+   SET finished_at = ?2
+ WHERE id = ?1;
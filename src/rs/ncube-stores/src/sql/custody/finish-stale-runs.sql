@@ -0,0 +1,3 @@
+UPDATE process_run
+   SET finished_at = ?1
+ WHERE finished_at IS NULL;
@@ -0,0 +1,5 @@
+SELECT event,
+       hash
+  FROM custody_link
+ ORDER BY event DESC
+ LIMIT 1;
@@ -0,0 +1,8 @@
+SELECT id,
+       unit,
+       kind,
+       data,
+       created_at
+  FROM custody_event
+ WHERE unit = ?1
+ ORDER BY id;
@@ -0,0 +1,6 @@
+INSERT INTO duplicate (
+  unit,
+  cluster,
+  created_at
+) VALUES
+(?1, ?2, ?3);
@@ -0,0 +1,4 @@
+SELECT id,
+       content_hash,
+       coalesce(title, '') || ' ' || coalesce(body, '') AS text
+  FROM unit;
@@ -0,0 +1,4 @@
+SELECT cluster,
+       unit
+  FROM duplicate
+ ORDER BY cluster, unit;
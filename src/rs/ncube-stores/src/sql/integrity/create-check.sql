@@ -0,0 +1,13 @@
+INSERT INTO integrity_check (
+  report,
+  download,
+  status,
+  md5,
+  sha256
+) VALUES (
+  ?1,
+  ?2,
+  ?3,
+  ?4,
+  ?5
+);
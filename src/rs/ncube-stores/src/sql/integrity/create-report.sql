@@ -0,0 +1 @@
+INSERT INTO integrity_report (created_at) VALUES (?1);
@@ -0,0 +1,9 @@
+SELECT id AS download,
+       unit,
+       id_hash,
+       location,
+       md5,
+       sha256
+  FROM download
+ WHERE location IS NOT NULL
+ ORDER BY id;
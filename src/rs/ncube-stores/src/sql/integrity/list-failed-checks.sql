@@ -0,0 +1,12 @@
+SELECT c.download,
+       d.unit,
+       d.id_hash,
+       d.location,
+       c.status,
+       c.md5,
+       c.sha256
+  FROM integrity_check c
+  JOIN download d ON d.id = c.download
+ WHERE c.report = ?1
+   AND c.status <> 'intact'
+ ORDER BY c.download;
@@ -0,0 +1,11 @@
+SELECT r.id,
+       r.created_at,
+       COUNT(c.id) AS total,
+       COALESCE(SUM(c.status = 'intact'), 0) AS intact,
+       COALESCE(SUM(c.status = 'missing'), 0) AS missing,
+       COALESCE(SUM(c.status = 'altered'), 0) AS altered,
+       COALESCE(SUM(c.status = 'unverified'), 0) AS unverified
+  FROM integrity_report r
+  LEFT JOIN integrity_check c ON c.report = r.id
+ WHERE r.id = ?1
+ GROUP BY r.id;
@@ -3,8 +3,9 @@ INSERT INTO investigation (
   slug,
   description,
   methodology,
+  methodology_version,
   created_at,
   updated_at
 ) VALUES
-(?1, ?2, ?3, ?4, ?5, ?6)
+(?1, ?2, ?3, ?4, (SELECT version FROM methodology WHERE id = ?4), ?5, ?6)
 ON CONFLICT(slug) DO NOTHING;
@@ -0,0 +1,13 @@
+INSERT INTO verification_history (verification, from_state, to_state, event, author, note, created_at)
+SELECT v.id,
+       json_extract(v.state, '$.value'),
+       json_extract(?4, '$.value'),
+       json_extract(?4, '$.event.type'),
+       ?5,
+       ?6,
+       ?7
+  FROM verification v
+ WHERE v.investigation = ?1
+   AND v.segment = ?2
+   AND v.unit = ?3
+   AND json_extract(v.state, '$.value') = json_extract(?8, '$.value');
@@ -4,6 +4,7 @@ SELECT i.id AS id,
        i.description AS description,
        i.created_at AS created_at,
        i.updated_at AS updated_at,
-       m.slug AS methodology
+       m.slug AS methodology,
+       i.methodology_version AS methodology_version
   FROM investigation i
   JOIN methodology m ON i.methodology = m.id;
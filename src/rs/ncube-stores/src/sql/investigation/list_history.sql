@@ -0,0 +1,14 @@
+SELECT h.id AS id,
+       h.verification AS verification,
+       h.from_state AS from_state,
+       h.to_state AS to_state,
+       h.event AS event,
+       h.author AS author,
+       h.note AS note,
+       h.created_at AS created_at
+  FROM verification_history h
+  JOIN verification v ON h.verification = v.id
+  JOIN investigation i ON v.investigation = i.id
+ WHERE i.slug = ?1
+   AND v.id = ?2
+ ORDER BY h.id;
@@ -0,0 +1,9 @@
+SELECT v.id AS id,
+       v.unit AS unit,
+       v.state AS state
+  FROM verification v
+  JOIN segment s ON v.segment = s.id
+  JOIN investigation i ON v.investigation = i.id
+ WHERE i.slug = ?1
+   AND s.slug = ?2
+   AND v.unit = ?3;
@@ -0,0 +1,7 @@
+SELECT v.id AS id,
+       v.unit AS unit,
+       v.state AS state
+  FROM verification v
+  JOIN investigation i ON v.investigation = i.id
+ WHERE i.slug = ?1
+   AND v.id = ?2;
@@ -1,5 +1,7 @@
 UPDATE verification
-   SET state = ?4
+   SET state = ?4,
+       updated_at = ?5
  WHERE investigation = ?1
    AND segment = ?2
-   AND unit = ?3;
+   AND unit = ?3
+   AND json_extract(state, '$.value') = json_extract(?6, '$.value');
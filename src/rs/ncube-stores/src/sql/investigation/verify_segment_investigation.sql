@@ -1,5 +1,7 @@
 SELECT i.id AS id,
-       m.initial_state AS initial_state
+       v.initial_state AS initial_state
   FROM investigation i
-  JOIN methodology m ON i.methodology = m.id
+  JOIN methodology_version v
+    ON v.methodology = i.methodology
+   AND v.version = i.methodology_version
  WHERE i.slug = ?1;
@@ -0,0 +1,11 @@
+INSERT OR REPLACE INTO perceptual_hash (
+  download,
+  frame,
+  hash,
+  created_at
+) VALUES (
+  ?1,
+  ?2,
+  ?3,
+  ?4
+);
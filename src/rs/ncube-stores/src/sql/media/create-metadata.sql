@@ -0,0 +1,9 @@
+INSERT OR REPLACE INTO download_metadata (
+  download,
+  metadata,
+  created_at
+) VALUES (
+  ?1,
+  ?2,
+  ?3
+);
@@ -0,0 +1,7 @@
+SELECT d.id AS download,
+       d.type,
+       d.location
+  FROM download d
+ WHERE d.type IN ('image', 'video')
+   AND d.location IS NOT NULL
+ ORDER BY d.id;
@@ -0,0 +1,14 @@
+SELECT d.id,
+       d.unit,
+       u.title,
+       p.hash,
+       d.id_hash,
+       d.type,
+       d.term,
+       d.md5,
+       d.sha256,
+       d.location
+  FROM perceptual_hash p
+  JOIN download d ON d.id = p.download
+  JOIN unit u ON u.id = d.unit
+ WHERE d.unit <> ?1;
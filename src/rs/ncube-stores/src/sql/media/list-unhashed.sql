@@ -0,0 +1,8 @@
+SELECT d.id AS download,
+       d.type,
+       d.location
+  FROM download d
+ WHERE d.type IN ('image', 'video')
+   AND d.location IS NOT NULL
+   AND NOT EXISTS (SELECT 1 FROM perceptual_hash p WHERE p.download = d.id)
+ ORDER BY d.id;
@@ -0,0 +1,5 @@
+SELECT d.id_hash,
+       p.hash
+  FROM perceptual_hash p
+  JOIN download d ON d.id = p.download
+ WHERE d.unit = ?1;
@@ -0,0 +1,5 @@
+SELECT d.sha256
+  FROM download d
+ WHERE d.location = ?1
+ ORDER BY d.id
+ LIMIT 1;
@@ -0,0 +1,8 @@
+SELECT d.id AS download,
+       d.type,
+       d.location
+  FROM download d
+ WHERE d.type IN ('image', 'video')
+   AND d.location = ?1
+ ORDER BY d.id
+ LIMIT 1;
@@ -0,0 +1,3 @@
+SELECT metadata
+  FROM download_metadata
+ WHERE download = ?1;
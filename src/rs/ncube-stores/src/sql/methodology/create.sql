@@ -3,8 +3,10 @@ INSERT INTO methodology (
   slug,
   description,
   process,
+  initial_state,
+  version,
   created_at,
   updated_at
 ) VALUES
-(?1, ?2, ?3, ?4, ?5, ?6)
+(?1, ?2, ?3, ?4, ?5, 1, ?6, ?7)
 ON CONFLICT(slug) DO NOTHING;
@@ -0,0 +1,15 @@
+INSERT INTO methodology_version (
+  methodology,
+  version,
+  process,
+  initial_state,
+  created_at
+)
+SELECT id,
+       version,
+       process,
+       initial_state,
+       ?2
+  FROM methodology
+ WHERE slug = ?1
+    ON CONFLICT (methodology, version) DO NOTHING;
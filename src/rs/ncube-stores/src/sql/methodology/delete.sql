@@ -0,0 +1,2 @@
+DELETE FROM methodology
+ WHERE slug = ?1;
@@ -0,0 +1,6 @@
+DELETE FROM methodology_version
+ WHERE methodology IN (
+    SELECT id
+      FROM methodology
+     WHERE slug = ?1
+ );
@@ -0,0 +1,6 @@
+SELECT EXISTS(
+  SELECT i.id
+    FROM investigation i
+    JOIN methodology m ON i.methodology = m.id
+   WHERE m.slug = ?1
+);
@@ -4,6 +4,7 @@ SELECT id,
        description,
        process,
        initial_state,
+       version,
        created_at,
        updated_at
   FROM methodology
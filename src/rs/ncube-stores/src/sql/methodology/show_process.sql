@@ -0,0 +1,3 @@
+SELECT process
+  FROM methodology
+ WHERE slug = ?1;
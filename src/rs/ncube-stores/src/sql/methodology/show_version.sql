@@ -0,0 +1,13 @@
+SELECT m.id AS id,
+       m.title AS title,
+       m.slug AS slug,
+       m.description AS description,
+       v.process AS process,
+       v.initial_state AS initial_state,
+       v.version AS version,
+       m.created_at AS created_at,
+       m.updated_at AS updated_at
+  FROM methodology m
+  JOIN methodology_version v ON v.methodology = m.id
+ WHERE m.slug = ?1
+   AND v.version = ?2;
@@ -0,0 +1,9 @@
+UPDATE methodology
+   SET title = ?1,
+       slug = ?2,
+       description = ?3,
+       process = ?4,
+       initial_state = ?5,
+       version = version + ?6,
+       updated_at = ?7
+ WHERE slug = ?8;
@@ -10,19 +10,14 @@ SELECT u.id AS id,
        u.description AS description,
        u.created_at AS created_at,
        u.fetched_at AS fetched_at,
-       u.data AS data
+       u.data AS data,
+       snippet(unit_fts, 1, char(2), char(3), '…', 32) AS highlight_body,
+       snippet(unit_fts, 2, char(2), char(3), '…', 32) AS highlight_title,
+       snippet(unit_fts, 3, char(2), char(3), '…', 32) AS highlight_description,
+       {key} AS sort_key
   FROM unit_fts
   LEFT JOIN unit u ON u.id = unit_fts.id
-  LEFT JOIN tagged_unit tu ON tu.unit = unit_fts.id
-  LEFT JOIN query_tag qt ON qt.id = tu.query_tag
  WHERE {}
-   AND u.id NOT IN (
-   SELECT u.id
-     FROM unit_fts
-     LEFT JOIN unit u ON u.id = unit_fts.id
-     LEFT JOIN tagged_unit tu ON tu.unit = unit_fts.id
-     LEFT JOIN query_tag qt ON qt.id = tu.query_tag
-    WHERE {}
-    ORDER BY bm25(unit_fts), unit_fts.id ASC LIMIT ?1
- )
- ORDER BY bm25(unit_fts), unit_fts.id ASC LIMIT ?2;
+   AND {after}
+ ORDER BY {order}
+ LIMIT ?3 OFFSET ?4;
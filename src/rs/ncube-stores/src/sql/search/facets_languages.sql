@@ -0,0 +1,8 @@
+SELECT lower(u.language) AS value,
+       count(DISTINCT u.id) AS count
+  FROM unit_fts
+  LEFT JOIN unit u ON u.id = unit_fts.id
+ WHERE {}
+   AND lower(u.language) IS NOT NULL
+ GROUP BY value
+ ORDER BY count DESC, value ASC;
@@ -0,0 +1,9 @@
+SELECT m.type AS value,
+       count(DISTINCT u.id) AS count
+  FROM unit_fts
+  LEFT JOIN unit u ON u.id = unit_fts.id
+  JOIN media m ON m.unit = u.id
+ WHERE {}
+   AND m.type IS NOT NULL
+ GROUP BY value
+ ORDER BY count DESC, value ASC;
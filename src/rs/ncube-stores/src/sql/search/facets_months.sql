@@ -0,0 +1,8 @@
+SELECT strftime('%Y-%m', u.created_at) AS value,
+       count(DISTINCT u.id) AS count
+  FROM unit_fts
+  LEFT JOIN unit u ON u.id = unit_fts.id
+ WHERE {}
+   AND strftime('%Y-%m', u.created_at) IS NOT NULL
+ GROUP BY value
+ ORDER BY value ASC;
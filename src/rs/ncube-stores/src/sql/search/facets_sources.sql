@@ -0,0 +1,8 @@
+SELECT u.source AS value,
+       count(DISTINCT u.id) AS count
+  FROM unit_fts
+  LEFT JOIN unit u ON u.id = unit_fts.id
+ WHERE {}
+   AND u.source IS NOT NULL
+ GROUP BY value
+ ORDER BY count DESC, value ASC;
@@ -0,0 +1,10 @@
+SELECT qt.label AS value,
+       count(DISTINCT u.id) AS count
+  FROM unit_fts
+  LEFT JOIN unit u ON u.id = unit_fts.id
+  JOIN tagged_unit tu ON tu.unit = u.id
+  JOIN query_tag qt ON qt.id = tu.query_tag
+ WHERE {}
+   AND qt.label IS NOT NULL
+ GROUP BY value
+ ORDER BY count DESC, value ASC;
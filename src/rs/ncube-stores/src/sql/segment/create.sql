@@ -2,8 +2,9 @@ INSERT INTO segment (
   query,
   title,
   slug,
+  sort,
   created_at,
   updated_at
 ) VALUES
-(?1, ?2, ?3, ?4, ?5)
+(?1, ?2, ?3, ?4, ?5, ?6)
 ON CONFLICT(slug) DO NOTHING;
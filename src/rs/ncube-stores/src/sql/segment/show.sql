@@ -2,6 +2,7 @@ SELECT id,
        query,
        title,
        slug,
+       sort,
        created_at,
        updated_at
   FROM segment
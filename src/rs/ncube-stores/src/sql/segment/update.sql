@@ -2,5 +2,6 @@ UPDATE segment
    SET query = ?1,
        title = ?2,
        slug = ?3,
-       updated_at = ?4
- WHERE slug = ?5;
+       sort = ?4,
+       updated_at = ?5
+ WHERE slug = ?6;
@@ -2,8 +2,5 @@ SELECT id,
        type,
        term
   FROM query
- WHERE id NOT IN (
-   SELECT id FROM query
-   ORDER BY id DESC LIMIT ?1
- )
- ORDER BY id DESC LIMIT ?2;
+ WHERE ?1 IS NULL OR id < ?1
+ ORDER BY id DESC LIMIT ?2 OFFSET ?3;
@@ -0,0 +1,63 @@
+-- The tables that sugarcube creates in a workspace database before the
+-- workspace migrations run, as far as the stores query them.
+CREATE TABLE unit (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  id_hash TEXT NOT NULL UNIQUE,
+  id_fields JSON,
+  content_hash TEXT NOT NULL,
+  content_fields JSON,
+  source TEXT NOT NULL,
+  unit_id TEXT,
+  body TEXT,
+  href TEXT,
+  author TEXT,
+  title TEXT,
+  description TEXT,
+  language TEXT,
+  created_at TEXT,
+  fetched_at TEXT NOT NULL,
+  data JSON
+);
+
+CREATE TABLE media (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  unit INTEGER NOT NULL REFERENCES unit(id),
+  id_hash TEXT NOT NULL,
+  type TEXT NOT NULL,
+  term TEXT NOT NULL,
+  data JSON
+);
+
+CREATE TABLE download (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  unit INTEGER NOT NULL REFERENCES unit(id),
+  id_hash TEXT NOT NULL,
+  type TEXT NOT NULL,
+  term TEXT NOT NULL,
+  md5 TEXT,
+  sha256 TEXT,
+  location TEXT,
+  data JSON
+);
+
+CREATE TABLE query (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  type TEXT NOT NULL,
+  term TEXT NOT NULL
+);
+
+CREATE TABLE query_result (
+  unit INTEGER NOT NULL REFERENCES unit(id),
+  query INTEGER NOT NULL REFERENCES query(id)
+);
+
+CREATE TABLE query_tag (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  label TEXT NOT NULL UNIQUE,
+  description TEXT
+);
+
+CREATE TABLE tagged_unit (
+  unit INTEGER NOT NULL REFERENCES unit(id),
+  query_tag INTEGER NOT NULL REFERENCES query_tag(id)
+);
@@ -0,0 +1,36 @@
+-- A handful of units to run search queries against. Units 1 and 5, and
+-- units 3 and 6 are duplicates of each other.
+INSERT INTO unit (id, id_hash, content_hash, source, body, author, language, created_at, fetched_at)
+VALUES (1, 'h1', 'c1', 'youtube_video', 'protest in aleppo', 'alice', 'en', '2020-01-15T10:00:00.000Z', '2020-01-16T10:00:00.000Z'),
+       (2, 'h2', 'c2', 'twitter_tweet', 'airstrike in aleppo', 'bob', 'ar', '2020-03-01T10:00:00.000Z', '2020-03-02T10:00:00.000Z'),
+       (3, 'h3', 'c3', 'twitter_tweet', 'protest in idlib', 'alice', 'en', '2020-03-31T10:00:00.000Z', '2020-04-01T10:00:00.000Z'),
+       (4, 'h4', 'c4', 'youtube_video', 'airstrike in idlib', NULL, 'ar', '2020-05-10T10:00:00.000Z', '2020-05-11T10:00:00.000Z'),
+       (5, 'h5', 'c1', 'youtube_video', 'protest in aleppo', 'zoë', 'en', '2020-01-15T10:00:00.000Z', '2020-01-16T10:00:00.000Z'),
+       (6, 'h6', 'c3', 'http_url', 'protest in idlib', 'bob', 'en', '2020-04-01T10:00:00.000Z', '2020-04-02T10:00:00.000Z'),
+       (7, 'h7', 'c7', 'http_url', 'weather report', 'bob', 'en', '2020-06-01T10:00:00.000Z', '2020-06-02T10:00:00.000Z');
+
+INSERT INTO media (unit, id_hash, type, term)
+VALUES (1, 'm1', 'video', 'https://youtube.com/watch?v=1'),
+       (2, 'm2', 'image', 'https://twitter.com/2/photo/1'),
+       (2, 'm3', 'video', 'https://twitter.com/2/video/1'),
+       (3, 'm4', 'image', 'https://twitter.com/3/photo/1');
+
+INSERT INTO query_tag (id, label) VALUES (1, 'civilian'), (2, 'verified');
+INSERT INTO tagged_unit (unit, query_tag) VALUES (2, 1), (4, 1), (2, 2);
+
+INSERT INTO duplicate (unit, cluster) VALUES (1, 1), (5, 1), (3, 3), (6, 3);
+
+INSERT INTO segment (id, query, title, slug, created_at, updated_at)
+VALUES (1, 'aleppo', 'Aleppo', 'aleppo', '2021-03-14T15:09:26Z', '2021-03-14T15:09:26Z');
+INSERT INTO investigation (id, title, slug, methodology) VALUES (1, 'Aleppo', 'aleppo', 1);
+
+INSERT INTO verification (id, investigation, segment, unit, state)
+VALUES (1, 1, 1, 1, '{"value":"incoming_data"}'),
+       (2, 1, 1, 2, '{"value":"desk_research"}'),
+       (3, 1, 1, 5, '{"value":"desk_research"}');
+
+INSERT INTO annotation (verification, key, value, name)
+VALUES (2, 'location', '"Aleppo"', 'Location'),
+       (2, 'has-teargas', 'true', 'Has teargas'),
+       (3, 'location', '"Aleppo City"', 'Location'),
+       (3, 'has-teargas', 'false', 'Has teargas');
@@ -1,22 +1,20 @@
-SELECT id,
-       id_hash,
-       id_fields,
-       content_hash,
-       content_fields,
-       source,
-       unit_id,
-       body,
-       href,
-       author,
-       title,
-       description,
-       language,
-       created_at,
-       fetched_at,
-       data
-  FROM unit
- WHERE id NOT IN (
-   SELECT id FROM unit
-   ORDER BY id_hash ASC LIMIT ?1
- )
- ORDER BY id_hash ASC LIMIT ?2;
+SELECT u.id AS id,
+       u.id_hash AS id_hash,
+       u.id_fields AS id_fields,
+       u.content_hash AS content_hash,
+       u.content_fields AS content_fields,
+       u.source AS source,
+       u.unit_id AS unit_id,
+       u.body AS body,
+       u.href AS href,
+       u.author AS author,
+       u.title AS title,
+       u.description AS description,
+       u.language AS language,
+       u.created_at AS created_at,
+       u.fetched_at AS fetched_at,
+       u.data AS data,
+       {key} AS sort_key
+  FROM unit u
+ WHERE {after}
+ ORDER BY {order} LIMIT ?3 OFFSET ?4;
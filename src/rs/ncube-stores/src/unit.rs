@@ -1,10 +1,12 @@
 use async_trait::async_trait;
-use ncube_data::{Download, Media, QueryTag, Source, Unit};
+use ncube_data::{Cursor, Download, Media, Page, PageRequest, QueryTag, SortOrder, Source, Unit};
 use ncube_db::{errors::DatabaseError, http, sqlite, Database};
 use rusqlite::{params, Error as RusqliteError};
-use serde_rusqlite::from_rows;
+use serde_rusqlite::{from_row, from_rows};
 use tracing::instrument;
 
+use crate::UnitOrderSqlite;
+
 pub fn unit_store(wrapped_db: Database) -> Box<dyn UnitStore + Send + Sync> {
     match wrapped_db {
         Database::Sqlite(db) => Box::new(UnitStoreSqlite { db }),
@@ -14,7 +16,8 @@ pub fn unit_store(wrapped_db: Database) -> Box<dyn UnitStore + Send + Sync> {
 
 #[async_trait]
 pub trait UnitStore {
-    async fn list(&self, page: i32, page_size: i32) -> Result<Vec<Unit>, DatabaseError>;
+    async fn list(&self, sort: &SortOrder, page: &PageRequest)
+        -> Result<Page<Unit>, DatabaseError>;
     async fn list_ids(&self, ids: Vec<i32>) -> Result<Vec<Unit>, DatabaseError>;
     async fn show(&self, id: i32) -> Result<Option<Unit>, DatabaseError>;
 }
@@ -27,19 +30,34 @@ pub struct UnitStoreSqlite {
 #[async_trait]
 impl UnitStore for UnitStoreSqlite {
     #[instrument]
-    async fn list(&self, page: i32, page_size: i32) -> Result<Vec<Unit>, DatabaseError> {
+    async fn list(
+        &self,
+        sort: &SortOrder,
+        page: &PageRequest,
+    ) -> Result<Page<Unit>, DatabaseError> {
         let conn = self.db.connection().await?;
-        let mut stmt = conn.prepare_cached(include_str!("../sql/unit/paginate.sql"))?;
+        // Units that are not part of a search have no rank. The rank is a real
+        // since SQLite reads an integer in `ORDER BY` as a column index.
+        let order = UnitOrderSqlite::new(sort, "0.0");
+        let mut stmt =
+            conn.prepare_cached(&order.to_sql(include_str!("../sql/unit/paginate.sql"), page))?;
         let mut stmt2 = conn.prepare_cached(include_str!("../sql/unit/list-media.sql"))?;
         let mut stmt3 = conn.prepare_cached(include_str!("../sql/unit/list-downloads.sql"))?;
         let mut stmt4 = conn.prepare_cached(include_str!("../sql/unit/list-sources.sql"))?;
         let mut stmt5 = conn.prepare_cached(include_str!("../sql/unit/list-tags.sql"))?;
 
-        let offset = page * page_size;
         let mut units: Vec<Unit> = vec![];
+        let mut last: Option<Cursor> = None;
+
+        let mut params = order.params(page);
+        params.push(Box::new(page.size));
+        params.push(Box::new(page.offset()));
+
+        let mut rows = stmt.query(params)?;
 
-        for unit in from_rows::<Unit>(stmt.query(params![offset as i32, page_size as i32])?) {
-            let mut unit = unit?;
+        while let Some(row) = rows.next()? {
+            let mut unit = from_row::<Unit>(row)?;
+            last = Some(order.cursor(row, unit.id)?);
             let mut medias: Vec<Media> = vec![];
             let mut downloads: Vec<Download> = vec![];
             let mut sources: Vec<Source> = vec![];
@@ -69,7 +87,9 @@ impl UnitStore for UnitStoreSqlite {
             units.push(unit);
         }
 
-        Ok(units)
+        let next = page.next(units.len(), last);
+
+        Ok(Page { data: units, next })
     }
 
     #[instrument]
@@ -107,6 +127,8 @@ impl UnitStore for UnitStoreSqlite {
                     downloads: vec![],
                     sources: vec![],
                     tags: vec![],
+                    highlights: None,
+                    rank: None,
                 })
             }) {
                 Ok(value) => value,
@@ -175,6 +197,8 @@ impl UnitStore for UnitStoreSqlite {
                 downloads: vec![],
                 sources: vec![],
                 tags: vec![],
+                highlights: None,
+                rank: None,
             })
         }) {
             Ok(value) => value,
@@ -220,18 +244,36 @@ pub struct UnitStoreHttp {
 #[async_trait]
 impl UnitStore for UnitStoreHttp {
     #[instrument]
-    async fn list(&self, page: i32, page_size: i32) -> Result<Vec<Unit>, DatabaseError> {
+    async fn list(
+        &self,
+        sort: &SortOrder,
+        page: &PageRequest,
+    ) -> Result<Page<Unit>, DatabaseError> {
         let mut url = self.client.url.clone();
         url.set_path(&format!(
             "/api/workspaces/{}/data",
             self.client.workspace.slug
         ));
+        // The cursor parameter is always sent, even if it is empty, to receive
+        // the next cursor as part of the response.
         url.query_pairs_mut()
             .clear()
-            .append_pair("page", &page.to_string())
-            .append_pair("size", &page_size.to_string());
-
-        let data: Vec<Unit> = self.client.get(url).await?.unwrap_or_else(|| vec![]);
+            .append_pair("page", &page.page.to_string())
+            .append_pair("size", &page.size.to_string())
+            .append_pair("sort", &sort.to_string())
+            .append_pair(
+                "cursor",
+                &page
+                    .after
+                    .as_ref()
+                    .map(|cursor| cursor.to_string())
+                    .unwrap_or_default(),
+            );
+
+        let data: Page<Unit> = self.client.get(url).await?.unwrap_or_else(|| Page {
+            data: vec![],
+            next: None,
+        });
 
         Ok(data)
     }
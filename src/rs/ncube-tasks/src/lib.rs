@@ -1,15 +1,36 @@
-use ncube_cache::GuardedCache;
-use ncube_data::{Task, Workspace, WorkspaceKind};
+use chrono::Utc;
+use futures::{stream, StreamExt};
+use ncube_cache::{GuardedCache, MediaCache};
+use ncube_crypto::file_digests;
+use ncube_data::{
+    IntegrityCheck, IntegrityStatus, MediaMetadata, MediaType, Task, Workspace, WorkspaceKind,
+};
+use ncube_db::{http::RemoteFile, Database};
+use ncube_dedup::{cluster, DEFAULT_THRESHOLD};
 use ncube_errors::HostError;
 use ncube_fs::{expand_tilde, mkdirp, unzip_workspace};
+use ncube_media::{
+    image_file_hash, image_file_metadata, image_file_thumbnail, video_file_hashes,
+    video_file_metadata, video_file_thumbnails,
+};
+use ncube_stores::{duplicate_store, integrity_store, media_store};
 use remove_dir_all::remove_dir_all;
 use std::fmt::Debug;
+use std::fs::File;
 use std::path::{Path, PathBuf};
-use tokio::{process::Command, sync::mpsc::Sender};
-use tracing::{debug, info, instrument};
+use tokio::{
+    io::AsyncWriteExt,
+    process::Command,
+    sync::mpsc::{self, Sender},
+    task,
+};
+use tracing::{debug, info, instrument, warn};
 
 pub type TaskCache = GuardedCache<Task>;
 
+/// The maximum number of frames that are hashed for every video.
+const VIDEO_FRAMES: usize = 60;
+
 #[instrument]
 fn env_path(workspace_path: &PathBuf) -> String {
     vec![
@@ -145,3 +166,492 @@ pub async fn run_data_process(
         )),
     }
 }
+
+#[instrument]
+pub async fn hash_media(
+    workspace: Workspace,
+    database: Database,
+    tx: &mut Sender<String>,
+) -> Result<(), HostError> {
+    match workspace.kind {
+        WorkspaceKind::Local(location) => {
+            let expanded_path = expand_tilde(location)
+                .ok_or_else(|| HostError::General("Failed to expand path".into()))?;
+            let ffmpeg = expanded_path.join("dist/ffmpeg/ffmpeg");
+
+            let media_store = media_store(database);
+            let files = media_store
+                .unhashed()
+                .await
+                .map_err(|e| HostError::General(e.to_string()))?;
+
+            tx.send(format!("Hashing {} downloads.", files.len()))
+                .await
+                .map_err(|e| HostError::General(format!("Failed to send notification: {}", e)))?;
+
+            let mut failed = 0;
+
+            for file in files {
+                let path = expanded_path.join(&file.location);
+
+                let hashes = match file.kind {
+                    MediaType::Image => {
+                        // Decoding large images takes a while, so we don't
+                        // block the runtime.
+                        let path = path.clone();
+                        task::spawn_blocking(move || image_file_hash(&path))
+                            .await
+                            .map_err(|e| HostError::General(e.to_string()))?
+                            .map(|hash| vec![hash])
+                    }
+                    MediaType::Video => video_file_hashes(&ffmpeg, &path, VIDEO_FRAMES).await,
+                    MediaType::Url => continue,
+                };
+
+                match hashes {
+                    Ok(hashes) => media_store
+                        .save_hashes(file.download, &hashes)
+                        .await
+                        .map_err(|e| HostError::General(e.to_string()))?,
+                    Err(e) => {
+                        warn!("Failed to hash {:?}: {}", path, e);
+                        failed += 1;
+                    }
+                }
+            }
+
+            if failed > 0 {
+                tx.send(format!("Failed to hash {} downloads.", failed))
+                    .await
+                    .map_err(|e| {
+                        HostError::General(format!("Failed to send notification: {}", e))
+                    })?;
+            }
+
+            Ok(())
+        }
+        _ => Err(HostError::General(
+            "Only local workspaces can run this task".into(),
+        )),
+    }
+}
+
+/// The location of the thumbnail of a download, relative to the workspace
+/// directory. `file_path` is the location of the download below the `data`
+/// directory. Images only have a thumbnail, videos have a poster frame as
+/// thumbnail and a contact sheet.
+pub fn thumbnail_location(file_path: &str, sheet: bool) -> String {
+    if sheet {
+        format!("thumbnails/{}.sheet.jpg", file_path)
+    } else {
+        format!("thumbnails/{}.jpg", file_path)
+    }
+}
+
+/// Generate the missing thumbnails of all downloaded images and videos.
+#[instrument]
+pub async fn generate_thumbnails(
+    workspace: Workspace,
+    database: Database,
+    tx: &mut Sender<String>,
+) -> Result<(), HostError> {
+    match workspace.kind {
+        WorkspaceKind::Local(location) => {
+            let expanded_path = expand_tilde(location)
+                .ok_or_else(|| HostError::General("Failed to expand path".into()))?;
+            let ffmpeg = expanded_path.join("dist/ffmpeg/ffmpeg");
+
+            let media_store = media_store(database);
+            let files = media_store
+                .files()
+                .await
+                .map_err(|e| HostError::General(e.to_string()))?;
+
+            let mut generated = 0;
+            let mut failed = 0;
+
+            for file in files {
+                let file_path = file.location.trim_start_matches("data/");
+                let path = expanded_path.join(&file.location);
+                let thumbnail = expanded_path.join(thumbnail_location(file_path, false));
+                let sheet = expanded_path.join(thumbnail_location(file_path, true));
+
+                let exists = match file.kind {
+                    MediaType::Video => thumbnail.exists() && sheet.exists(),
+                    _ => thumbnail.exists(),
+                };
+                if exists {
+                    continue;
+                }
+
+                if let Some(parent) = thumbnail.parent() {
+                    mkdirp(parent)?;
+                }
+
+                let result = match file.kind {
+                    MediaType::Image => {
+                        // Decoding large images takes a while, so we don't
+                        // block the runtime.
+                        let (path, thumbnail) = (path.clone(), thumbnail.clone());
+                        task::spawn_blocking(move || image_file_thumbnail(&path, &thumbnail))
+                            .await
+                            .map_err(|e| HostError::General(e.to_string()))?
+                    }
+                    MediaType::Video => {
+                        video_file_thumbnails(&ffmpeg, &path, &thumbnail, &sheet).await
+                    }
+                    MediaType::Url => continue,
+                };
+
+                match result {
+                    Ok(()) => generated += 1,
+                    Err(e) => {
+                        warn!("Failed to generate thumbnails of {:?}: {}", path, e);
+                        failed += 1;
+                    }
+                }
+            }
+
+            tx.send(format!("Generated thumbnails of {} downloads.", generated))
+                .await
+                .map_err(|e| HostError::General(format!("Failed to send notification: {}", e)))?;
+
+            if failed > 0 {
+                tx.send(format!(
+                    "Failed to generate thumbnails of {} downloads.",
+                    failed
+                ))
+                .await
+                .map_err(|e| HostError::General(format!("Failed to send notification: {}", e)))?;
+            }
+
+            Ok(())
+        }
+        _ => Err(HostError::General(
+            "Only local workspaces can run this task".into(),
+        )),
+    }
+}
+
+/// Cluster all units of a workspace by their duplicates and replace the
+/// previous clusters.
+#[instrument]
+pub async fn detect_duplicates(
+    workspace: Workspace,
+    database: Database,
+    tx: &mut Sender<String>,
+) -> Result<(), HostError> {
+    match workspace.kind {
+        WorkspaceKind::Local(_) => {
+            let duplicate_store = duplicate_store(database);
+            let documents = duplicate_store
+                .documents()
+                .await
+                .map_err(|e| HostError::General(e.to_string()))?;
+
+            tx.send(format!("Comparing {} units.", documents.len()))
+                .await
+                .map_err(|e| HostError::General(format!("Failed to send notification: {}", e)))?;
+
+            // Signing and comparing all units of a large workspace takes a
+            // while, so we don't block the runtime.
+            let clusters = task::spawn_blocking(move || cluster(&documents, DEFAULT_THRESHOLD))
+                .await
+                .map_err(|e| HostError::General(e.to_string()))?;
+
+            duplicate_store
+                .save_clusters(&clusters)
+                .await
+                .map_err(|e| HostError::General(e.to_string()))?;
+
+            tx.send(format!("Found {} clusters of duplicates.", clusters.len()))
+                .await
+                .map_err(|e| HostError::General(format!("Failed to send notification: {}", e)))?;
+
+            Ok(())
+        }
+        _ => Err(HostError::General(
+            "Only local workspaces can run this task".into(),
+        )),
+    }
+}
+
+/// The embedded metadata of the downloaded image or video at `location`,
+/// relative to the workspace directory. The metadata is extracted and stored
+/// the first time it is requested. Other files, and files that fail to be
+/// extracted, have no metadata.
+#[instrument]
+pub async fn extract_metadata(
+    workspace: Workspace,
+    database: Database,
+    location: &str,
+) -> Result<Option<MediaMetadata>, HostError> {
+    match workspace.kind {
+        WorkspaceKind::Local(workspace_location) => {
+            let media_store = media_store(database);
+
+            let file = match media_store
+                .file(location)
+                .await
+                .map_err(|e| HostError::General(e.to_string()))?
+            {
+                Some(file) => file,
+                None => return Ok(None),
+            };
+
+            if let Some(metadata) = media_store
+                .metadata(file.download)
+                .await
+                .map_err(|e| HostError::General(e.to_string()))?
+            {
+                return Ok(Some(metadata));
+            }
+
+            let expanded_path = expand_tilde(workspace_location)
+                .ok_or_else(|| HostError::General("Failed to expand path".into()))?;
+            let ffmpeg = expanded_path.join("dist/ffmpeg/ffmpeg");
+            let path = expanded_path.join(&file.location);
+
+            let metadata = match file.kind {
+                MediaType::Image => task::spawn_blocking(move || image_file_metadata(&path))
+                    .await
+                    .map_err(|e| HostError::General(e.to_string()))?,
+                MediaType::Video => video_file_metadata(&ffmpeg, &path).await,
+                MediaType::Url => return Ok(None),
+            };
+
+            match metadata {
+                Ok(metadata) => {
+                    media_store
+                        .save_metadata(file.download, &metadata)
+                        .await
+                        .map_err(|e| HostError::General(e.to_string()))?;
+
+                    Ok(Some(metadata))
+                }
+                Err(e) => {
+                    warn!("Failed to extract the metadata of {}: {}", location, e);
+                    Ok(None)
+                }
+            }
+        }
+        _ => Err(HostError::General(
+            "Only local workspaces can run this task".into(),
+        )),
+    }
+}
+
+/// Recompute the digests of all downloads of a workspace and compare them to
+/// the digests that were recorded when the files were downloaded. The result
+/// is recorded as a new integrity report, and the id of the report is
+/// returned.
+#[instrument]
+pub async fn verify_downloads(
+    workspace: Workspace,
+    database: Database,
+    tx: &mut Sender<String>,
+) -> Result<i32, HostError> {
+    match workspace.kind {
+        WorkspaceKind::Local(location) => {
+            let expanded_path = expand_tilde(location)
+                .ok_or_else(|| HostError::General("Failed to expand path".into()))?;
+
+            let created_at = Utc::now();
+            let integrity_store = integrity_store(database);
+            let files = integrity_store
+                .downloads()
+                .await
+                .map_err(|e| HostError::General(e.to_string()))?;
+
+            tx.send(format!("Verifying {} downloads.", files.len()))
+                .await
+                .map_err(|e| HostError::General(format!("Failed to send notification: {}", e)))?;
+
+            let mut checks: Vec<IntegrityCheck> = vec![];
+
+            for file in files {
+                let path = expanded_path.join(&file.location);
+
+                let digests = task::spawn_blocking(move || match File::open(&path) {
+                    Ok(reader) => file_digests(reader).map(Some),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(e),
+                })
+                .await
+                .map_err(|e| HostError::General(e.to_string()))??;
+
+                checks.push(IntegrityCheck::new(file, digests));
+            }
+
+            let failed = checks
+                .iter()
+                .filter(|check| {
+                    check.status == IntegrityStatus::Missing
+                        || check.status == IntegrityStatus::Altered
+                })
+                .count();
+
+            let report = integrity_store
+                .create(&created_at, &checks)
+                .await
+                .map_err(|e| HostError::General(e.to_string()))?;
+
+            tx.send(format!(
+                "Verified {} downloads, {} are missing or altered.",
+                checks.len(),
+                failed
+            ))
+            .await
+            .map_err(|e| HostError::General(format!("Failed to send notification: {}", e)))?;
+
+            Ok(report)
+        }
+        _ => Err(HostError::General(
+            "Only local workspaces can run this task".into(),
+        )),
+    }
+}
+
+/// Download a file of a remote workspace into the media cache. The digest of
+/// the file is verified before it is added to the cache. Nothing is done if
+/// the same file is downloaded into the cache already.
+#[instrument]
+pub async fn cache_download(
+    cache: MediaCache,
+    database: Database,
+    file_path: String,
+    sha256: String,
+) -> Result<(), HostError> {
+    let (partial, file) = match cache.reserve(&sha256)? {
+        Some(reserved) => reserved,
+        None => return Ok(()),
+    };
+
+    let result = fill_cache(&cache, database, &file_path, &sha256, &partial, file).await;
+    if result.is_err() {
+        // A failed download must not block the next attempt.
+        let _ = std::fs::remove_file(&partial);
+    }
+
+    result
+}
+
+async fn fill_cache(
+    cache: &MediaCache,
+    database: Database,
+    file_path: &str,
+    sha256: &str,
+    partial: &Path,
+    file: File,
+) -> Result<(), HostError> {
+    let media_store = media_store(database);
+    let mut remote = media_store
+        .download(file_path, None)
+        .await
+        .map_err(|e| HostError::General(e.to_string()))?;
+
+    let mut file = tokio::fs::File::from_std(file);
+    while let Some(chunk) = remote.body.next().await {
+        let chunk = chunk.map_err(|e| HostError::General(e.to_string()))?;
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    drop(file);
+
+    insert_cache(cache, file_path, sha256, partial).await
+}
+
+/// Verify the digest of a completed download and move it into the cache.
+async fn insert_cache(
+    cache: &MediaCache,
+    file_path: &str,
+    sha256: &str,
+    partial: &Path,
+) -> Result<(), HostError> {
+    let path = partial.to_path_buf();
+    let (_, digest) = task::spawn_blocking(move || File::open(&path).and_then(file_digests))
+        .await
+        .map_err(|e| HostError::General(e.to_string()))??;
+
+    if !digest.eq_ignore_ascii_case(sha256) {
+        return Err(HostError::General(format!(
+            "The digest of {} doesn't match, it is not cached.",
+            file_path
+        )));
+    }
+
+    cache.insert(sha256, partial)?;
+    debug!("Cached {} as {}.", file_path, sha256);
+
+    Ok(())
+}
+
+/// Serve a download of a remote workspace and write it into the media cache
+/// at the same time, the file is only fetched once. `reserved` is the partial
+/// file reserved in the cache. The download is cached even if the client
+/// disconnects before it received the whole file.
+pub fn tee_download(
+    cache: MediaCache,
+    reserved: (PathBuf, File),
+    file_path: String,
+    sha256: String,
+    remote: RemoteFile,
+) -> RemoteFile {
+    let RemoteFile {
+        status,
+        headers,
+        mut body,
+    } = remote;
+    let (partial, file) = reserved;
+    let (tx, rx) = mpsc::channel(16);
+
+    tokio::spawn(async move {
+        let mut file = tokio::fs::File::from_std(file);
+        let mut result = Ok(());
+
+        while let Some(chunk) = body.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    result = Err(HostError::General(e.to_string()));
+                    let _ = tx.send(Err(e)).await;
+                    break;
+                }
+            };
+
+            if result.is_ok() {
+                result = file.write_all(&chunk).await.map_err(HostError::from);
+            }
+
+            // A client that went away doesn't stop the download into the
+            // cache.
+            let _ = tx.send(Ok(chunk)).await;
+        }
+        drop(tx);
+
+        if result.is_ok() {
+            result = file.flush().await.map_err(HostError::from);
+        }
+        drop(file);
+
+        if result.is_ok() {
+            result = insert_cache(&cache, &file_path, &sha256, &partial).await;
+        }
+
+        if let Err(e) = result {
+            warn!("Failed to cache download {}: {}", file_path, e);
+            // A failed download must not block the next attempt.
+            let _ = std::fs::remove_file(&partial);
+        }
+    });
+
+    let body = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+
+    RemoteFile {
+        status,
+        headers,
+        body: Box::pin(body),
+    }
+}
@@ -30,6 +30,7 @@ fn main() {
         // FIXME: Handle the Option.unwrap explicitely
         host_db: format!("sqlite://{}", db_path.to_str().unwrap()),
         listen: "127.0.0.1:40666".parse().unwrap(),
+        media_cache: project.cache_dir().join("media"),
     };
 
     let local_listen = config.listen;
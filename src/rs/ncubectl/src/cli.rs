@@ -15,6 +15,18 @@ USAGE:
 FLAGS:
 {flags}";
 
+const HELP_METHODOLOGY: &str = "{about}
+
+USAGE:
+    {usage}
+
+FLAGS:
+{flags}";
+
+const USAGE_METHODOLOGY: &str =
+    "ncubectl methodology export <workspace> <methodology> [-f json|yaml] [-o file]
+    ncubectl methodology import <workspace> <file> [--conflict fail|rename|replace]";
+
 const USAGE_DELETE: &str = "ncubectl delete workspace <workspace> [-y]
     ncubectl delete account <workspace> <email> [-y]";
 
@@ -107,6 +119,242 @@ pub(crate) fn migrate_cli() -> App<'static> {
         )
 }
 
+pub(crate) fn verify_cli() -> App<'static> {
+    App::new("verify")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .setting(AppSettings::DisableVersion)
+        .help_template(HELP)
+        .about("Verify the downloads of a workspace. Exits with 1 if downloads are missing or altered.")
+        .arg(
+            Arg::new("workspace")
+                .value_name("workspace")
+                .required(true)
+                .about("The slug of the workspace to verify.")
+                .takes_value(true),
+        )
+}
+
+pub(crate) fn audit_cli() -> App<'static> {
+    App::new("audit")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .setting(AppSettings::DisableVersion)
+        .help_template(HELP)
+        .about("Replay the custody chain of a workspace. Exits with 1 at the first broken link.")
+        .arg(
+            Arg::new("workspace")
+                .value_name("workspace")
+                .required(true)
+                .about("The slug of the workspace to audit.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("anchor")
+                .short('a')
+                .long("anchor")
+                .value_name("head")
+                .about("A head of the chain that was recorded earlier. Exits with 1 if it isn't an intact link.")
+                .takes_value(true),
+        )
+}
+
+pub(crate) fn integrity_cli() -> App<'static> {
+    App::new("integrity")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .setting(AppSettings::DisableVersion)
+        .help_template(HELP)
+        .about("Print the integrity reports of a workspace.")
+        .arg(
+            Arg::new("workspace")
+                .value_name("workspace")
+                .required(true)
+                .about("The slug of the workspace.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("report")
+                .value_name("report")
+                .about("Print the missing and altered downloads of this report.")
+                .takes_value(true),
+        )
+}
+
+pub(crate) fn cache_cli() -> App<'static> {
+    App::new("cache")
+        .setting(AppSettings::DisableVersion)
+        .help_template(HELP)
+        .about("Print or purge the media cache of remote workspaces.")
+        .arg(
+            Arg::new("modifier")
+                .value_name("modifier")
+                .possible_values(&["purge"])
+                .about("Remove all cached files.")
+                .takes_value(true),
+        )
+}
+
+pub(crate) fn export_cli() -> App<'static> {
+    App::new("export")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .setting(AppSettings::DisableVersion)
+        .help_template(HELP)
+        .about("Export the units of a search, a segment or an investigation segment.")
+        .arg(
+            Arg::new("workspace")
+                .value_name("workspace")
+                .required(true)
+                .about("The slug of the workspace.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("query")
+                .short('q')
+                .long("query")
+                .value_name("query")
+                .about("Export the units that match this search query.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .value_name("sort")
+                .possible_values(&[
+                    "relevance",
+                    "created_at_asc",
+                    "created_at_desc",
+                    "fetched_at_asc",
+                    "fetched_at_desc",
+                    "author",
+                    "source",
+                ])
+                .about("The order of the units of a search.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("segment")
+                .short('s')
+                .long("segment")
+                .value_name("segment")
+                .about("Export the units of this segment.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("investigation")
+                .short('i')
+                .long("investigation")
+                .value_name("investigation")
+                .requires("segment")
+                .about("Export the units of the segment in this investigation.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("state")
+                .long("state")
+                .value_name("state")
+                .requires("investigation")
+                .about("Export only the units of the investigation in this state.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("format")
+                .short('f')
+                .long("format")
+                .value_name("format")
+                .possible_values(&["csv", "ndjson"])
+                .about("The format of the export, defaults to csv.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("columns")
+                .short('c')
+                .long("columns")
+                .value_name("columns")
+                .about("A comma separated list of columns, defaults to all columns.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("file")
+                .about("Write the export to this file instead of stdout.")
+                .takes_value(true),
+        )
+}
+
+pub(crate) fn methodology_cli() -> App<'static> {
+    App::new("methodology")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .setting(AppSettings::DisableVersion)
+        .help_template(HELP_METHODOLOGY)
+        .about("Export or import methodologies as YAML or JSON files.")
+        .override_usage(USAGE_METHODOLOGY)
+        .subcommand(
+            App::new("export")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .setting(AppSettings::DisableVersion)
+                .about("Export a methodology of a workspace.")
+                .arg(
+                    Arg::new("workspace")
+                        .value_name("workspace")
+                        .required(true)
+                        .about("The slug of the workspace.")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("methodology")
+                        .value_name("methodology")
+                        .required(true)
+                        .about("The slug of the methodology.")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .value_name("format")
+                        .possible_values(&["json", "yaml"])
+                        .about("The format of the file, defaults to the extension of the output file or json.")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("file")
+                        .about("Write the methodology to this file instead of stdout.")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("import")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .setting(AppSettings::DisableVersion)
+                .about("Import a methodology file into a workspace.")
+                .arg(
+                    Arg::new("workspace")
+                        .value_name("workspace")
+                        .required(true)
+                        .about("The slug of the workspace.")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("file")
+                        .value_name("file")
+                        .required(true)
+                        .about("The YAML or JSON file of the methodology.")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("conflict")
+                        .long("conflict")
+                        .value_name("conflict")
+                        .possible_values(&["fail", "rename", "replace"])
+                        .about("What to do if the methodology exists already, defaults to fail.")
+                        .takes_value(true),
+                ),
+        )
+}
+
 pub(crate) fn connection_cli() -> App<'static> {
     App::new("connection")
         .setting(AppSettings::ArgRequiredElseHelp)
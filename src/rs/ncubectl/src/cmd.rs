@@ -1,8 +1,14 @@
+use futures::StreamExt;
 use ncube_crypto as crypto;
-use ncube_data::{DatabaseRequest, WorkspaceKindRequest, WorkspaceRequest};
+use ncube_data::{
+    DatabaseRequest, ExportOptions, ImportConflict, IntegrityReport, IntegrityStatus,
+    MethodologyFormat, WorkspaceKindRequest, WorkspaceRequest,
+};
 use ncube_handlers as handlers;
+use ncube_handlers::export::{UnitExport, UnitSelection};
 use prettytable::{cell, format::FormatBuilder, row, Table};
-use std::io::Write;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use tracing::error;
 
 use crate::fatal;
@@ -121,6 +127,101 @@ pub(crate) async fn migrate(workspace: &str) {
     };
 }
 
+fn print_integrity_report(report: &IntegrityReport) {
+    let table_format = FormatBuilder::new().padding(0, 2).build();
+    let mut table = Table::new();
+    table.set_format(table_format);
+
+    table.add_row(row![
+        "REPORT",
+        "CREATED_AT",
+        "TOTAL",
+        "INTACT",
+        "MISSING",
+        "ALTERED",
+        "UNVERIFIED"
+    ]);
+    table.add_row(row![
+        report.id,
+        report.created_at.to_rfc3339(),
+        report.total,
+        report.intact,
+        report.missing,
+        report.altered,
+        report.unverified
+    ]);
+    table.printstd();
+
+    if report.checks.is_empty() {
+        return;
+    }
+
+    let mut table = Table::new();
+    table.set_format(table_format);
+
+    table.add_row(row!["UNIT", "STATUS", "LOCATION"]);
+    for check in &report.checks {
+        table.add_row(row![check.unit, check.status, check.location]);
+    }
+    println!();
+    table.printstd();
+}
+
+pub(crate) async fn verify(workspace: &str) {
+    let report = handlers::workspace::run_download_verification(workspace)
+        .await
+        .unwrap_or_else(|e| fatal!("failed to verify downloads: {}", e.to_string()));
+
+    print_integrity_report(&report);
+
+    if report.checks.iter().any(|check| {
+        check.status == IntegrityStatus::Missing || check.status == IntegrityStatus::Altered
+    }) {
+        std::process::exit(1);
+    }
+}
+
+pub(crate) async fn integrity_reports(workspace: &str) {
+    let reports = handlers::workspace::list_integrity_reports(workspace)
+        .await
+        .unwrap_or_else(|e| fatal!("failed to list integrity reports: {}", e.to_string()));
+
+    let table_format = FormatBuilder::new().padding(0, 2).build();
+    let mut table = Table::new();
+    table.set_format(table_format);
+
+    table.add_row(row![
+        "REPORT",
+        "CREATED_AT",
+        "TOTAL",
+        "INTACT",
+        "MISSING",
+        "ALTERED",
+        "UNVERIFIED"
+    ]);
+    for report in reports {
+        table.add_row(row![
+            report.id,
+            report.created_at.to_rfc3339(),
+            report.total,
+            report.intact,
+            report.missing,
+            report.altered,
+            report.unverified
+        ]);
+    }
+    table.printstd();
+}
+
+pub(crate) async fn integrity_report(workspace: &str, id: i32) {
+    let report = handlers::workspace::show_integrity_report(workspace, id)
+        .await
+        .unwrap_or_else(|e| fatal!("failed to show integrity report: {}", e.to_string()))
+        .unwrap_or_else(|| fatal!("integrity report {} doesn't exist", id));
+
+    print_integrity_report(&report);
+}
+
 pub(crate) async fn get() {
     let settings = handlers::config::show_config_all().await.unwrap();
 
@@ -145,3 +246,159 @@ pub(crate) async fn set(setting: &str, value: &str) {
         .await
         .unwrap();
 }
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+pub(crate) async fn cache() {
+    let (cache, stats) = handlers::config::show_media_cache()
+        .await
+        .unwrap_or_else(|e| fatal!("failed to inspect media cache: {}", e.to_string()));
+
+    let table_format = FormatBuilder::new().padding(0, 2).build();
+    let mut table = Table::new();
+    table.set_format(table_format);
+
+    table.add_row(row!["LOCATION", "FILES", "SIZE", "LIMIT"]);
+    table.add_row(row![
+        cache.root().to_string_lossy(),
+        stats.entries,
+        megabytes(stats.size_in_bytes),
+        megabytes(cache.max_size())
+    ]);
+    table.printstd();
+}
+
+pub(crate) async fn purge_cache() {
+    let purged = handlers::config::purge_media_cache()
+        .await
+        .unwrap_or_else(|e| fatal!("failed to purge media cache: {}", e.to_string()));
+
+    println!(
+        "Removed {} files ({}).",
+        purged.entries,
+        megabytes(purged.size_in_bytes)
+    );
+}
+
+pub(crate) async fn export(
+    workspace: &str,
+    selection: UnitSelection,
+    options: ExportOptions,
+    output: Option<&str>,
+) {
+    let export = handlers::export::export_units(workspace, selection, options)
+        .await
+        .unwrap_or_else(|e| fatal!("failed to export units: {}", e.to_string()));
+
+    let writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(
+            File::create(path)
+                .unwrap_or_else(|e| fatal!("cannot create {}: {}", path, e.to_string())),
+        ),
+        None => Box::new(std::io::stdout()),
+    };
+    let mut writer = BufWriter::new(writer);
+    let mut write = |chunk: &[u8]| {
+        writer
+            .write_all(chunk)
+            .unwrap_or_else(|e| fatal!("failed to write export: {}", e.to_string()));
+    };
+
+    match export {
+        UnitExport::Local(mut stream) => {
+            while let Some(chunk) = stream.next().await {
+                write(
+                    &chunk.unwrap_or_else(|e| fatal!("failed to export units: {}", e.to_string())),
+                );
+            }
+        }
+        UnitExport::Remote(mut file) => {
+            while let Some(chunk) = file.body.next().await {
+                write(
+                    &chunk.unwrap_or_else(|e| fatal!("failed to export units: {}", e.to_string())),
+                );
+            }
+        }
+    }
+
+    writer
+        .flush()
+        .unwrap_or_else(|e| fatal!("failed to write export: {}", e.to_string()));
+}
+
+pub(crate) async fn audit(workspace: &str, anchor: Option<&str>) {
+    let chain = handlers::workspace::verify_custody_chain(workspace, anchor)
+        .await
+        .unwrap_or_else(|e| fatal!("failed to verify custody chain: {}", e.to_string()));
+
+    let table_format = FormatBuilder::new().padding(0, 2).build();
+    let mut table = Table::new();
+    table.set_format(table_format);
+
+    table.add_row(row!["LENGTH", "UNSEALED", "HEAD"]);
+    table.add_row(row![
+        chain.length,
+        chain.unsealed,
+        chain.head.unwrap_or_else(|| "-".into())
+    ]);
+    table.printstd();
+
+    if let Some(link) = chain.broken {
+        let mut table = Table::new();
+        table.set_format(table_format);
+
+        table.add_row(row!["EVENT", "REASON", "EXPECTED", "FOUND"]);
+        table.add_row(row![
+            link.event,
+            link.reason,
+            link.expected.unwrap_or_else(|| "-".into()),
+            link.found.unwrap_or_else(|| "-".into())
+        ]);
+        println!();
+        table.printstd();
+
+        std::process::exit(1);
+    }
+
+    if let Some(false) = chain.anchored {
+        println!();
+        println!("The anchored head is not an intact link of the custody chain.");
+
+        std::process::exit(1);
+    }
+}
+
+pub(crate) async fn export_methodology(
+    workspace: &str,
+    methodology: &str,
+    format: MethodologyFormat,
+    output: Option<&str>,
+) {
+    let file = handlers::workspace::export_methodology(workspace, methodology, format)
+        .await
+        .unwrap_or_else(|e| fatal!("failed to export methodology: {}", e.to_string()));
+
+    match output {
+        Some(path) => std::fs::write(path, &file)
+            .unwrap_or_else(|e| fatal!("cannot write {}: {}", path, e.to_string())),
+        None => std::io::stdout()
+            .write_all(&file)
+            .unwrap_or_else(|e| fatal!("failed to write methodology: {}", e.to_string())),
+    }
+}
+
+pub(crate) async fn import_methodology(workspace: &str, path: &str, conflict: ImportConflict) {
+    let data =
+        std::fs::read(path).unwrap_or_else(|e| fatal!("cannot read {}: {}", path, e.to_string()));
+
+    let methodology = handlers::workspace::import_methodology(workspace, &data, conflict)
+        .await
+        .unwrap_or_else(|e| fatal!("failed to import methodology: {}", e.to_string()));
+
+    println!(
+        "Imported methodology {} (version {}).",
+        methodology.slug, methodology.version
+    );
+}
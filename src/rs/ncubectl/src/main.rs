@@ -1,6 +1,10 @@
 use clap::{crate_authors, crate_version, App, AppSettings, Arg};
 use directories::ProjectDirs;
-use ncube_data::DatabaseRequest;
+use ncube_data::{
+    DatabaseRequest, ExportColumn, ExportFormat, ExportOptions, ImportConflict, MethodologyFormat,
+    SortOrder,
+};
+use ncube_handlers::export::UnitSelection;
 use ncubed::{Application, ApplicationConfig};
 use std::fs::create_dir_all;
 use tracing::Level;
@@ -18,6 +22,14 @@ const USAGE: &str = "ncubectl [-hV -d database -v]
     ncubectl set <setting> <value>
     ncubectl reset [secret]
     ncubectl migrate <workspace>
+    ncubectl verify <workspace>
+    ncubectl integrity <workspace> [<report>]
+    ncubectl audit <workspace> [-a head]
+    ncubectl cache [purge]
+    ncubectl export <workspace> (-q query [--sort sort] | [-i investigation [--state state]] -s segment)
+        [-f csv|ndjson] [-c columns] [-o file]
+    ncubectl methodology export <workspace> <methodology> [-f json|yaml] [-o file]
+    ncubectl methodology import <workspace> <file> [--conflict fail|rename|replace]
     ncubectl delete workspace <workspace> [-y]
     ncubectl delete account <workspace> <email> [-y]
 ";
@@ -81,6 +93,12 @@ async fn main() {
         .subcommand(cli::set_cli())
         .subcommand(cli::reset_cli())
         .subcommand(cli::migrate_cli())
+        .subcommand(cli::verify_cli())
+        .subcommand(cli::integrity_cli())
+        .subcommand(cli::audit_cli())
+        .subcommand(cli::cache_cli())
+        .subcommand(cli::export_cli())
+        .subcommand(cli::methodology_cli())
         .subcommand(cli::connection_cli())
         .subcommand(cli::delete_cli())
         .get_matches();
@@ -109,6 +127,7 @@ async fn main() {
     let config = ApplicationConfig {
         host_db: format!("sqlite://{}", db_path),
         listen: "127.0.0.1:40666".parse().unwrap(),
+        media_cache: project.cache_dir().join("media"),
     };
 
     let app = Application::new(config);
@@ -174,6 +193,120 @@ async fn main() {
             cmd::migrate(&workspace).await;
         }
 
+        Some(("verify", verify_matches)) => {
+            let workspace = verify_matches.value_of("workspace").unwrap();
+
+            cmd::verify(workspace).await;
+        }
+
+        Some(("integrity", integrity_matches)) => {
+            let workspace = integrity_matches.value_of("workspace").unwrap();
+
+            match integrity_matches.value_of("report") {
+                Some(report) => {
+                    let id: i32 = report
+                        .parse()
+                        .unwrap_or_else(|_| fatal!("Invalid report id: {}", report));
+
+                    cmd::integrity_report(workspace, id).await;
+                }
+                None => cmd::integrity_reports(workspace).await,
+            }
+        }
+
+        Some(("audit", audit_matches)) => {
+            let workspace = audit_matches.value_of("workspace").unwrap();
+            let anchor = audit_matches.value_of("anchor");
+
+            cmd::audit(workspace, anchor).await;
+        }
+
+        Some(("cache", cache_matches)) => match cache_matches.value_of("modifier") {
+            Some("purge") => cmd::purge_cache().await,
+            Some(_) => fatal!("Unknown cache modifier."),
+            None => cmd::cache().await,
+        },
+
+        Some(("export", export_matches)) => {
+            let workspace = export_matches.value_of("workspace").unwrap();
+
+            let selection = match (
+                export_matches.value_of("investigation"),
+                export_matches.value_of("segment"),
+                export_matches.value_of("query"),
+            ) {
+                (Some(investigation), Some(segment), _) => UnitSelection::Investigation {
+                    investigation: investigation.to_string(),
+                    segment: segment.to_string(),
+                    state: export_matches.value_of("state").map(String::from),
+                },
+                (None, Some(segment), _) => UnitSelection::Segment(segment.to_string()),
+                (None, None, Some(query)) => {
+                    let sort = match export_matches.value_of("sort") {
+                        Some(sort) => serde_json::from_value::<SortOrder>(sort.into())
+                            .unwrap_or_else(|_| fatal!("Unknown sort order: {}", sort)),
+                        None => SortOrder::default(),
+                    };
+
+                    UnitSelection::Search {
+                        query: query.to_string(),
+                        sort,
+                    }
+                }
+                _ => fatal!("Export requires a query or a segment."),
+            };
+
+            let format = match export_matches.value_of("format") {
+                Some("ndjson") => ExportFormat::Ndjson,
+                _ => ExportFormat::Csv,
+            };
+            let columns =
+                ExportColumn::parse_list(export_matches.value_of("columns").unwrap_or(""))
+                    .unwrap_or_else(|e| fatal!("{}", e.to_string()));
+
+            cmd::export(
+                workspace,
+                selection,
+                ExportOptions { format, columns },
+                export_matches.value_of("output"),
+            )
+            .await;
+        }
+
+        Some(("methodology", methodology_matches)) => match methodology_matches.subcommand() {
+            Some(("export", export_matches)) => {
+                let workspace = export_matches.value_of("workspace").unwrap();
+                let methodology = export_matches.value_of("methodology").unwrap();
+                let output = export_matches.value_of("output");
+
+                let format = match (export_matches.value_of("format"), output) {
+                    (Some("yaml"), _) => MethodologyFormat::Yaml,
+                    (Some(_), _) => MethodologyFormat::Json,
+                    (None, Some(path)) if path.ends_with(".yaml") || path.ends_with(".yml") => {
+                        MethodologyFormat::Yaml
+                    }
+                    (None, _) => MethodologyFormat::Json,
+                };
+
+                cmd::export_methodology(workspace, methodology, format, output).await;
+            }
+
+            Some(("import", import_matches)) => {
+                let workspace = import_matches.value_of("workspace").unwrap();
+                let file = import_matches.value_of("file").unwrap();
+
+                let conflict = match import_matches.value_of("conflict") {
+                    Some("rename") => ImportConflict::Rename,
+                    Some("replace") => ImportConflict::Replace,
+                    _ => ImportConflict::Fail,
+                };
+
+                cmd::import_methodology(workspace, file, conflict).await;
+            }
+
+            _ => unreachable!(),
+        },
+
         Some(("delete", delete_matches)) => match delete_matches.subcommand() {
             Some(("workspace", delete_workspace_matches)) => {
                 let workspace = delete_workspace_matches.value_of("workspace").unwrap();
@@ -12,6 +12,7 @@ use ncube_db::errors::DatabaseError;
 use ncube_errors::HostError;
 use ncube_http_api::start_http_api;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -33,6 +34,8 @@ pub enum ApplicationError {
 pub struct ApplicationConfig {
     pub host_db: String,
     pub listen: SocketAddr,
+    /// The directory of the media cache of remote workspaces.
+    pub media_cache: PathBuf,
 }
 
 #[derive(Debug)]
@@ -46,7 +49,9 @@ impl Application {
     }
 
     async fn setup(&self) -> Result<(), ApplicationError> {
-        let host_actor = HostActor::new(&self.config.host_db)?.start().await?;
+        let host_actor = HostActor::new(&self.config.host_db, self.config.media_cache.clone())?
+            .start()
+            .await?;
         HostActor::register_once(host_actor).await;
         let task_actor = TaskActor::new().await.start().await?;
         TaskActor::register_once(task_actor).await;
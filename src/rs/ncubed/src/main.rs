@@ -68,6 +68,7 @@ async fn main() {
     let config = ApplicationConfig {
         host_db: format!("sqlite://{}", db_path.to_str().unwrap()),
         listen: listen_address.parse().unwrap(),
+        media_cache: project.cache_dir().join("media"),
     };
 
     let app = Application::new(config);
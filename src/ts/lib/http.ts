@@ -20,6 +20,7 @@ import {
   SourceReq,
   SourceTag,
   Unit,
+  VerificationTransition,
   VerifySegmentReq,
   Workspace,
   WorkspaceReq,
@@ -526,10 +527,13 @@ export const listInvestigationSegments = async (
 export const showMethodology = async (
   workspace: string,
   methodology: string,
+  version?: number,
 ): Promise<Methodology> => {
-  const resp = await fetch(
-    `http://127.0.0.1:40666/api/workspaces/${workspace}/methodologies/${methodology}`,
-  );
+  const url =
+    version === undefined
+      ? `http://127.0.0.1:40666/api/workspaces/${workspace}/methodologies/${methodology}`
+      : `http://127.0.0.1:40666/api/workspaces/${workspace}/methodologies/${methodology}/versions/${version}`;
+  const resp = await fetch(url);
 
   const {process, ...rest} = await dataResponse<
     Methodology & {process: string}
@@ -645,6 +649,18 @@ export const listAnnotations = async (
   );
 };
 
+export const listVerificationHistory = async (
+  workspace: string,
+  investigation: string,
+  verification: number,
+): Promise<VerificationTransition[]> => {
+  const resp = await fetch(
+    `http://127.0.0.1:40666/api/workspaces/${workspace}/investigations/${investigation}/verifications/${verification}/history`,
+  );
+
+  return dataResponse(resp);
+};
+
 /*
  * Workspace stats
  */
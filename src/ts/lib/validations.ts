@@ -93,6 +93,15 @@ export const remoteWorkspaceReq = Yup.object({
 export const segmentReq = Yup.object({
   title: Yup.string().defined(),
   query: Yup.string().defined(),
+  sort: Yup.string().oneOf([
+    "relevance",
+    "created_at_asc",
+    "created_at_desc",
+    "fetched_at_asc",
+    "fetched_at_desc",
+    "author",
+    "source",
+  ]),
 });
 
 export const processRunReq = Yup.object({
@@ -452,9 +452,11 @@ export default createMachine<
           investigation: Investigation;
           segment: Segment;
         };
+        // Investigations use the version of the methodology they started with.
         const methodology = await showMethodology(
           workspace.slug,
           investigation.methodology,
+          investigation.methodology_version,
         );
 
         // We ensure that columns are in the right order.
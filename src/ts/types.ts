@@ -101,6 +101,30 @@ export type Source = {
 export type SearchResults<T extends Resource> = {
   data: T[];
   total: number;
+  next?: string;
+};
+
+export type SortOrder =
+  | "relevance"
+  | "created_at_asc"
+  | "created_at_desc"
+  | "fetched_at_asc"
+  | "fetched_at_desc"
+  | "author"
+  | "source";
+
+export type FacetCount = {
+  value: string;
+  count: number;
+};
+
+export type Facets = {
+  tags: FacetCount[];
+  sources: FacetCount[];
+  languages: FacetCount[];
+  years: FacetCount[];
+  months: FacetCount[];
+  media: FacetCount[];
 };
 
 // export type Stats = Record<string, number>;
@@ -151,11 +175,90 @@ export type Unit = Resource & {
   downloads: Download[];
   sources: Source[];
   tags: SourceTag[];
+  highlights?: Highlights;
+  rank?: number;
+};
+
+export type DuplicateCluster = {
+  id: number;
+  units: number[];
+};
+
+export type SimilarMedia = {
+  unit: number;
+  title?: string;
+  similar_to: string;
+  distance: number;
+  download: Download;
+};
+
+export type IntegrityStatus = "intact" | "missing" | "altered" | "unverified";
+
+export type IntegrityCheck = {
+  download: number;
+  unit: number;
+  id_hash: string;
+  location: string;
+  status: IntegrityStatus;
+  md5?: string;
+  sha256?: string;
+};
+
+export type CustodyEventKind =
+  | "fetched"
+  | "downloaded"
+  | "verification"
+  | "annotation";
+
+export type CustodyEvent = {
+  id: number;
+  unit: number;
+  kind: CustodyEventKind;
+  data: Record<string, unknown>;
+  created_at: string;
+};
+
+export type ChainBreak = "altered" | "deleted" | "inserted";
+
+export type BrokenLink = {
+  event: number;
+  reason: ChainBreak;
+  expected?: string;
+  found?: string;
+};
+
+export type CustodyChain = {
+  length: number;
+  unsealed: number;
+  head?: string;
+  broken?: BrokenLink;
+};
+
+export type IntegrityReport = {
+  id: number;
+  created_at: string;
+  total: number;
+  intact: number;
+  missing: number;
+  altered: number;
+  unverified: number;
+  checks: IntegrityCheck[];
+};
+
+export type WorkspaceUnit = Unit & {
+  workspace: string;
+};
+
+export type Highlights = {
+  body?: string;
+  title?: string;
+  description?: string;
 };
 
 export type Segment = SlugResource & {
   title: string;
   query: string;
+  sort?: SortOrder;
   // FIXME: dates should be Date not string.
   created_at: string;
   updated_at: string;
@@ -196,6 +299,7 @@ export type Methodology = {
     MethodologySchema,
     EventObject
   >;
+  version: number;
   created_at: string;
   updated_at: string;
 };
@@ -204,6 +308,7 @@ export type Investigation = SlugResource & {
   title: string;
   description?: string;
   methodology: string;
+  methodology_version: number;
   created_at: string;
   updated_at: string;
 };
@@ -228,6 +333,18 @@ export type Annotation = {
   name: string;
   value: unknown;
   note?: string;
+  author?: string;
+};
+
+export type VerificationTransition = {
+  id: number;
+  verification: number;
+  from_state?: string;
+  to_state: string;
+  event: string;
+  author?: string;
+  note?: string;
+  created_at: string;
 };
 
 export type AnnotationSchema = {
@@ -289,8 +406,25 @@ export type ProcessRunReq = {
   kind: "all" | "new" | "selection";
 };
 
+export type GeoLocation = {
+  latitude: number;
+  longitude: number;
+  altitude?: number;
+};
+
+export type MediaMetadata = {
+  tags: Record<string, string>;
+  xmp?: string;
+  duration?: number;
+  codecs: string[];
+  created_at?: string;
+  location?: GeoLocation;
+};
+
 export type FileMetadata = {
   size_in_bytes: number;
+  sha256?: string;
+  media?: MediaMetadata;
 };
 
 /*